@@ -23,6 +23,10 @@ cargo clippy
 cargo build --workspace
 cargo test --workspace
 
+# Optional features must keep building too
+cargo clippy --all-features
+cargo test --all-features
+
 if [[ -z "${CI+x}" ]]; then
     # Try a Windows build, cross compiles must work
     #
@@ -143,7 +143,7 @@ for target in $targets; do
   # From: https://stackoverflow.com/a/66875783/473672
   SDKROOT=$(xcrun -sdk $CROSSBUILD_MACOS_SDK --show-sdk-path) \
   MACOSX_DEPLOYMENT_TARGET=$(xcrun -sdk $CROSSBUILD_MACOS_SDK --show-sdk-platform-version) \
-    cargo build --release "--target=$target"
+    cargo build --release --features self-update "--target=$target"
 done
 
 # From: https://developer.apple.com/documentation/apple-silicon/building-a-universal-macos-binary#Update-the-Architecture-List-of-Custom-Makefiles
@@ -164,16 +164,19 @@ $LIVE && cp "target/riff-universal-apple-darwin-release" "riff-$EXPECTED_VERSION
 #
 # From: https://timryan.org/2018/07/27/cross-compiling-linux-binaries-from-macos.html
 rustup target add x86_64-unknown-linux-musl
-cargo build --release --target=x86_64-unknown-linux-musl
+cargo build --release --features self-update --target=x86_64-unknown-linux-musl
 $LIVE && cp "target/x86_64-unknown-linux-musl/release/riff" "riff-$NEW_VERSION_NUMBER-x86_64-linux"
 
 # Build a Windows binary on macOS
 #
 # From: https://gist.github.com/Mefistophell/9787e1b6d2d9441c16d2ac79d6a505e6
 rustup target add x86_64-pc-windows-gnu
-cargo build --release --target=x86_64-pc-windows-gnu
+cargo build --release --features self-update --target=x86_64-pc-windows-gnu
 $LIVE && cp "target/x86_64-pc-windows-gnu/release/riff.exe" "riff-$NEW_VERSION_NUMBER-x86_64-windows.exe"
 
+# For verifying downloads in riff --self-update
+$LIVE && shasum -a 256 riff-"$NEW_VERSION_NUMBER"-* > SHA256SUMS
+
 # Mark new release on Github. This implicitly triggers Homebrew deployment and
 # cargo publishing through deployment.yml.
 $LIVE && git push && git push --tags
@@ -187,7 +190,8 @@ $LIVE && cat <<EOM
 Now, create a new release on GitHub:
 <https://github.com/walles/riff/releases/new?tag=$NEW_VERSION_NUMBER>
 
-Attach your "riff" binaries that was just built to the release:
+Attach your "riff" binaries that was just built to the release, together with
+the SHA256SUMS file:
 
 EOM
 if $LIVE; then
@@ -1,8 +1,12 @@
+use crate::palette::Palette;
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Color {
     Default,
     Red,
     Green,
+    DarkRed,
+    DarkGreen,
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
@@ -26,96 +30,168 @@ pub const ANSI_STYLE_NORMAL: AnsiStyle = AnsiStyle {
 };
 
 impl AnsiStyle {
-    /// Renders a (possibly empty) ANSI escape sequence to switch to this style
+    /// Appends a (possibly empty) ANSI escape sequence to switch to this style
     /// from the before style.
-    pub fn from(&self, before: &AnsiStyle) -> String {
+    ///
+    /// Appending rather than returning a `String` saves one allocation per
+    /// token when rendering.
+    pub fn push_from(&self, before: &AnsiStyle, palette: &Palette, rendered: &mut String) {
         if self == before {
-            return String::from("");
+            return;
         }
 
         if self == &ANSI_STYLE_NORMAL {
             // Special case for resetting to default style
-            return String::from("\x1b[0m");
+            rendered.push_str(&palette.normal);
+            return;
         }
 
-        let mut return_me = String::new();
-
         if self.inverse && !before.inverse {
             // Inverse on
-            return_me.push_str("\x1b[7m");
+            rendered.push_str(&palette.inverse_video);
         }
         if !self.inverse && before.inverse {
             // Inverse off
-            return_me.push_str("\x1b[27m");
+            rendered.push_str(&palette.not_inverse_video);
         }
 
         if self.weight != before.weight {
-            match self.weight {
-                Weight::Normal => return_me.push_str("\x1b[22m"),
-                Weight::Bold => return_me.push_str("\x1b[1m"),
-                Weight::Faint => return_me.push_str("\x1b[2m"),
-            }
+            rendered.push_str(palette.weight(self.weight));
         }
 
         if self.color != before.color {
-            match self.color {
-                Color::Default => return_me.push_str("\x1b[39m"),
-                Color::Red => return_me.push_str("\x1b[31m"),
-                Color::Green => return_me.push_str("\x1b[32m"),
-            }
+            rendered.push_str(palette.color(self.color));
         }
-
-        return return_me;
     }
 }
 
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+enum StripState {
+    #[default]
+    Normal,
+    Escape,
+    EscapeBracket,
+}
+
+/// Removes ANSI escape codes from one line of input after another.
+///
+/// When input gets chunked oddly, an escape sequence can start at the end of
+/// one line and end at the start of the next. Then the rest of the sequence
+/// gets removed from the start of the next line, rather than being left
+/// behind as garbage.
+#[derive(Clone, Debug, Default)]
+pub struct AnsiStripper {
+    state: StripState,
+}
+
 // Modifies the input so that all ANSI escape codes are removed
 pub fn remove_ansi_escape_codes(line: &mut Vec<u8>) {
-    enum State {
-        Normal,
-        Escape,
-        EscapeBracket,
-    }
+    AnsiStripper::default().strip(line);
+}
 
-    let mut state = State::Normal;
-    let mut next_index_without_ansi = 0usize;
-
-    for index in 0..line.len() {
-        let byte = line[index];
-        match state {
-            State::Normal => {
-                if byte == b'\x1b' {
-                    state = State::Escape;
-                } else {
-                    line[next_index_without_ansi] = byte;
-                    next_index_without_ansi += 1;
+impl AnsiStripper {
+    /// Modifies the line so that all ANSI escape codes are removed, including
+    /// the end of any sequence left unfinished by the previous line
+    pub fn strip(&mut self, line: &mut Vec<u8>) {
+        let mut state = self.state;
+        let mut next_index_without_ansi = 0usize;
+
+        // A lone ESC at the end of the previous line turned out not to start
+        // an escape sequence, it needs to go somewhere
+        if state == StripState::Escape && line.first().is_some_and(|byte| *byte != b'[') {
+            line.insert(0, b'\x1b');
+            state = StripState::Normal;
+        }
+
+        for index in 0..line.len() {
+            let byte = line[index];
+            match state {
+                StripState::Normal => {
+                    if byte == b'\x1b' {
+                        state = StripState::Escape;
+                    } else {
+                        line[next_index_without_ansi] = byte;
+                        next_index_without_ansi += 1;
+                    }
                 }
-            }
-            State::Escape => {
-                if byte == b'[' {
-                    state = State::EscapeBracket;
-                } else {
-                    // Not an ANSI sequence
-                    state = State::Normal;
-
-                    // Push the characters that we thought were the escape
-                    // sequence's opening
-                    line[next_index_without_ansi] = b'\x1b';
-                    next_index_without_ansi += 1;
-                    line[next_index_without_ansi] = byte;
-                    next_index_without_ansi += 1;
+                StripState::Escape => {
+                    if byte == b'[' {
+                        state = StripState::EscapeBracket;
+                    } else {
+                        // Not an ANSI sequence
+                        state = StripState::Normal;
+
+                        // Push the characters that we thought were the escape
+                        // sequence's opening
+                        line[next_index_without_ansi] = b'\x1b';
+                        next_index_without_ansi += 1;
+                        line[next_index_without_ansi] = byte;
+                        next_index_without_ansi += 1;
+                    }
                 }
-            }
-            State::EscapeBracket => {
-                if !byte.is_ascii_digit() && byte != b';' {
-                    // Neither digit nor semicolon, this marks the end of the sequence
-                    state = State::Normal;
+                StripState::EscapeBracket => {
+                    if !byte.is_ascii_digit() && byte != b';' {
+                        // Neither digit nor semicolon, this marks the end of the sequence
+                        state = StripState::Normal;
+                    }
                 }
             }
         }
+
+        line.truncate(next_index_without_ansi);
+        self.state = state;
     }
+}
 
-    line.truncate(next_index_without_ansi);
+/// Splits a line into ANSI escape sequences and characters. Escape sequences
+/// are returned as `Err`, so that callers can tell them apart from text.
+pub fn parts(line: &str) -> impl Iterator<Item = Result<char, &str>> {
+    let mut rest = line;
+    return std::iter::from_fn(move || {
+        let mut chars = rest.chars();
+        let first = chars.next()?;
+        if first != '\x1b' {
+            rest = chars.as_str();
+            return Some(Ok(first));
+        }
+
+        // Escape sequence, find its end
+        let length = if rest[1..].starts_with('[') {
+            // CSI: Parameters, then a final byte in the @-~ range
+            match rest[2..].find(|c: char| ('@'..='~').contains(&c)) {
+                Some(index) => 2 + index + 1,
+                None => rest.len(),
+            }
+        } else if rest[1..].starts_with(']') {
+            // OSC, like hyperlinks: Ends with BEL or ESC backslash
+            match rest[2..].find(['\x07', '\x1b']) {
+                Some(index) if rest[2 + index..].starts_with('\x07') => 2 + index + 1,
+                Some(index) => (2 + index + 2).min(rest.len()),
+                None => rest.len(),
+            }
+        } else {
+            // Some other escape, assume it's two characters
+            1 + chars.next().map_or(0, char::len_utf8)
+        };
+
+        let escape = &rest[..length];
+        rest = &rest[length..];
+        return Some(Err(escape));
+    });
+}
+
+/// The parameters of a color (SGR) escape sequence from [`parts`], like `1;31`
+/// for `ESC[1;31m`. `None` for other escape sequences, like hyperlinks.
+#[must_use]
+pub fn sgr_parameters(escape: &str) -> Option<&str> {
+    let parameters = escape.strip_prefix("\x1b[")?.strip_suffix('m')?;
+    if !parameters
+        .chars()
+        .all(|c| c.is_ascii_digit() || c == ';' || c == ':')
+    {
+        return None;
+    }
+    return Some(parameters);
 }
 
 #[cfg(test)]
@@ -125,6 +201,28 @@ mod tests {
     #[cfg(test)]
     use pretty_assertions::assert_eq;
 
+    #[test]
+    fn test_parts() {
+        let parts: Vec<Result<char, &str>> =
+            parts("\x1b[1;31ma\x1b]8;;https://x\x07b\x1b]52;c;eA==\x1b\\").collect();
+        assert_eq!(
+            parts,
+            [
+                Err("\x1b[1;31m"),
+                Ok('a'),
+                Err("\x1b]8;;https://x\x07"),
+                Ok('b'),
+                Err("\x1b]52;c;eA==\x1b\\"),
+            ]
+        );
+
+        assert_eq!(sgr_parameters("\x1b[1;31m"), Some("1;31"));
+        assert_eq!(sgr_parameters("\x1b[m"), Some(""));
+        assert_eq!(sgr_parameters("\x1b[2J"), None);
+        assert_eq!(sgr_parameters("\x1b[\x07m"), None);
+        assert_eq!(sgr_parameters("\x1b]8;;https://x\x07"), None);
+    }
+
     #[test]
     fn test_non_sgr() {
         let mut line = b"hel\x1b[0Klo".to_vec();
@@ -145,4 +243,58 @@ mod tests {
         remove_ansi_escape_codes(&mut line);
         assert_eq!(line, b"hello");
     }
+
+    #[test]
+    fn test_sequence_split_across_lines() {
+        let mut stripper = AnsiStripper::default();
+        let mut first = b"hel\x1b[3".to_vec();
+        let mut second = b"3mlo".to_vec();
+        stripper.strip(&mut first);
+        stripper.strip(&mut second);
+        assert_eq!(first, b"hel");
+        assert_eq!(second, b"lo");
+
+        // A lone ESC that turns out not to start a sequence is kept
+        let mut first = b"hel\x1b".to_vec();
+        let mut second = b"lo".to_vec();
+        stripper.strip(&mut first);
+        stripper.strip(&mut second);
+        assert_eq!(first, b"hel");
+        assert_eq!(second, b"\x1blo");
+    }
+
+    /// Splitting the input anywhere must not change what comes out
+    #[test]
+    fn test_fuzz_split_anywhere() {
+        const ALPHABET: &[u8] = b"\x1b[;0123mKab";
+
+        // Xorshift, good enough for making up test input
+        let mut random: u32 = 0x2545_f491;
+        let mut next_random = || {
+            random ^= random << 13;
+            random ^= random >> 17;
+            random ^= random << 5;
+            return random as usize;
+        };
+
+        for _ in 0..500 {
+            let length = next_random() % 20;
+            let input: Vec<u8> = (0..length)
+                .map(|_| ALPHABET[next_random() % ALPHABET.len()])
+                .collect();
+
+            let mut expected = input.clone();
+            remove_ansi_escape_codes(&mut expected);
+
+            for split in 0..=input.len() {
+                let mut stripper = AnsiStripper::default();
+                let mut first = input[..split].to_vec();
+                let mut second = input[split..].to_vec();
+                stripper.strip(&mut first);
+                stripper.strip(&mut second);
+                first.extend(second);
+                assert_eq!(first, expected, "input {input:?} split at {split}");
+            }
+        }
+    }
 }
@@ -0,0 +1,205 @@
+use crate::git_objects::GitObjects;
+use crate::palette::Palette;
+use std::process::{Command, Stdio};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const DAY: u64 = 24 * 60 * 60;
+
+/// Upper age limits for all but the coldest of the palette's blame heat
+/// colors
+const HEAT_AGES: [u64; 4] = [DAY, 7 * DAY, 30 * DAY, 365 * DAY];
+
+/// Parse `git blame --date=unix` output like `7e009865 (Adam 1792040276  1)
+/// code` into the length of the blame column and the commit timestamp.
+///
+/// Uncommitted lines have all-zeros hashes and get `None` for a timestamp.
+fn parse(line: &str) -> Option<(usize, Option<u64>)> {
+    let open = line.find(" (")?;
+    let close = open + line[open..].find(')')?;
+
+    let mut fields = line[open + 2..close].split_whitespace().rev();
+    let _line_number: u64 = fields.next()?.parse().ok()?;
+    let timestamp: u64 = fields.next()?.parse().ok()?;
+
+    let hash = line.split(' ').next()?.trim_start_matches('^');
+    if hash.chars().all(|c| c == '0') {
+        return Some((close + 1, None));
+    }
+    return Some((close + 1, Some(timestamp)));
+}
+
+/// Seconds since the epoch
+#[must_use]
+pub fn now() -> u64 {
+    return SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+}
+
+/// Pick the color for a line last changed at `timestamp`, from the hottest
+/// (most recent) to the coldest color. Uncommitted lines have no timestamp and
+/// are the hottest.
+#[must_use]
+pub fn heat_color(colors: &[String], timestamp: Option<u64>, now: u64) -> Option<&str> {
+    if colors.is_empty() {
+        return None;
+    }
+
+    let heat = match timestamp {
+        None => 0,
+        Some(timestamp) => {
+            let age = now.saturating_sub(timestamp);
+            HEAT_AGES
+                .iter()
+                .position(|max_age| age < *max_age)
+                .unwrap_or(HEAT_AGES.len())
+        }
+    };
+    return Some(&colors[heat.min(colors.len() - 1)]);
+}
+
+/// Color the blame column of a `git blame --date=unix` line by how old the
+/// line is, from hot (recent) to cold (old).
+///
+/// Lines we don't understand are returned unchanged.
+#[must_use]
+pub fn format_blame_line(line: &str, now: u64, palette: &Palette) -> String {
+    let (column_length, timestamp) = match parse(line) {
+        Some(parsed) => parsed,
+        None => return line.to_string(),
+    };
+    let color = match heat_color(&palette.blame_heat, timestamp, now) {
+        Some(color) => color,
+        None => return line.to_string(),
+    };
+
+    return format!(
+        "{}{}{}{}",
+        color,
+        &line[..column_length],
+        palette.normal,
+        &line[column_length..]
+    );
+}
+
+/// Commit timestamps from `git blame --line-porcelain` output, one per line
+fn parse_line_porcelain(porcelain: &str) -> Vec<u64> {
+    return porcelain
+        .lines()
+        .filter_map(|line| line.strip_prefix("committer-time "))
+        .filter_map(|timestamp| timestamp.parse().ok())
+        .collect();
+}
+
+/// When each line of a file was last changed as of `HEAD`, according to `git
+/// blame`. The path is relative to the top of the repository, like in `git
+/// diff` output.
+///
+/// Returns `None` if we aren't in a repository or the file isn't in `HEAD`.
+#[must_use]
+pub fn line_timestamps(git_objects: &GitObjects, path: &str) -> Option<Vec<u64>> {
+    let toplevel = git_objects.toplevel()?;
+
+    let output = Command::new("git")
+        .args(["blame", "--line-porcelain", "HEAD", "--", path].iter())
+        .current_dir(toplevel)
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    return Some(parse_line_porcelain(&String::from_utf8_lossy(
+        &output.stdout,
+    )));
+}
+
+/// Give the first character of a line, the `-` or ` ` gutter, a background
+/// color
+#[must_use]
+pub fn color_gutter(line: &str, background: &str, default_background: &str) -> String {
+    // Skip leading escape sequences
+    let mut index = 0;
+    while line[index..].starts_with("\x1b[") {
+        match line[index + 2..].find(|c: char| ('@'..='~').contains(&c)) {
+            Some(end) => index += 2 + end + 1,
+            None => return line.to_string(),
+        }
+    }
+
+    let gutter_end = index + line[index..].chars().next().map_or(0, char::len_utf8);
+    return format!(
+        "{}{background}{}{default_background}{}",
+        &line[..index],
+        &line[index..gutter_end],
+        &line[gutter_end..]
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::*;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    const NOW: u64 = 1_700_000_000;
+
+    #[test]
+    fn test_heat() {
+        let palette = Palette::default();
+        let hot = format!("7e009865 (Adam {}   1)", NOW - 60);
+        assert_eq!(
+            format_blame_line(&format!("{hot} code"), NOW, &palette),
+            format!("{}{hot}{NORMAL} code", BLAME_HEAT[0])
+        );
+
+        let cold = format!("^023b23f (Bea Cepa {}  12)", NOW - 2 * 365 * DAY);
+        assert_eq!(
+            format_blame_line(&format!("{cold} (x)"), NOW, &palette),
+            format!("{}{cold}{NORMAL} (x)", BLAME_HEAT[4])
+        );
+
+        let uncommitted = format!("00000000 (Not Committed Yet {} 3)", NOW - 400 * DAY);
+        assert_eq!(
+            format_blame_line(&uncommitted, NOW, &palette),
+            format!("{}{uncommitted}{NORMAL}", BLAME_HEAT[0])
+        );
+    }
+
+    #[test]
+    fn test_parse_line_porcelain() {
+        let porcelain = concat!(
+            "7e00986587 1 1 2\n",
+            "author agent\n",
+            "committer-time 1792040276\n",
+            "filename x\n",
+            "\tcode\n",
+            "7e00986587 2 2\n",
+            "committer-time 1792040276\n",
+            "\tcommitter-time 5\n",
+        );
+        assert_eq!(parse_line_porcelain(porcelain), [1792040276, 1792040276]);
+    }
+
+    #[test]
+    fn test_color_gutter() {
+        assert_eq!(
+            color_gutter(&format!("{OLD}-x{NORMAL}"), "<bg>", "</bg>"),
+            format!("{OLD}<bg>-</bg>x{NORMAL}")
+        );
+        assert_eq!(color_gutter(" x", "<bg>", "</bg>"), "<bg> </bg>x");
+        assert_eq!(color_gutter("", "<bg>", "</bg>"), "<bg></bg>");
+    }
+
+    #[test]
+    fn test_not_blame() {
+        let palette = Palette::default();
+        assert_eq!(format_blame_line("", NOW, &palette), "");
+        assert_eq!(
+            format_blame_line("7e009865 (Adam 2026-10-15 1) x", NOW, &palette),
+            "7e009865 (Adam 2026-10-15 1) x"
+        );
+    }
+}
@@ -0,0 +1,106 @@
+use crate::tokenizer::{classify, TokenClass};
+
+/// Returns how many tokens a comment marker starting at `index` takes up, or
+/// zero if there is no comment marker there.
+///
+/// `//` comments start at whitespace. `#` and `--` comments also need
+/// whitespace after them, so that `#[derive]` and `i--;` aren't comments.
+fn comment_marker_length(tokens: &[&str], index: usize) -> usize {
+    let after_whitespace = index == 0 || tokens[index - 1].chars().all(char::is_whitespace);
+    if !after_whitespace {
+        return 0;
+    }
+
+    let followed_by_whitespace = |length: usize| {
+        return tokens
+            .get(index + length)
+            .is_none_or(|next| next.chars().all(char::is_whitespace));
+    };
+
+    let token = tokens[index];
+    let next = tokens.get(index + 1).copied();
+    if token == "/" && next == Some("/") {
+        return 2;
+    }
+    if token == "#" && followed_by_whitespace(1) {
+        return 1;
+    }
+    if token == "-" && next == Some("-") && followed_by_whitespace(2) {
+        return 2;
+    }
+    return 0;
+}
+
+/// For each token, true if it's part of a `//`, `#` or `--` comment at the
+/// end of its line. Comment markers inside of strings don't count.
+#[must_use]
+pub fn trailing_comment_mask(tokens: &[&str]) -> Vec<bool> {
+    let classes = classify(tokens);
+    let mut mask = vec![false; tokens.len()];
+
+    let mut in_comment = false;
+    for index in 0..tokens.len() {
+        if tokens[index] == "\n" {
+            in_comment = false;
+            continue;
+        }
+
+        if !in_comment
+            && classes[index] != TokenClass::String
+            && comment_marker_length(tokens, index) > 0
+        {
+            in_comment = true;
+        }
+        mask[index] = in_comment;
+    }
+
+    return mask;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokenizer::tokenize;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    /// The comment parts of each line of `text`
+    fn comments(text: &str) -> Vec<String> {
+        let tokens = tokenize(text);
+        let mask = trailing_comment_mask(&tokens);
+        return text
+            .lines()
+            .enumerate()
+            .map(|(line_number, _)| {
+                let mut line_index = 0;
+                let mut comment = String::new();
+                for (token, is_comment) in tokens.iter().zip(&mask) {
+                    if *token == "\n" {
+                        line_index += 1;
+                        continue;
+                    }
+                    if line_index == line_number && *is_comment {
+                        comment.push_str(token);
+                    }
+                }
+                return comment;
+            })
+            .collect();
+    }
+
+    #[test]
+    fn test_trailing_comment_mask() {
+        assert_eq!(
+            comments(concat!(
+                "let x = 5; // Five\n",
+                "x = 5  # Five\n",
+                "SELECT 1 -- One\n",
+                "i--;\n",
+                "#[derive(Debug)]\n",
+                "let url = \"http://example.com\";\n",
+            )),
+            ["// Five", "# Five", "-- One", "", "", ""]
+        );
+    }
+}
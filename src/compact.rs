@@ -0,0 +1,112 @@
+use crate::config::Config;
+use std::borrow::Cow;
+
+/// Whether to use the compact layout for this terminal width: always with
+/// `--compact`, and with the `compact-below` setting when the terminal is
+/// narrower than that
+#[must_use]
+pub fn is_compact(forced: bool, width: Option<usize>, compact_below: Option<usize>) -> bool {
+    if forced {
+        return true;
+    }
+    return match (width, compact_below) {
+        (Some(width), Some(compact_below)) => width < compact_below,
+        _ => false,
+    };
+}
+
+/// Remove the `-`, `+` or ` ` prefix from a rendered hunk line, leaving the
+/// colors to tell removed and added lines apart
+#[must_use]
+pub fn drop_prefix(line: &str) -> Cow<'_, str> {
+    // Skip the escape sequences coloring the prefix
+    let mut index = 0;
+    while line[index..].starts_with("\x1b[") {
+        match line[index..].find('m') {
+            Some(end) => index += end + 1,
+            None => break,
+        }
+    }
+
+    return match line[index..].chars().next() {
+        Some('-' | '+' | ' ') => Cow::Owned(format!("{}{}", &line[..index], &line[index + 1..])),
+        _ => Cow::Borrowed(line),
+    };
+}
+
+/// One bold line naming the file, instead of the `---` and `+++` lines
+#[must_use]
+pub fn file_header(old_name: &str, new_name: &str, config: &Config) -> String {
+    let palette = &config.palette;
+    let (name, note) = if new_name == "/dev/null" {
+        (
+            old_name.strip_prefix("a/").unwrap_or(old_name),
+            " (deleted)",
+        )
+    } else if old_name == "/dev/null" {
+        (new_name.strip_prefix("b/").unwrap_or(new_name), " (new)")
+    } else {
+        (new_name.strip_prefix("b/").unwrap_or(new_name), "")
+    };
+
+    return format!("{}{name}{}{note}", palette.bold, palette.normal);
+}
+
+/// `⚠` is double width in many terminals, and narrow ones don't have room to
+/// spare for that
+#[must_use]
+pub fn warning_sign(config: &Config) -> &'static str {
+    if config.compact {
+        return "!";
+    }
+    return "⚠";
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_is_compact() {
+        assert!(is_compact(true, None, None));
+        assert!(is_compact(false, Some(60), Some(80)));
+        assert!(!is_compact(false, Some(80), Some(80)));
+        assert!(!is_compact(false, None, Some(80)));
+        assert!(!is_compact(false, Some(60), None));
+    }
+
+    #[test]
+    fn test_drop_prefix() {
+        assert_eq!(drop_prefix("\x1b[31m-old\x1b[0m"), "\x1b[31mold\x1b[0m");
+        assert_eq!(
+            drop_prefix("\x1b[2m\x1b[32m+\x1b[0mnew"),
+            "\x1b[2m\x1b[32m\x1b[0mnew"
+        );
+        assert_eq!(drop_prefix(" context"), "context");
+        assert_eq!(drop_prefix("\\ No newline"), "\\ No newline");
+    }
+
+    #[test]
+    fn test_file_header() {
+        let config = Config::default();
+        let palette = &config.palette;
+        let bold = |text: &str| {
+            return format!("{}{text}{}", palette.bold, palette.normal);
+        };
+        assert_eq!(
+            file_header("a/src/x.rs", "b/src/y.rs", &config),
+            bold("src/y.rs")
+        );
+        assert_eq!(
+            file_header("/dev/null", "b/x.rs", &config),
+            format!("{} (new)", bold("x.rs"))
+        );
+        assert_eq!(
+            file_header("a/x.rs", "/dev/null", &config),
+            format!("{} (deleted)", bold("x.rs"))
+        );
+    }
+}
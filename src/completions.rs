@@ -0,0 +1,285 @@
+/// Command line options, with what their value is if they take one, and
+/// descriptions for shells that show them. Keep in sync with `HELP_TEXT` in
+/// `main.rs`.
+const OPTIONS: &[(&str, Option<&str>, &str)] = &[
+    ("-b", None, "Ignore changes in amount of whitespace"),
+    (
+        "--ignore-space-change",
+        None,
+        "Ignore changes in amount of whitespace",
+    ),
+    ("--iw", None, "Ignore all whitespace"),
+    (
+        "--word-regex",
+        Some("regex"),
+        "What a word is when refining",
+    ),
+    (
+        "--plain-spaces",
+        None,
+        "Don't highlight spaces between words",
+    ),
+    (
+        "--keep-noisy-highlights",
+        None,
+        "Keep highlights of very changed lines",
+    ),
+    ("--no-pager", None, "Do not page the result"),
+    (
+        "--embedded",
+        None,
+        "Highlight diffs embedded in other output",
+    ),
+    ("--progress", None, "Show progress on stderr while paging"),
+    (
+        "--only-changes",
+        None,
+        "Show only headers and changed lines",
+    ),
+    ("-q", None, "Leave out decorative lines"),
+    ("--quiet", None, "Leave out decorative lines"),
+    (
+        "--input-fd",
+        Some("fd"),
+        "Read the diff from this file descriptor",
+    ),
+    (
+        "--refine-renames",
+        None,
+        "Diff renamed and copied files shown without changes",
+    ),
+    ("--detect-renames", None, "Find renames in plain diffs"),
+    (
+        "--sort",
+        Some("order"),
+        "Order files by path or change size",
+    ),
+    (
+        "--collapse-removed",
+        Some("lines"),
+        "Shorten long runs of removed lines",
+    ),
+    (
+        "--max-line-length",
+        Some("columns"),
+        "Truncate lines wider than this",
+    ),
+    ("--max-memory", Some("size"), "Memory budget, like 512M"),
+    (
+        "--ruler",
+        Some("columns"),
+        "Mark these columns in added lines",
+    ),
+    (
+        "--output",
+        Some("file"),
+        "Also write the output to this file",
+    ),
+    (
+        "--width",
+        Some("columns"),
+        "Terminal width, overrides COLUMNS",
+    ),
+    ("--single-threaded", None, "Refine changes in one thread"),
+    ("--keep-input-colors", None, "Keep colors of non-diff lines"),
+    ("--html-email", None, "Make an HTML email from a patch"),
+    ("--legend", None, "Explain the colors at the top"),
+    (
+        "--dim-repeated-files",
+        None,
+        "Dim files changed by the previous commit",
+    ),
+    (
+        "--new-file-banner",
+        None,
+        "One line headers for added files",
+    ),
+    ("--compact", None, "Layout for narrow terminals"),
+    (
+        "--whitespace-summary",
+        None,
+        "List whitespace errors per file at the end",
+    ),
+    ("--no-header", None, "Leave out file headers"),
+    (
+        "--terminal-title",
+        None,
+        "Show the current file in the title",
+    ),
+    (
+        "--plain-refined",
+        None,
+        "Mark changes with text, not colors",
+    ),
+    ("--describe", None, "Text for screen readers, no colors"),
+    ("--age-heatmap", None, "Color gutters by line age"),
+    ("--list-themes", None, "Show what the themes look like"),
+    (
+        "--paging",
+        Some("when"),
+        "Like delta, never means --no-pager",
+    ),
+    (
+        "--generate-completions",
+        Some("shell"),
+        "Print shell completions",
+    ),
+    (
+        "--generate-config",
+        None,
+        "Print a commented default config file",
+    ),
+    ("--install-git-config", None, "Make riff the git pager"),
+    (
+        "--uninstall-git-config",
+        None,
+        "Stop using riff as the git pager",
+    ),
+    (
+        "--write",
+        None,
+        "With --generate-config, write the config file",
+    ),
+    ("--help", None, "Print help text"),
+    ("--version", None, "Print version number"),
+];
+
+pub const SHELLS: &[&str] = &["bash", "zsh", "fish"];
+
+/// True if `option` is followed by a value, like `--width 100`
+#[must_use]
+pub fn takes_value(option: &str) -> bool {
+    return OPTIONS
+        .iter()
+        .any(|(candidate, value, _)| *candidate == option && value.is_some());
+}
+
+/// Returns a completion script for the named shell, or `None` if we don't
+/// know that shell.
+#[must_use]
+pub fn generate(shell: &str) -> Option<String> {
+    return match shell {
+        "bash" => Some(bash()),
+        "zsh" => Some(zsh()),
+        "fish" => Some(fish()),
+        _ => None,
+    };
+}
+
+#[must_use]
+fn bash() -> String {
+    let options: Vec<&str> = OPTIONS.iter().map(|(option, _, _)| *option).collect();
+    let options = options.join(" ");
+    let shells = SHELLS.join(" ");
+
+    return format!(
+        r#"# bash completion for riff, install with:
+#   riff --generate-completions bash > /usr/share/bash-completion/completions/riff
+_riff() {{
+    local current="${{COMP_WORDS[COMP_CWORD]}}"
+    local previous="${{COMP_WORDS[COMP_CWORD-1]}}"
+
+    if [[ "$previous" == "--generate-completions" ]]; then
+        COMPREPLY=($(compgen -W "{shells}" -- "$current"))
+        return
+    fi
+
+    if [[ "$current" == -* ]]; then
+        COMPREPLY=($(compgen -W "{options}" -- "$current"))
+        return
+    fi
+
+    COMPREPLY=($(compgen -f -- "$current"))
+}}
+complete -o filenames -F _riff riff
+"#
+    );
+}
+
+#[must_use]
+fn zsh() -> String {
+    let mut arguments = String::new();
+    for (option, value, description) in OPTIONS {
+        if *option == "--generate-completions" {
+            let shells = SHELLS.join(" ");
+            arguments.push_str(&format!(
+                "    '{option}[{description}]:shell:({shells})' \\\n"
+            ));
+            continue;
+        }
+        match value {
+            Some(value) => {
+                arguments.push_str(&format!("    '{option}[{description}]:{value}:' \\\n"))
+            }
+            None => arguments.push_str(&format!("    '{option}[{description}]' \\\n")),
+        }
+    }
+
+    return format!(
+        r#"#compdef riff
+# zsh completion for riff, install with:
+#   riff --generate-completions zsh > "${{fpath[1]}}/_riff"
+_arguments \
+{arguments}    '*:file:_files'
+"#
+    );
+}
+
+#[must_use]
+fn fish() -> String {
+    let mut script = String::from(
+        "# fish completion for riff, install with:\n\
+         #   riff --generate-completions fish > ~/.config/fish/completions/riff.fish\n",
+    );
+
+    for (option, value, description) in OPTIONS {
+        let flag = match option.strip_prefix("--") {
+            Some(long) => format!("-l {long}"),
+            None => format!("-s {}", &option[1..]),
+        };
+
+        let mut line = format!("complete -c riff {flag} -d '{description}'");
+        if *option == "--generate-completions" {
+            line.push_str(&format!(" -x -a '{}'", SHELLS.join(" ")));
+        } else if value.is_some() {
+            line.push_str(" -r");
+        }
+        script.push_str(&line);
+        script.push('\n');
+    }
+
+    return script;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_all_shells_generate() {
+        for shell in SHELLS {
+            let script = generate(shell).unwrap();
+            assert!(script.contains("only-changes"), "{}:\n{}", shell, script);
+        }
+
+        assert_eq!(generate("cmd.exe"), None);
+    }
+
+    #[test]
+    fn test_fish_flags() {
+        let script = fish();
+        assert!(script.contains("complete -c riff -s b -d "));
+        assert!(script.contains("complete -c riff -l no-pager -d "));
+        assert!(
+            script.contains("complete -c riff -l sort -d 'Order files by path or change size' -r")
+        );
+    }
+
+    #[test]
+    fn test_takes_value() {
+        assert!(takes_value("--sort"));
+        assert!(takes_value("--width"));
+        assert!(!takes_value("--compact"));
+        assert!(!takes_value("--no-such-option"));
+    }
+}
@@ -0,0 +1,1788 @@
+use crate::ansi::Weight;
+use crate::file_sort::SortOrder;
+use crate::file_timestamp;
+use crate::hunk_header;
+use crate::palette::Palette;
+use crate::theme::{self, Theme};
+use crate::token_style::TokenStyleRule;
+use crate::tokenizer::TokenClass;
+use encoding_rs::{Encoding, WINDOWS_1252};
+use regex::Regex;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::exit;
+use std::{env, fs};
+
+/// Example converters for `--generate-config`, commented out. Converters feed
+/// repository contents to external parsers, so like git's `textconv` they only
+/// run when the user asks for them.
+///
+/// `{}` in a command will be replaced by the path to a temporary file
+/// containing the binary contents. Without `{}`, the contents are passed on
+/// stdin.
+const EXAMPLE_CONVERTERS: &[(&str, &str)] = &[
+    ("pdf", "pdftotext -layout - -"),
+    ("sqlite", "sqlite3 {} .dump"),
+];
+
+/// How whitespace differences are treated when refining
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Whitespace {
+    /// Whitespace changes get highlighted like any other change
+    Exact,
+
+    /// Like `diff -b`: Ignore whitespace at end of line, and consider all other
+    /// runs of whitespace equal
+    IgnoreChange,
+
+    /// Like `diff -w`: Ignore all whitespace
+    IgnoreAll,
+}
+
+/// Where trailing whitespace and non-leading TABs get flagged
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum WhitespaceErrors {
+    /// For repositories where trailing whitespace is intentional, like
+    /// Markdown line breaks
+    Off,
+
+    /// Flag errors in added lines
+    Added,
+
+    /// Like `Added`, but also warn about trailing whitespace in context lines
+    AddedAndContext,
+}
+
+impl WhitespaceErrors {
+    const NAMES: [(&'static str, WhitespaceErrors); 3] = [
+        ("off", WhitespaceErrors::Off),
+        ("added", WhitespaceErrors::Added),
+        ("added-and-context", WhitespaceErrors::AddedAndContext),
+    ];
+
+    /// Config file name of this setting
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        return Self::NAMES
+            .iter()
+            .find(|(_, value)| *value == self)
+            .map(|(name, _)| *name)
+            .unwrap();
+    }
+
+    #[must_use]
+    fn parse(name: &str) -> Option<WhitespaceErrors> {
+        return Self::NAMES
+            .iter()
+            .find(|(candidate, _)| *candidate == name)
+            .map(|(_, value)| *value);
+    }
+}
+
+/// What to do about control characters in the input, like BEL, backspace or
+/// escape sequences that aren't colors
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ControlCharacters {
+    /// Show them in caret notation, like `^[` for ESC
+    Escape,
+
+    /// Remove them
+    Strip,
+
+    /// Send them to the terminal as they came
+    Pass,
+}
+
+impl ControlCharacters {
+    const NAMES: [(&'static str, ControlCharacters); 3] = [
+        ("escape", ControlCharacters::Escape),
+        ("strip", ControlCharacters::Strip),
+        ("pass", ControlCharacters::Pass),
+    ];
+
+    /// Config file name of this setting
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        return Self::NAMES
+            .iter()
+            .find(|(_, value)| *value == self)
+            .map(|(name, _)| *name)
+            .unwrap();
+    }
+
+    #[must_use]
+    fn parse(name: &str) -> Option<ControlCharacters> {
+        return Self::NAMES
+            .iter()
+            .find(|(candidate, _)| *candidate == name)
+            .map(|(_, value)| *value);
+    }
+}
+
+/// How much of a changed identifier gets highlighted
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Identifiers {
+    /// Highlight changed identifiers as a whole
+    Whole,
+
+    /// Highlight only the changed camelCase or snake_case segments, like
+    /// `Email` in `getUserName` -> `getUserEmail`
+    Segments,
+}
+
+impl Identifiers {
+    const NAMES: [(&'static str, Identifiers); 2] = [
+        ("whole", Identifiers::Whole),
+        ("segments", Identifiers::Segments),
+    ];
+
+    /// Config file name of this setting
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        return Self::NAMES
+            .iter()
+            .find(|(_, value)| *value == self)
+            .map(|(name, _)| *name)
+            .unwrap();
+    }
+
+    #[must_use]
+    fn parse(name: &str) -> Option<Identifiers> {
+        return Self::NAMES
+            .iter()
+            .find(|(candidate, _)| *candidate == name)
+            .map(|(_, value)| *value);
+    }
+}
+
+/// How trailing `//`, `#` and `--` comments are refined
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TrailingComments {
+    /// Together with the code before them
+    WithCode,
+
+    /// Separately from the code, so that changes in one don't make highlights
+    /// smear into the other
+    Separately,
+}
+
+impl TrailingComments {
+    const NAMES: [(&'static str, TrailingComments); 2] = [
+        ("with-code", TrailingComments::WithCode),
+        ("separately", TrailingComments::Separately),
+    ];
+
+    /// Config file name of this setting
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        return Self::NAMES
+            .iter()
+            .find(|(_, value)| *value == self)
+            .map(|(name, _)| *name)
+            .unwrap();
+    }
+
+    #[must_use]
+    fn parse(name: &str) -> Option<TrailingComments> {
+        return Self::NAMES
+            .iter()
+            .find(|(candidate, _)| *candidate == name)
+            .map(|(_, value)| *value);
+    }
+}
+
+/// How to color removed and added lines that have no counterpart on the other
+/// side, in blocks of changes that also modify lines
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum UnpairedLines {
+    /// Like the modified lines
+    Same,
+
+    /// In darker shades of red and green, so that the lines that were edited
+    /// stand out from the ones that were introduced or dropped
+    Shaded,
+}
+
+impl UnpairedLines {
+    const NAMES: [(&'static str, UnpairedLines); 2] = [
+        ("same", UnpairedLines::Same),
+        ("shaded", UnpairedLines::Shaded),
+    ];
+
+    /// Config file name of this setting
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        return Self::NAMES
+            .iter()
+            .find(|(_, value)| *value == self)
+            .map(|(name, _)| *name)
+            .unwrap();
+    }
+
+    #[must_use]
+    fn parse(name: &str) -> Option<UnpairedLines> {
+        return Self::NAMES
+            .iter()
+            .find(|(candidate, _)| *candidate == name)
+            .map(|(_, value)| *value);
+    }
+}
+
+/// Whether to highlight the space between two highlighted words, so that
+/// `Monkey Dance` gets one highlight rather than two
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SpacesBetweenHighlights {
+    Highlight,
+    Plain,
+}
+
+impl SpacesBetweenHighlights {
+    const NAMES: [(&'static str, SpacesBetweenHighlights); 2] = [
+        ("highlight", SpacesBetweenHighlights::Highlight),
+        ("plain", SpacesBetweenHighlights::Plain),
+    ];
+
+    /// Config file name of this setting
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        return Self::NAMES
+            .iter()
+            .find(|(_, value)| *value == self)
+            .map(|(name, _)| *name)
+            .unwrap();
+    }
+
+    #[must_use]
+    fn parse(name: &str) -> Option<SpacesBetweenHighlights> {
+        return Self::NAMES
+            .iter()
+            .find(|(candidate, _)| *candidate == name)
+            .map(|(_, value)| *value);
+    }
+}
+
+/// What to do with changed lines that would be mostly highlighted
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NoisyLines {
+    /// Show them without highlights, since highlighting most of a line
+    /// doesn't help reading it
+    Unhighlight,
+
+    /// Keep the highlights
+    Keep,
+}
+
+impl NoisyLines {
+    const NAMES: [(&'static str, NoisyLines); 2] = [
+        ("unhighlight", NoisyLines::Unhighlight),
+        ("keep", NoisyLines::Keep),
+    ];
+
+    /// Config file name of this setting
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        return Self::NAMES
+            .iter()
+            .find(|(_, value)| *value == self)
+            .map(|(name, _)| *name)
+            .unwrap();
+    }
+
+    #[must_use]
+    fn parse(name: &str) -> Option<NoisyLines> {
+        return Self::NAMES
+            .iter()
+            .find(|(candidate, _)| *candidate == name)
+            .map(|(_, value)| *value);
+    }
+}
+
+/// How to show files that were added
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NewFiles {
+    /// Like any other diff, with `---` and `+++` lines and hunk headers
+    Diff,
+
+    /// With a `new file: path (N lines)` line instead of the `---`, `+++`
+    /// and `@@` lines, which say nothing more for a new file
+    Banner,
+}
+
+impl NewFiles {
+    const NAMES: [(&'static str, NewFiles); 2] =
+        [("diff", NewFiles::Diff), ("banner", NewFiles::Banner)];
+
+    /// Config file name of this setting
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        return Self::NAMES
+            .iter()
+            .find(|(_, value)| *value == self)
+            .map(|(name, _)| *name)
+            .unwrap();
+    }
+
+    #[must_use]
+    fn parse(name: &str) -> Option<NewFiles> {
+        return Self::NAMES
+            .iter()
+            .find(|(candidate, _)| *candidate == name)
+            .map(|(_, value)| *value);
+    }
+}
+
+/// Whether to look for secrets, like API keys, in added lines
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Secrets {
+    Off,
+
+    /// Give likely secrets a red background, and count them at the end
+    Flag,
+}
+
+impl Secrets {
+    const NAMES: [(&'static str, Secrets); 2] = [("off", Secrets::Off), ("flag", Secrets::Flag)];
+
+    /// Config file name of this setting
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        return Self::NAMES
+            .iter()
+            .find(|(_, value)| *value == self)
+            .map(|(name, _)| *name)
+            .unwrap();
+    }
+
+    #[must_use]
+    fn parse(name: &str) -> Option<Secrets> {
+        return Self::NAMES
+            .iter()
+            .find(|(candidate, _)| *candidate == name)
+            .map(|(_, value)| *value);
+    }
+}
+
+/// What to do with `---` and `+++` file names too long for the terminal
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LongPaths {
+    /// Let the terminal wrap them
+    Wrap,
+
+    /// Collapse the leading directories the old and new names have in common
+    /// into an ellipsis
+    Shorten,
+}
+
+impl LongPaths {
+    const NAMES: [(&'static str, LongPaths); 2] =
+        [("wrap", LongPaths::Wrap), ("shorten", LongPaths::Shorten)];
+
+    /// Config file name of this setting
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        return Self::NAMES
+            .iter()
+            .find(|(_, value)| *value == self)
+            .map(|(name, _)| *name)
+            .unwrap();
+    }
+
+    #[must_use]
+    fn parse(name: &str) -> Option<LongPaths> {
+        return Self::NAMES
+            .iter()
+            .find(|(candidate, _)| *candidate == name)
+            .map(|(_, value)| *value);
+    }
+}
+
+/// How to show the hashes in `commit` lines
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CommitHashes {
+    /// As git printed them
+    Full,
+
+    /// Shortened to the repository's `core.abbrev` length
+    Short,
+
+    /// Shortened, followed by the full hash in faint for copying
+    ShortAndFull,
+
+    /// Shortened, linking to the commit's web page using an OSC 8 hyperlink
+    Link,
+}
+
+impl CommitHashes {
+    const NAMES: [(&'static str, CommitHashes); 4] = [
+        ("full", CommitHashes::Full),
+        ("short", CommitHashes::Short),
+        ("short-and-full", CommitHashes::ShortAndFull),
+        ("link", CommitHashes::Link),
+    ];
+
+    /// Config file name of this setting
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        return Self::NAMES
+            .iter()
+            .find(|(_, value)| *value == self)
+            .map(|(name, _)| *name)
+            .unwrap();
+    }
+
+    #[must_use]
+    fn parse(name: &str) -> Option<CommitHashes> {
+        return Self::NAMES
+            .iter()
+            .find(|(candidate, _)| *candidate == name)
+            .map(|(_, value)| *value);
+    }
+}
+
+/// Old / new blocks matching this rule won't get refined
+#[derive(Clone, Debug)]
+pub struct NoRefineRule {
+    pub name: String,
+    pub regex: Regex,
+}
+
+impl PartialEq for NoRefineRule {
+    fn eq(&self, other: &Self) -> bool {
+        return self.name == other.name && self.regex.as_str() == other.regex.as_str();
+    }
+}
+
+impl Eq for NoRefineRule {}
+
+/// What a word is when refining files of some type, like git's
+/// `diff.wordRegex`
+#[derive(Clone, Debug)]
+pub struct WordRegex {
+    /// File name extension, without the dot. `None` means all files.
+    pub extension: Option<String>,
+    pub regex: Regex,
+}
+
+impl PartialEq for WordRegex {
+    fn eq(&self, other: &Self) -> bool {
+        return self.extension == other.extension && self.regex.as_str() == other.regex.as_str();
+    }
+}
+
+impl Eq for WordRegex {}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Config {
+    /// File name extension (without the dot) to converter command line
+    pub converters: HashMap<String, String>,
+
+    /// Blocks where any line matches any of these are shown without
+    /// refinement
+    pub no_refine: Vec<NoRefineRule>,
+
+    /// From the `[word-regex]` section, followed by the one from
+    /// `--word-regex`. Later entries win.
+    pub word_regexes: Vec<WordRegex>,
+
+    /// From the `[header-prefixes]` section. Lines starting with these, after
+    /// any indentation, get styled like `diff` and `index` lines.
+    pub header_prefixes: Vec<(String, Weight)>,
+
+    /// Highlight diffs embedded in other text, passing the other text through
+    /// unchanged. Set by `--embedded`.
+    pub embedded: bool,
+
+    /// Show progress on stderr while paging. Set by `--progress`.
+    pub progress: bool,
+
+    /// Leave out context lines. Set by `--only-changes`.
+    pub only_changes: bool,
+
+    /// Leave out decorative lines, like `index` and `similarity index` lines.
+    /// Set by `--quiet`.
+    pub quiet: bool,
+
+    /// Diff the contents of renamed and copied files that come without any
+    /// hunks. Set by `--refine-renames`.
+    pub refine_renames: bool,
+
+    /// Show removed and added files with similar contents as renames. Set by
+    /// `--detect-renames`.
+    pub detect_renames: bool,
+
+    /// Reorder the files of the diff. Set by `--sort`.
+    pub sort_files: Option<SortOrder>,
+
+    /// Mark these columns in added lines. Set by `--ruler`.
+    pub rulers: Vec<usize>,
+
+    /// Leave out file headers. Set by `--no-header`.
+    pub no_header: bool,
+
+    /// No `-` and `+` prefixes, one line file headers and no double width
+    /// symbols, for narrow terminals. Set by `--compact`, or by the
+    /// `compact-below` setting.
+    pub compact: bool,
+
+    /// From the `compact-below` setting, use the compact layout in terminals
+    /// narrower than this many columns
+    pub compact_below: Option<usize>,
+
+    /// Set the terminal title to the current file name. Set by
+    /// `--terminal-title` when writing to a terminal.
+    pub terminal_title: bool,
+
+    /// Explain the styling at the top of the output. Set by `--legend`.
+    pub legend: bool,
+
+    /// Explain the parent columns of combined diffs on a line before their
+    /// first hunk
+    pub combined_legend: bool,
+
+    /// List whitespace errors in added lines per file at the end of the
+    /// output. Set by `--whitespace-summary`.
+    pub whitespace_summary: bool,
+
+    /// Dim the `---` and `+++` lines of files that the previous commit also
+    /// changed, in `git log -p` output. Set by `--dim-repeated-files`.
+    pub dim_repeated_files: bool,
+
+    /// Mark changed parts of lines with `[-` and `{+` rather than with colors.
+    /// Set by `--plain-refined`.
+    pub plain_refined: bool,
+
+    /// Output text for screen readers, with `removed:` and `added:` prefixes
+    /// instead of colors. Set by `--describe`.
+    pub describe: bool,
+
+    /// Also write the highlighted output to this file. Set by `--output`.
+    pub output_file: Option<PathBuf>,
+
+    /// Truncate output lines wider than this. Set by `--max-line-length`.
+    pub max_line_length: Option<usize>,
+
+    /// Memory budget in bytes for queued output. Set by `--max-memory`.
+    pub max_memory: Option<usize>,
+
+    /// Show only this many lines at the start and at the end of longer runs
+    /// of removed lines. Set by `--collapse-removed`.
+    pub collapse_removed: Option<usize>,
+
+    /// Width of the terminal the output will be shown in, from `--width` or
+    /// `$COLUMNS`. Unknown if neither is set.
+    pub width: Option<usize>,
+
+    /// Refine in one thread rather than one per CPU, for debugging and
+    /// benchmarking. Set by `--single-threaded`.
+    pub single_threaded: bool,
+
+    /// Input is `git blame --date=unix` output rather than a diff. Set by
+    /// `riff blame`.
+    pub blame: bool,
+
+    /// Color the gutters of context and removed lines by when they were last
+    /// changed, according to `git blame`. Set by `--age-heatmap`.
+    pub age_heatmap: bool,
+
+    /// Running as `git add -p`'s `interactive.diffFilter`, where every input
+    /// line must come out as exactly one output line with the same text
+    pub diff_filter: bool,
+
+    /// Show lines that aren't part of any diff with their original colors
+    /// rather than uncolored. Set by `--keep-input-colors`.
+    pub keep_input_colors: bool,
+
+    /// Set by `-b`, `--ignore-space-change` and `--iw`
+    pub whitespace: Whitespace,
+
+    /// Where to flag whitespace errors, from the `whitespace-errors` setting
+    pub whitespace_errors: WhitespaceErrors,
+
+    /// How to show commit hashes, from the `commit-hashes` setting
+    pub commit_hashes: CommitHashes,
+
+    /// From the `control-characters` setting
+    pub control_characters: ControlCharacters,
+
+    /// From the `identifiers` setting
+    pub identifiers: Identifiers,
+
+    /// From the `trailing-comments` setting
+    pub trailing_comments: TrailingComments,
+
+    /// From the `unpaired-lines` setting
+    pub unpaired_lines: UnpairedLines,
+
+    /// From the `spaces-between-highlights` setting, or `--plain-spaces`
+    pub spaces_between_highlights: SpacesBetweenHighlights,
+
+    /// From the `noisy-lines` setting, or `--keep-noisy-highlights`
+    pub noisy_lines: NoisyLines,
+
+    /// From the `new-files` setting, or `--new-file-banner`
+    pub new_files: NewFiles,
+
+    /// From the `secrets` setting
+    pub secrets: Secrets,
+
+    /// From the `hunk-header` setting. `None` shows hunk headers as they
+    /// come.
+    pub hunk_header: Option<String>,
+
+    /// From the `long-paths` setting. Only applies if we know the terminal
+    /// width.
+    pub long_paths: LongPaths,
+
+    /// Lines that aren't valid UTF-8 get decoded using the first of these
+    /// that can decode them
+    pub fallback_encodings: Vec<&'static Encoding>,
+
+    /// Styling of unchanged tokens, from the `theme` setting or the `[theme]`
+    /// section
+    pub theme: Theme,
+
+    /// User defined themes by name, from the `[themes]` section
+    pub themes: Vec<(String, Theme)>,
+
+    /// The escape sequences we color our output with
+    pub palette: Palette,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        return Config {
+            converters: HashMap::new(),
+            no_refine: vec![],
+            word_regexes: vec![],
+            header_prefixes: vec![],
+            embedded: false,
+            progress: false,
+            only_changes: false,
+            quiet: false,
+            single_threaded: false,
+            blame: false,
+            age_heatmap: false,
+            diff_filter: false,
+            keep_input_colors: false,
+            refine_renames: false,
+            detect_renames: false,
+            sort_files: None,
+            rulers: Vec::new(),
+            no_header: false,
+            compact: false,
+            compact_below: None,
+            terminal_title: false,
+            legend: false,
+            combined_legend: true,
+            whitespace_summary: false,
+            dim_repeated_files: false,
+            plain_refined: false,
+            describe: false,
+            output_file: None,
+            max_line_length: None,
+            max_memory: None,
+            collapse_removed: None,
+            width: None,
+            whitespace: Whitespace::Exact,
+            whitespace_errors: WhitespaceErrors::Added,
+            commit_hashes: CommitHashes::Full,
+            control_characters: ControlCharacters::Escape,
+            identifiers: Identifiers::Whole,
+            trailing_comments: TrailingComments::WithCode,
+            unpaired_lines: UnpairedLines::Same,
+            spaces_between_highlights: SpacesBetweenHighlights::Highlight,
+            noisy_lines: NoisyLines::Unhighlight,
+            new_files: NewFiles::Diff,
+            secrets: Secrets::Off,
+            hunk_header: None,
+            long_paths: LongPaths::Wrap,
+
+            // Latin-1 superset, common from Windows tools
+            fallback_encodings: vec![WINDOWS_1252],
+            theme: Theme::default(),
+            themes: Vec::new(),
+            palette: Palette::default(),
+        };
+    }
+}
+
+fn parse_token_class(name: &str) -> Option<TokenClass> {
+    return TokenClass::ALL
+        .iter()
+        .copied()
+        .find(|class| class.name() == name);
+}
+
+/// Parse a `[theme.rules]` table of name = { regex = "...", weight = "..." },
+/// optionally with a `class` the tokens must have
+fn parse_theme_rules(value: &toml::Value, section: &str, theme: &mut Theme) -> Result<(), String> {
+    let rules = value.as_table().ok_or(format!(
+        "[{section}.rules] must be a table of name = {{ regex = \"...\", weight = \"...\" }}"
+    ))?;
+    for (name, rule) in rules {
+        let regex = rule
+            .get("regex")
+            .and_then(toml::Value::as_str)
+            .ok_or(format!("{section}.rules.{name} needs a regex string"))?;
+        let weight = rule
+            .get("weight")
+            .and_then(toml::Value::as_str)
+            .and_then(theme::parse_weight)
+            .ok_or(format!(
+                "{section}.rules.{name}.weight must be one of \"normal\", \"bold\" or \"faint\""
+            ))?;
+        let class = match rule.get("class") {
+            Some(class) => {
+                let class = class
+                    .as_str()
+                    .ok_or(format!("{section}.rules.{name}.class must be a string"))?;
+                Some(parse_token_class(class).ok_or(format!(
+                    "{section}.rules.{name}: Unknown token class: {class}"
+                ))?)
+            }
+            None => None,
+        };
+        let rule = TokenStyleRule::new(name, regex, class, weight)
+            .map_err(|error| format!("{section}.rules.{name}: {error}"))?;
+        theme.add_rule(rule);
+    }
+    return Ok(());
+}
+
+/// Parse a table of token class = "weight", like the `[theme]` section, with
+/// an optional `rules` table for styling tokens matching regexes
+fn parse_theme(value: &toml::Value, section: &str) -> Result<Theme, String> {
+    let classes = value.as_table().ok_or(format!(
+        "[{section}] must be a table of token class = \"weight\""
+    ))?;
+
+    let mut theme = Theme::default();
+    for (name, weight) in classes {
+        if name == "rules" {
+            parse_theme_rules(weight, section, &mut theme)?;
+            continue;
+        }
+
+        let class =
+            parse_token_class(name).ok_or(format!("{section}: Unknown token class: {name}"))?;
+        let weight = weight
+            .as_str()
+            .and_then(theme::parse_weight)
+            .ok_or(format!(
+                "{section}.{name} must be one of \"normal\", \"bold\" or \"faint\""
+            ))?;
+        theme.set_weight(class, weight);
+    }
+    return Ok(theme);
+}
+
+/// Settings that repositories can't put in their `.riff.toml`. Converters run
+/// commands, and cloning a repository shouldn't make us run its commands. The
+/// others protect the user, from terminal escape sequences and from missing
+/// secrets, so only the user gets to turn them off.
+const USER_ONLY_SETTINGS: &[&str] = &["converters", "control-characters", "secrets"];
+
+#[must_use]
+fn exit_on_error<T>(result: Result<T, String>, path: &Path) -> T {
+    return match result {
+        Ok(value) => value,
+        Err(message) => {
+            eprintln!("ERROR: {}: {}", path.to_string_lossy(), message);
+            exit(1);
+        }
+    };
+}
+
+/// Find `.riff.toml` at the root of the repository containing `start`. The
+/// root is the first directory upwards from `start` that has a `.git` in it.
+#[must_use]
+fn find_repo_config(start: &Path) -> Option<PathBuf> {
+    let root = start.ancestors().find(|dir| dir.join(".git").exists())?;
+    let path = root.join(".riff.toml");
+    if !path.is_file() {
+        return None;
+    }
+    return Some(path);
+}
+
+/// Repository config file settings without the ones only the user can make,
+/// and the names of the settings that were left out
+fn check_repo_toml(toml_text: &str) -> Result<(toml::Table, Vec<&'static str>), String> {
+    let mut table = toml_text
+        .parse::<toml::Table>()
+        .map_err(|error| error.to_string())?;
+    let ignored: Vec<&str> = USER_ONLY_SETTINGS
+        .iter()
+        .copied()
+        .filter(|key| table.remove(*key).is_some())
+        .collect();
+    return Ok((table, ignored));
+}
+
+/// Where we look for the user's config file. Respects `$XDG_CONFIG_HOME`,
+/// falls back to `~/.config/riff/config.toml`.
+pub fn default_config_path() -> Option<PathBuf> {
+    if let Ok(xdg_config_home) = env::var("XDG_CONFIG_HOME") {
+        if !xdg_config_home.is_empty() {
+            return Some(PathBuf::from(xdg_config_home).join("riff/config.toml"));
+        }
+    }
+
+    let home = env::var("HOME").or_else(|_| env::var("USERPROFILE")).ok()?;
+    return Some(PathBuf::from(home).join(".config/riff/config.toml"));
+}
+
+/// Returns a config file with all settings at their default values, and
+/// comments explaining them. Used by `--generate-config`.
+#[must_use]
+pub fn default_config_toml() -> String {
+    let defaults = Config::default();
+
+    let mut toml_text = String::from(
+        r#"# riff config file. Settings left out get their default values.
+#
+# Documentation: <https://github.com/walles/riff#configuration>
+
+# Lines that aren't valid UTF-8 are decoded using the first of these encodings
+# that can decode them. An empty list shows invalid UTF-8 as replacement
+# characters. UTF-16 input is always recognized.
+"#,
+    );
+
+    let encodings: Vec<String> = defaults
+        .fallback_encodings
+        .iter()
+        .map(|encoding| format!("{:?}", encoding.name()))
+        .collect();
+    toml_text.push_str(&format!(
+        "fallback-encodings = [{}]\n",
+        encodings.join(", ")
+    ));
+
+    toml_text.push_str(&format!(
+        r#"
+# Where to flag trailing whitespace and non-leading TABs: "added" lines only,
+# "added-and-context" to also warn about trailing whitespace in context lines,
+# or "off" for repositories where trailing whitespace is intentional.
+whitespace-errors = "{}"
+"#,
+        defaults.whitespace_errors.name()
+    ));
+
+    toml_text.push_str(&format!(
+        r#"
+# How to show hashes in "commit" lines: "full", "short" for the repository's
+# core.abbrev length, "short-and-full" for a short hash followed by a faint full
+# one, or "link" for a short hash linking to the commit on the origin remote's
+# web site, in terminals that support hyperlinks.
+commit-hashes = "{}"
+"#,
+        defaults.commit_hashes.name()
+    ));
+
+    toml_text.push_str(&format!(
+        r#"
+# Control characters in the input, like BEL or escape sequences other than
+# colors, could mess with your terminal. "escape" shows them as ^G or ^[,
+# "strip" removes them and "pass" sends them to the terminal as is.
+control-characters = "{}"
+"#,
+        defaults.control_characters.name()
+    ));
+
+    toml_text.push_str(&format!(
+        r#"
+# How much of a changed identifier to highlight in source code: "whole" for the
+# whole identifier, or "segments" for only the changed camelCase or snake_case
+# parts of it, like "Email" in getUserName -> getUserEmail.
+identifiers = "{}"
+"#,
+        defaults.identifiers.name()
+    ));
+
+    toml_text.push_str(&format!(
+        r#"
+# How to refine trailing //, # and -- comments in source code: "with-code", or
+# "separately" from the code before them, so that a change in a comment doesn't
+# make highlights smear into the code, and the other way around.
+trailing-comments = "{}"
+"#,
+        defaults.trailing_comments.name()
+    ));
+
+    toml_text.push_str(&format!(
+        r#"
+# How to color removed and added lines without a counterpart on the other side,
+# in changes that also modify lines: the "same" as the modified lines, or
+# "shaded" darker, to tell edited lines from introduced ones at a glance.
+unpaired-lines = "{}"
+"#,
+        defaults.unpaired_lines.name()
+    ));
+
+    toml_text.push_str(&format!(
+        r#"
+# Whether to "highlight" the space between two highlighted words, so that they
+# get one highlight rather than two, or to leave it "plain".
+spaces-between-highlights = "{}"
+"#,
+        defaults.spaces_between_highlights.name()
+    ));
+
+    toml_text.push_str(&format!(
+        r#"
+# Changed lines that would be mostly highlighted are shown without highlights
+# with "unhighlight", since highlighting most of a line doesn't help reading
+# it. "keep" keeps their highlights.
+noisy-lines = "{}"
+"#,
+        defaults.noisy_lines.name()
+    ));
+
+    toml_text.push_str(&format!(
+        r#"
+# How to show added files: as a "diff" like any other, or with a "banner" line
+# saying "new file: path (N lines)" instead of the "---", "+++" and "@@" lines.
+new-files = "{}"
+"#,
+        defaults.new_files.name()
+    ));
+
+    toml_text.push_str(&format!(
+        r#"
+# "flag" gives likely secrets in added lines, like AWS keys, private keys and
+# long random looking strings, a red background, and says how many there were
+# at the end. "off" doesn't look for them.
+secrets = "{}"
+"#,
+        defaults.secrets.name()
+    ));
+
+    toml_text.push_str(
+        r#"
+# How to show "@@ -12,3 +12,4 @@ fn main()" hunk headers. Without this setting,
+# they are shown as they come. Placeholders are {header} for the "@@ ... @@"
+# part, {function} for the function name, {old_lines} and {new_lines} for
+# computed line ranges like "12–14", and {ranges} for both, like
+# "old: 12–14 | new: 12–15".
+# hunk-header = "{function}  lines {old_lines} → {new_lines}"
+"#,
+    );
+
+    toml_text.push_str(
+        r#"
+# In terminals narrower than this many columns, leave out the "-" and "+"
+# prefixes and show one line file headers, like with --compact. Only applies
+# if we know the terminal width.
+# compact-below = 80
+"#,
+    );
+
+    toml_text.push_str(&format!(
+        r#"
+# File names too long for the terminal: "wrap" lets the terminal wrap them, and
+# "shorten" collapses their common leading directories, like "a/…/dir/file.rs".
+long-paths = "{}"
+"#,
+        defaults.long_paths.name()
+    ));
+
+    toml_text.push_str(
+        r#"
+# Commands for turning "Binary files differ" files into diffable text, by file
+# name extension. "{}" is replaced by the path to a temporary file containing
+# the binary contents. Without "{}", the contents are passed on stdin. None are
+# enabled by default.
+[converters]
+"#,
+    );
+    for (extension, command) in EXAMPLE_CONVERTERS {
+        toml_text.push_str(&format!("# {extension} = {command:?}\n"));
+    }
+
+    toml_text.push_str(
+        r#"
+# Blocks of changes where any line matches one of these regexes are shown
+# without highlighting the changed parts of lines.
+[no-refine]
+# integrity = "^\s*integrity sha512-"
+
+# What a word is when highlighting the changed parts of lines, by file name
+# extension, like git's diff.wordRegex. Text between words is compared
+# character by character.
+[word-regex]
+# sql = "[A-Za-z_][A-Za-z0-9_]*|[^[:space:]]"
+
+# Lines starting with these, after any indentation, are styled like the "diff"
+# and "index" lines. Weights can be "normal", "bold" or "faint".
+[header-prefixes]
+# "Change-Id:" = "faint"
+
+# Styling of unchanged tokens, by token class. Weights can be "normal", "bold"
+# or "faint".
+#
+# Instead of this section, you can also pick a theme by name, like
+# theme = "faint-punctuation". "riff --list-themes" shows what they look like.
+# Your own named themes go into sections like [themes.my-theme].
+[theme]
+"#,
+    );
+    for class in TokenClass::ALL {
+        toml_text.push_str(&format!("# {} = \"normal\"\n", class.name()));
+    }
+    toml_text.push_str(
+        r#"
+# Unchanged tokens matching these regexes get these weights, regardless of
+# their class. Rules can be limited to one class, like class = "identifier".
+# [theme.rules]
+# todo = { regex = "TODO|FIXME", weight = "bold" }
+"#,
+    );
+
+    toml_text.push_str(
+        r#"
+# File type profiles are picked automatically from file names and can't be
+# configured yet:
+# * YAML (.yaml, .yml and kubectl diff output): Keys are highlighted as a
+#   whole, and bookkeeping fields like resourceVersion are lowlighted
+# * Markdown (.md, .markdown): Markup is highlighted separately from text, and
+#   two trailing spaces are a hard line break rather than a whitespace error
+"#,
+    );
+
+    return toml_text;
+}
+
+impl Config {
+    /// Turn off everything that adds or removes lines, git can't map the
+    /// user's answers back onto the diff otherwise
+    /// The regex matching words in `file`, if there is one for its type
+    #[must_use]
+    pub fn word_regex_for(&self, file: &str) -> Option<&Regex> {
+        let name = file_timestamp::strip(file);
+        let base_name = name.rsplit('/').next().unwrap_or(name);
+        let extension = base_name
+            .rsplit_once('.')
+            .map(|(_, extension)| extension.to_lowercase());
+        return self
+            .word_regexes
+            .iter()
+            .rev()
+            .find(|word_regex| word_regex.extension.is_none() || word_regex.extension == extension)
+            .map(|word_regex| &word_regex.regex);
+    }
+
+    pub fn enable_diff_filter_mode(&mut self) {
+        self.diff_filter = true;
+        self.only_changes = false;
+        self.quiet = false;
+        self.refine_renames = false;
+        self.detect_renames = false;
+        self.sort_files = None;
+        self.collapse_removed = None;
+        self.legend = false;
+        self.combined_legend = false;
+        self.whitespace_summary = false;
+        self.no_header = false;
+        self.compact = false;
+        self.new_files = NewFiles::Diff;
+        self.terminal_title = false;
+        self.converters.clear();
+
+        // git add -p needs to find "@@ -" in the hunk headers
+        self.hunk_header = None;
+    }
+
+    /// Load the user's config file if there is one, and then the current
+    /// repository's `.riff.toml` on top of that. Settings found in neither
+    /// get their default values.
+    ///
+    /// Exits with an error message if a config file is broken.
+    pub fn load() -> Config {
+        let mut config = Config::default();
+
+        if let Some(path) = default_config_path() {
+            if let Ok(toml_text) = fs::read_to_string(&path) {
+                config = exit_on_error(Config::from_toml(&toml_text), &path);
+            }
+        }
+
+        let repo_config_path = env::current_dir()
+            .ok()
+            .and_then(|cwd| find_repo_config(&cwd));
+        if let Some(path) = repo_config_path {
+            if let Ok(toml_text) = fs::read_to_string(&path) {
+                let (table, ignored) = exit_on_error(check_repo_toml(&toml_text), &path);
+                for key in ignored {
+                    eprintln!(
+                        "WARNING: {}: Ignoring {key}, it can only be set in your own config file",
+                        path.to_string_lossy()
+                    );
+                }
+                config = exit_on_error(config.merge_table(table), &path);
+            }
+        }
+
+        return config;
+    }
+
+    /// Parse config file contents. Settings not mentioned keep their default
+    /// values.
+    pub fn from_toml(toml_text: &str) -> Result<Config, String> {
+        return Config::default().merge_toml(toml_text);
+    }
+
+    /// Apply the settings in `toml_text` on top of this config. Tables like
+    /// `[converters]` and `[themes]` get their entries merged, other settings
+    /// are replaced.
+    fn merge_toml(self, toml_text: &str) -> Result<Config, String> {
+        let table = toml_text
+            .parse::<toml::Table>()
+            .map_err(|error| error.to_string())?;
+        return self.merge_table(table);
+    }
+
+    /// Like [`Config::merge_toml`], but with the settings parsed already
+    fn merge_table(self, table: toml::Table) -> Result<Config, String> {
+        let mut config = self;
+        let mut theme_name: Option<String> = None;
+        for (key, value) in table {
+            match key.as_str() {
+                "converters" => {
+                    let converters = value
+                        .as_table()
+                        .ok_or("[converters] must be a table of extension = \"command\"")?;
+                    for (extension, command) in converters {
+                        let command = command.as_str().ok_or(format!(
+                            "converters.{extension} must be a string, an empty string disables it"
+                        ))?;
+                        if command.is_empty() {
+                            config.converters.remove(extension);
+                        } else {
+                            config
+                                .converters
+                                .insert(extension.to_string(), command.to_string());
+                        }
+                    }
+                }
+                "no-refine" => {
+                    let rules = value
+                        .as_table()
+                        .ok_or("[no-refine] must be a table of name = \"regex\"")?;
+                    for (name, regex) in rules {
+                        let regex = regex
+                            .as_str()
+                            .ok_or(format!("no-refine.{name} must be a regex string"))?;
+
+                        // Multi line mode makes ^ and $ match at line
+                        // boundaries, since we match whole blocks at once
+                        let regex = Regex::new(&format!("(?m){regex}"))
+                            .map_err(|error| format!("no-refine.{name}: {error}"))?;
+                        config.no_refine.push(NoRefineRule {
+                            name: name.to_string(),
+                            regex,
+                        });
+                    }
+                }
+                "word-regex" => {
+                    let regexes = value
+                        .as_table()
+                        .ok_or("[word-regex] must be a table of extension = \"regex\"")?;
+                    for (extension, regex) in regexes {
+                        let regex = regex
+                            .as_str()
+                            .ok_or(format!("word-regex.{extension} must be a regex string"))?;
+                        let regex = Regex::new(regex)
+                            .map_err(|error| format!("word-regex.{extension}: {error}"))?;
+                        config.word_regexes.push(WordRegex {
+                            extension: Some(extension.to_lowercase()),
+                            regex,
+                        });
+                    }
+                }
+                "header-prefixes" => {
+                    let prefixes = value
+                        .as_table()
+                        .ok_or("[header-prefixes] must be a table of \"prefix\" = \"weight\"")?;
+                    for (prefix, weight) in prefixes {
+                        if prefix.trim().is_empty() {
+                            return Err("header-prefixes: Prefixes can't be empty".to_string());
+                        }
+                        let weight = weight.as_str().and_then(theme::parse_weight).ok_or(
+                            format!(
+                                "header-prefixes.\"{prefix}\" must be one of \"normal\", \"bold\" or \"faint\""
+                            ),
+                        )?;
+
+                        // A repository's .riff.toml can restyle our own prefixes
+                        config
+                            .header_prefixes
+                            .retain(|(existing, _)| existing != prefix);
+                        config.header_prefixes.push((prefix.to_string(), weight));
+                    }
+                }
+                "fallback-encodings" => {
+                    let labels = value
+                        .as_array()
+                        .ok_or("fallback-encodings must be a list of encoding names")?;
+                    config.fallback_encodings = vec![];
+                    for label in labels {
+                        let label = label
+                            .as_str()
+                            .ok_or("fallback-encodings must be a list of encoding names")?;
+                        let encoding = Encoding::for_label(label.as_bytes())
+                            .ok_or(format!("fallback-encodings: Unknown encoding: {label}"))?;
+                        config.fallback_encodings.push(encoding);
+                    }
+                }
+                "whitespace-errors" => {
+                    config.whitespace_errors = value
+                        .as_str()
+                        .and_then(WhitespaceErrors::parse)
+                        .ok_or(
+                            "whitespace-errors must be one of \"off\", \"added\" or \"added-and-context\"",
+                        )?;
+                }
+                "commit-hashes" => {
+                    config.commit_hashes = value
+                        .as_str()
+                        .and_then(CommitHashes::parse)
+                        .ok_or(
+                            "commit-hashes must be one of \"full\", \"short\", \"short-and-full\" or \"link\"",
+                        )?;
+                }
+                "control-characters" => {
+                    config.control_characters =
+                        value.as_str().and_then(ControlCharacters::parse).ok_or(
+                            "control-characters must be one of \"escape\", \"strip\" or \"pass\"",
+                        )?;
+                }
+                "identifiers" => {
+                    config.identifiers = value
+                        .as_str()
+                        .and_then(Identifiers::parse)
+                        .ok_or("identifiers must be one of \"whole\" or \"segments\"")?;
+                }
+                "trailing-comments" => {
+                    config.trailing_comments =
+                        value.as_str().and_then(TrailingComments::parse).ok_or(
+                            "trailing-comments must be one of \"with-code\" or \"separately\"",
+                        )?;
+                }
+                "unpaired-lines" => {
+                    config.unpaired_lines = value
+                        .as_str()
+                        .and_then(UnpairedLines::parse)
+                        .ok_or("unpaired-lines must be one of \"same\" or \"shaded\"")?;
+                }
+                "spaces-between-highlights" => {
+                    config.spaces_between_highlights = value
+                        .as_str()
+                        .and_then(SpacesBetweenHighlights::parse)
+                        .ok_or(
+                            "spaces-between-highlights must be one of \"highlight\" or \"plain\"",
+                        )?;
+                }
+                "noisy-lines" => {
+                    config.noisy_lines = value
+                        .as_str()
+                        .and_then(NoisyLines::parse)
+                        .ok_or("noisy-lines must be one of \"unhighlight\" or \"keep\"")?;
+                }
+                "new-files" => {
+                    config.new_files = value
+                        .as_str()
+                        .and_then(NewFiles::parse)
+                        .ok_or("new-files must be one of \"diff\" or \"banner\"")?;
+                }
+                "secrets" => {
+                    config.secrets = value
+                        .as_str()
+                        .and_then(Secrets::parse)
+                        .ok_or("secrets must be one of \"off\" or \"flag\"")?;
+                }
+                "compact-below" => {
+                    config.compact_below = Some(
+                        value
+                            .as_integer()
+                            .filter(|columns| *columns > 0)
+                            .map(|columns| columns as usize)
+                            .ok_or("compact-below must be a positive number of columns")?,
+                    );
+                }
+                "long-paths" => {
+                    config.long_paths = value
+                        .as_str()
+                        .and_then(LongPaths::parse)
+                        .ok_or("long-paths must be one of \"wrap\" or \"shorten\"")?;
+                }
+                "hunk-header" => {
+                    let template = value
+                        .as_str()
+                        .ok_or("hunk-header must be a template string")?;
+                    hunk_header::check_template(template)
+                        .map_err(|error| format!("hunk-header: {error}"))?;
+                    config.hunk_header = Some(template.to_string());
+                }
+                "theme" => {
+                    if let Some(name) = value.as_str() {
+                        // Resolved below, when all [themes] are known
+                        theme_name = Some(name.to_string());
+                    } else {
+                        config.theme = parse_theme(&value, "theme")?;
+                    }
+                }
+                "themes" => {
+                    let themes = value
+                        .as_table()
+                        .ok_or("[themes] must be a table of theme name = table")?;
+                    for (name, theme) in themes {
+                        let theme = parse_theme(theme, &format!("themes.{name}"))?;
+                        config.themes.push((name.to_string(), theme));
+                    }
+                }
+                _ => return Err(format!("Unknown setting: {key}")),
+            }
+        }
+
+        if let Some(name) = theme_name {
+            let user_theme = config
+                .themes
+                .iter()
+                .find(|(candidate, _)| *candidate == name)
+                .map(|(_, theme)| theme.clone());
+            config.theme = user_theme
+                .or_else(|| Theme::built_in(&name))
+                .ok_or(format!("Unknown theme: {name}"))?;
+        }
+
+        return Ok(config);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_empty_config() {
+        assert_eq!(Config::from_toml("").unwrap(), Config::default());
+    }
+
+    #[test]
+    fn test_merge_toml() {
+        let user = Config::from_toml(
+            r#"
+            commit-hashes = "short"
+            [no-refine]
+            lockfile = "^lockfileVersion"
+            "#,
+        )
+        .unwrap();
+        let merged = user
+            .merge_toml(
+                r#"
+                whitespace-errors = "off"
+                [no-refine]
+                integrity = "integrity sha512-"
+                "#,
+            )
+            .unwrap();
+
+        assert_eq!(merged.commit_hashes, CommitHashes::Short);
+        assert_eq!(merged.whitespace_errors, WhitespaceErrors::Off);
+        let rule_names: Vec<&str> = merged
+            .no_refine
+            .iter()
+            .map(|rule| rule.name.as_str())
+            .collect();
+        assert_eq!(rule_names, ["lockfile", "integrity"]);
+    }
+
+    #[test]
+    fn test_repo_config() {
+        let repo = tempfile::tempdir().unwrap();
+        let subdir = repo.path().join("src/deep");
+        fs::create_dir_all(&subdir).unwrap();
+        fs::create_dir(repo.path().join(".git")).unwrap();
+        assert_eq!(find_repo_config(&subdir), None);
+
+        fs::write(repo.path().join(".riff.toml"), "").unwrap();
+        assert_eq!(
+            find_repo_config(&subdir),
+            Some(repo.path().join(".riff.toml"))
+        );
+
+        let (table, ignored) = check_repo_toml(r#"identifiers = "segments""#).unwrap();
+        assert_eq!(table.len(), 1);
+        assert!(ignored.is_empty());
+        let (table, ignored) = check_repo_toml(
+            "[converters]
+zip = \"rm -rf ~\"",
+        )
+        .unwrap();
+        assert!(table.is_empty());
+        assert_eq!(ignored, ["converters"]);
+
+        // Protecting the user is up to the user
+        let (table, ignored) = check_repo_toml(
+            r#"
+identifiers = "whole"
+control-characters = "pass"
+secrets = "off"
+"#,
+        )
+        .unwrap();
+        assert_eq!(ignored, ["control-characters", "secrets"]);
+        let mut config = Config {
+            control_characters: ControlCharacters::Strip,
+            secrets: Secrets::Flag,
+            ..Config::default()
+        };
+        config = config.merge_table(table).unwrap();
+        assert_eq!(config.control_characters, ControlCharacters::Strip);
+        assert_eq!(config.secrets, Secrets::Flag);
+    }
+
+    #[test]
+    fn test_converters() {
+        let config = Config::from_toml(
+            r#"
+            [converters]
+            docx = "pandoc -t plain {}"
+            pdf = ""
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.converters.get("docx"),
+            Some(&"pandoc -t plain {}".to_string())
+        );
+        assert_eq!(config.converters.get("pdf"), None);
+
+        // Nothing runs unless asked for
+        assert!(Config::default().converters.is_empty());
+    }
+
+    #[test]
+    fn test_no_refine() {
+        let config = Config::from_toml(
+            r#"
+            [no-refine]
+            integrity = "^\\s*integrity sha512-"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.no_refine.len(), 1);
+        assert_eq!(config.no_refine[0].name, "integrity");
+        assert!(config.no_refine[0]
+            .regex
+            .is_match("foo\n  integrity sha512-abc\n"));
+
+        assert!(Config::from_toml("[no-refine]\nbroken = \"(\"").is_err());
+    }
+
+    #[test]
+    fn test_word_regex() {
+        let mut config = Config::from_toml(
+            r#"
+            [word-regex]
+            SQL = "[a-z]+"
+            "#,
+        )
+        .unwrap();
+        let regex = |file: &str| {
+            return config.word_regex_for(file).map(Regex::as_str);
+        };
+        assert_eq!(regex("b/db/schema.sql"), Some("[a-z]+"));
+        assert_eq!(
+            regex("b/x.v2/schema.sql\t2021-01-01 12:00:00"),
+            Some("[a-z]+")
+        );
+        assert_eq!(regex("b/README.md"), None);
+        assert_eq!(regex("b/x.sql/README"), None);
+
+        // From --word-regex, for all files
+        config.word_regexes.push(WordRegex {
+            extension: None,
+            regex: Regex::new(r"\S+").unwrap(),
+        });
+        assert_eq!(
+            config.word_regex_for("b/README.md").map(Regex::as_str),
+            Some(r"\S+")
+        );
+        assert_eq!(
+            config.word_regex_for("b/schema.sql").map(Regex::as_str),
+            Some(r"\S+")
+        );
+
+        assert!(Config::from_toml("[word-regex]\nsql = \"(\"").is_err());
+    }
+
+    #[test]
+    fn test_header_prefixes() {
+        let config = Config::from_toml(
+            r#"
+            [header-prefixes]
+            "Change-Id:" = "faint"
+            "Depends-On:" = "bold"
+            "#,
+        )
+        .unwrap()
+        .merge_toml("[header-prefixes]\n\"Change-Id:\" = \"normal\"")
+        .unwrap();
+        assert_eq!(
+            config.header_prefixes,
+            vec![
+                ("Depends-On:".to_string(), Weight::Bold),
+                ("Change-Id:".to_string(), Weight::Normal),
+            ]
+        );
+
+        assert_eq!(
+            Config::from_toml("[header-prefixes]\n\"Change-Id:\" = \"blinking\""),
+            Err(
+                "header-prefixes.\"Change-Id:\" must be one of \"normal\", \"bold\" or \"faint\""
+                    .to_string()
+            )
+        );
+        assert!(Config::from_toml("[header-prefixes]\n\"\" = \"bold\"").is_err());
+    }
+
+    #[test]
+    fn test_fallback_encodings() {
+        let config = Config::from_toml(r#"fallback-encodings = ["latin1", "shift_jis"]"#).unwrap();
+        assert_eq!(
+            config.fallback_encodings,
+            vec![WINDOWS_1252, encoding_rs::SHIFT_JIS]
+        );
+
+        assert_eq!(
+            Config::from_toml(r#"fallback-encodings = ["klingon"]"#),
+            Err("fallback-encodings: Unknown encoding: klingon".to_string())
+        );
+    }
+
+    #[test]
+    fn test_whitespace_errors() {
+        let config = Config::from_toml(r#"whitespace-errors = "off""#).unwrap();
+        assert_eq!(config.whitespace_errors, WhitespaceErrors::Off);
+
+        let config = Config::from_toml(r#"whitespace-errors = "added-and-context""#).unwrap();
+        assert_eq!(config.whitespace_errors, WhitespaceErrors::AddedAndContext);
+
+        assert!(Config::from_toml(r#"whitespace-errors = "everywhere""#).is_err());
+    }
+
+    #[test]
+    fn test_commit_hashes() {
+        let config = Config::from_toml(r#"commit-hashes = "short-and-full""#).unwrap();
+        assert_eq!(config.commit_hashes, CommitHashes::ShortAndFull);
+
+        assert!(Config::from_toml(r#"commit-hashes = "medium""#).is_err());
+    }
+
+    #[test]
+    fn test_control_characters() {
+        let config = Config::from_toml(r#"control-characters = "strip""#).unwrap();
+        assert_eq!(config.control_characters, ControlCharacters::Strip);
+
+        assert!(Config::from_toml(r#"control-characters = "yes""#).is_err());
+    }
+
+    #[test]
+    fn test_identifiers() {
+        let config = Config::from_toml(r#"identifiers = "segments""#).unwrap();
+        assert_eq!(config.identifiers, Identifiers::Segments);
+
+        assert!(Config::from_toml(r#"identifiers = "parts""#).is_err());
+    }
+
+    #[test]
+    fn test_trailing_comments() {
+        let config = Config::from_toml(r#"trailing-comments = "separately""#).unwrap();
+        assert_eq!(config.trailing_comments, TrailingComments::Separately);
+
+        assert!(Config::from_toml(r#"trailing-comments = "apart""#).is_err());
+    }
+
+    #[test]
+    fn test_unpaired_lines() {
+        let config = Config::from_toml(r#"unpaired-lines = "shaded""#).unwrap();
+        assert_eq!(config.unpaired_lines, UnpairedLines::Shaded);
+
+        assert!(Config::from_toml(r#"unpaired-lines = "dark""#).is_err());
+    }
+
+    #[test]
+    fn test_heuristics() {
+        let config = Config::from_toml(concat!(
+            "spaces-between-highlights = \"plain\"\n",
+            "noisy-lines = \"keep\"\n",
+        ))
+        .unwrap();
+        assert_eq!(
+            config.spaces_between_highlights,
+            SpacesBetweenHighlights::Plain
+        );
+        assert_eq!(config.noisy_lines, NoisyLines::Keep);
+
+        assert!(Config::from_toml(r#"spaces-between-highlights = "bridge""#).is_err());
+        assert!(Config::from_toml(r#"noisy-lines = "off""#).is_err());
+    }
+
+    #[test]
+    fn test_new_files() {
+        let config = Config::from_toml(r#"new-files = "banner""#).unwrap();
+        assert_eq!(config.new_files, NewFiles::Banner);
+
+        assert!(Config::from_toml(r#"new-files = "summary""#).is_err());
+    }
+
+    #[test]
+    fn test_secrets() {
+        let config = Config::from_toml(r#"secrets = "flag""#).unwrap();
+        assert_eq!(config.secrets, Secrets::Flag);
+
+        assert!(Config::from_toml(r#"secrets = "yes""#).is_err());
+    }
+
+    #[test]
+    fn test_compact_below() {
+        let config = Config::from_toml("compact-below = 80").unwrap();
+        assert_eq!(config.compact_below, Some(80));
+
+        assert!(Config::from_toml("compact-below = 0").is_err());
+        assert!(Config::from_toml(r#"compact-below = "80""#).is_err());
+    }
+
+    #[test]
+    fn test_long_paths() {
+        let config = Config::from_toml(r#"long-paths = "shorten""#).unwrap();
+        assert_eq!(config.long_paths, LongPaths::Shorten);
+
+        assert!(Config::from_toml(r#"long-paths = "truncate""#).is_err());
+    }
+
+    #[test]
+    fn test_hunk_header() {
+        let config = Config::from_toml(r#"hunk-header = "{function}""#).unwrap();
+        assert_eq!(config.hunk_header, Some("{function}".to_string()));
+
+        assert_eq!(
+            Config::from_toml(r#"hunk-header = "{fn}""#).err(),
+            Some(
+                "hunk-header: Unknown placeholder {fn}, must be one of {header}, {function}, {old_lines}, {new_lines}, {ranges}"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_theme() {
+        let config = Config::from_toml(
+            r#"
+            [theme]
+            punctuation = "faint"
+            "#,
+        )
+        .unwrap();
+
+        let mut expected = Theme::default();
+        expected.set_weight(TokenClass::Punctuation, crate::ansi::Weight::Faint);
+        assert_eq!(config.theme, expected);
+
+        assert_eq!(
+            Config::from_toml("[theme]\nmonkey = \"faint\""),
+            Err("theme: Unknown token class: monkey".to_string())
+        );
+        assert!(Config::from_toml("[theme]\nnumber = \"blinking\"").is_err());
+    }
+
+    #[test]
+    fn test_theme_rules() {
+        let config = Config::from_toml(
+            r#"
+            [theme]
+            punctuation = "faint"
+
+            [theme.rules]
+            todo = { regex = "TODO|FIXME", weight = "bold", class = "identifier" }
+            "#,
+        )
+        .unwrap();
+
+        let mut expected = Theme::default();
+        expected.set_weight(TokenClass::Punctuation, crate::ansi::Weight::Faint);
+        expected.add_rule(
+            TokenStyleRule::new(
+                "todo",
+                "TODO|FIXME",
+                Some(TokenClass::Identifier),
+                crate::ansi::Weight::Bold,
+            )
+            .unwrap(),
+        );
+        assert_eq!(config.theme, expected);
+
+        assert_eq!(
+            Config::from_toml(
+                "[theme.rules]
+todo = { regex = \"TODO\", weight = \"bold\", class = \"monkey\" }"
+            ),
+            Err("theme.rules.todo: Unknown token class: monkey".to_string())
+        );
+        assert!(Config::from_toml(
+            "[theme.rules]
+todo = { regex = \"(\", weight = \"bold\" }"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_named_themes() {
+        let config = Config::from_toml(r#"theme = "faint-punctuation""#).unwrap();
+        assert_eq!(config.theme, Theme::built_in("faint-punctuation").unwrap());
+
+        let config = Config::from_toml(
+            r#"
+            theme = "mine"
+
+            [themes.mine]
+            number = "bold"
+            "#,
+        )
+        .unwrap();
+        let mut expected = Theme::default();
+        expected.set_weight(TokenClass::Number, crate::ansi::Weight::Bold);
+        assert_eq!(config.theme, expected);
+        assert_eq!(config.themes, [("mine".to_string(), expected)]);
+
+        assert_eq!(
+            Config::from_toml(r#"theme = "monkey""#),
+            Err("Unknown theme: monkey".to_string())
+        );
+    }
+
+    #[test]
+    fn test_default_config_toml() {
+        // The generated config must parse, and must not change anything
+        assert_eq!(
+            Config::from_toml(&default_config_toml()).unwrap(),
+            Config::default()
+        );
+    }
+
+    #[test]
+    fn test_unknown_setting() {
+        assert_eq!(
+            Config::from_toml("monkey = true"),
+            Err("Unknown setting: monkey".to_string())
+        );
+    }
+}
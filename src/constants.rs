@@ -1,7 +1,13 @@
 pub const OLD: &str = "\x1b[31m"; // Red
 pub const NEW: &str = "\x1b[32m"; // Green
 
+// Darker shades for lines without a counterpart, with `unpaired-lines =
+// "shaded"`: https://en.wikipedia.org/wiki/ANSI_escape_code#8-bit
+pub const OLD_UNPAIRED: &str = "\x1b[38;5;124m"; // Dark red
+pub const NEW_UNPAIRED: &str = "\x1b[38;5;28m"; // Dark green
+
 pub const INVERSE_VIDEO: &str = "\x1b[7m";
+pub const NOT_INVERSE_VIDEO: &str = "\x1b[27m";
 
 pub const NO_EOF_NEWLINE_COLOR: &str = "\x1b[2m"; // Faint
 
@@ -17,4 +23,32 @@ pub const RED: &str = "\x1b[31m";
 // Dark blue: https://en.wikipedia.org/wiki/ANSI_escape_code#8-bit
 pub const BLUE_TO_END_OF_LINE: &str = "\x1b[48;5;17m\x1b[0K";
 
+// Hot to cold, for blame line ages: https://en.wikipedia.org/wiki/ANSI_escape_code#8-bit
+pub const BLAME_HEAT: [&str; 5] = [
+    "\x1b[38;5;196m", // Red
+    "\x1b[38;5;208m", // Orange
+    "\x1b[38;5;220m", // Yellow
+    "\x1b[38;5;109m", // Grayish blue
+    "\x1b[38;5;244m", // Gray
+];
+
+/// Backgrounds for `--age-heatmap` gutters, from the most recent changes to
+/// the oldest
+pub const AGE_HEAT_BACKGROUND: [&str; 5] = [
+    "\x1b[48;5;52m",  // Dark red
+    "\x1b[48;5;94m",  // Brown
+    "\x1b[48;5;58m",  // Olive
+    "\x1b[48;5;23m",  // Dark teal
+    "\x1b[48;5;236m", // Dark gray
+];
+
+/// Background of `--ruler` columns
+pub const RULER_BACKGROUND: &str = "\x1b[48;5;238m"; // Gray
+
+/// Background of likely secrets in added lines
+pub const SECRET_BACKGROUND: &str = "\x1b[41m"; // Red
+
+pub const DEFAULT_COLOR: &str = "\x1b[39m";
+pub const DEFAULT_BACKGROUND: &str = "\x1b[49m";
+
 pub const NORMAL: &str = "\x1b[0m";
@@ -0,0 +1,130 @@
+use crate::ansi::{parts, sgr_parameters};
+use crate::config::ControlCharacters;
+use std::borrow::Cow;
+
+/// TABs are whitespace, not control characters
+fn is_control(c: char) -> bool {
+    return c != '\t' && c.is_control();
+}
+
+/// Caret notation, like `cat -v`: `^[` for ESC, `^?` for DEL and `M-^[` for
+/// the C1 version of ESC
+fn push_caret(result: &mut String, c: char) {
+    let code = c as u32;
+    if code >= 0x80 {
+        result.push_str("M-");
+    }
+    if code & 0x7f == 0x7f {
+        result.push_str("^?");
+        return;
+    }
+    result.push('^');
+    result.push(char::from(((code & 0x7f) as u8) + 0x40));
+}
+
+/// Apply the `control-characters` policy to a line where the color escape
+/// codes have already been removed.
+///
+/// This is what keeps a malicious diff from smuggling escape sequences, like
+/// the OSC 52 clipboard one, to the reviewer's terminal.
+#[must_use]
+pub fn sanitize(line: &str, policy: ControlCharacters) -> Cow<'_, str> {
+    if policy == ControlCharacters::Pass || !line.chars().any(is_control) {
+        return Cow::Borrowed(line);
+    }
+
+    let mut result = String::with_capacity(line.len() + 8);
+    for c in line.chars() {
+        if !is_control(c) {
+            result.push(c);
+        } else if policy == ControlCharacters::Escape {
+            push_caret(&mut result, c);
+        }
+    }
+    return Cow::Owned(result);
+}
+
+/// Like [`sanitize`], but for lines that still have their colors, like the
+/// non-diff lines of `--embedded`. Color (SGR) escape sequences are kept, other
+/// escape sequences get the `control-characters` policy.
+#[must_use]
+pub fn sanitize_keeping_colors(line: &str, policy: ControlCharacters) -> Cow<'_, str> {
+    if policy == ControlCharacters::Pass || is_colors_only(line) {
+        return Cow::Borrowed(line);
+    }
+
+    let mut result = String::with_capacity(line.len() + 8);
+    for part in parts(line) {
+        match part {
+            Err(escape) if sgr_parameters(escape).is_some() => result.push_str(escape),
+            Err(escape) => result.push_str(&sanitize(escape, policy)),
+            Ok(c) => {
+                let mut buffer = [0; 4];
+                result.push_str(&sanitize(c.encode_utf8(&mut buffer), policy));
+            }
+        }
+    }
+    return Cow::Owned(result);
+}
+
+/// True if the only control characters in `line` are parts of color (SGR)
+/// escape sequences
+#[must_use]
+pub fn is_colors_only(line: &str) -> bool {
+    return parts(line).all(|part| match part {
+        Err(escape) => sgr_parameters(escape).is_some(),
+        Ok(c) => !is_control(c),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_sanitize() {
+        let line = "+a\tb\x07c\x1b]52;c;ZXZpbA==\x07d\x7f\u{9b}";
+        assert_eq!(
+            sanitize(line, ControlCharacters::Escape),
+            "+a\tb^Gc^[]52;c;ZXZpbA==^Gd^?M-^["
+        );
+        assert_eq!(
+            sanitize(line, ControlCharacters::Strip),
+            "+a\tbc]52;c;ZXZpbA==d"
+        );
+        assert_eq!(sanitize(line, ControlCharacters::Pass), line);
+        assert_eq!(sanitize("+a\tb", ControlCharacters::Escape), "+a\tb");
+    }
+
+    #[test]
+    fn test_sanitize_keeping_colors() {
+        // OSC 52 writes to the clipboard
+        let line = "\x1b[31mred\x1b[0m\x1b]52;c;ZXZpbA==\x07\x1b[2J";
+        assert_eq!(
+            sanitize_keeping_colors(line, ControlCharacters::Escape),
+            "\x1b[31mred\x1b[0m^[]52;c;ZXZpbA==^G^[[2J"
+        );
+        assert_eq!(
+            sanitize_keeping_colors(line, ControlCharacters::Strip),
+            "\x1b[31mred\x1b[0m]52;c;ZXZpbA==[2J"
+        );
+        assert_eq!(sanitize_keeping_colors(line, ControlCharacters::Pass), line);
+        assert_eq!(
+            sanitize_keeping_colors("\x1b[1;31mx\x1b[m", ControlCharacters::Escape),
+            "\x1b[1;31mx\x1b[m"
+        );
+    }
+
+    #[test]
+    fn test_is_colors_only() {
+        assert!(is_colors_only("\x1b[31m-a\tb\x1b[0m"));
+        assert!(is_colors_only("plain"));
+        assert!(!is_colors_only("\x1b[31m-a\x1b[2J"));
+        assert!(!is_colors_only("\x1b]8;;https://example.com\x07x"));
+        assert!(!is_colors_only("a\x08b"));
+        assert!(!is_colors_only("unterminated \x1b"));
+    }
+}
@@ -0,0 +1,249 @@
+use crate::config::Config;
+use crate::git_objects::GitObjects;
+use crate::line_diff::{unified_diff, CONTEXT_LINES, MAX_LINES_PRODUCT};
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::thread;
+
+/// Parse `index 25ab17b..1b5b67f 100644` into `("25ab17b", "1b5b67f")`
+#[must_use]
+pub fn parse_index_line(line: &str) -> Option<(String, String)> {
+    let hashes = line.strip_prefix("index ")?.split(' ').next()?;
+    let (old_hash, new_hash) = hashes.split_once("..")?;
+    if old_hash.contains(',') {
+        // Combined diff, we only handle two sides
+        return None;
+    }
+
+    return Some((old_hash.to_string(), new_hash.to_string()));
+}
+
+/// Parse `Binary files a/x.pdf and b/x.pdf differ` into `("a/x.pdf",
+/// "b/x.pdf")`
+#[must_use]
+pub fn parse_binary_files_line(line: &str) -> Option<(&str, &str)> {
+    return line
+        .strip_prefix("Binary files ")?
+        .strip_suffix(" differ")?
+        .split_once(" and ");
+}
+
+/// The configured converter command for a binary file type, if any
+#[must_use]
+pub fn converter_for<'a>(config: &'a Config, old_path: &str, new_path: &str) -> Option<&'a str> {
+    let named_path = if new_path == "/dev/null" {
+        old_path
+    } else {
+        new_path
+    };
+    let (_, extension) = named_path.rsplit_once('.')?;
+    return config
+        .converters
+        .get(&extension.to_lowercase())
+        .map(String::as_str);
+}
+
+/// Converts the binary blobs from a `Binary files ... differ` section into
+/// text using the configured converter for the file type, and returns a
+/// unified diff of the results.
+///
+/// Returns `None` if there is no converter for this file type, if we can't get
+/// the blobs from git, or if the converter fails. The caller should then just
+/// print the binary files line as usual.
+#[must_use]
+pub fn diff_binary_files(
+    config: &Config,
+    git_objects: &GitObjects,
+    hashes: &(String, String),
+    old_path: &str,
+    new_path: &str,
+) -> Option<Vec<String>> {
+    let converter = converter_for(config, old_path, new_path)?;
+
+    let old_text = convert(converter, &get_blob(git_objects, &hashes.0, old_path)?)?;
+    let new_text = convert(converter, &get_blob(git_objects, &hashes.1, new_path)?)?;
+
+    let mut lines = vec![format!("--- {old_path}"), format!("+++ {new_path}")];
+    lines.append(&mut unified_diff(
+        &old_text,
+        &new_text,
+        CONTEXT_LINES,
+        MAX_LINES_PRODUCT,
+    )?);
+    return Some(lines);
+}
+
+/// Get blob contents from the git repository we're currently in.
+///
+/// Uncommitted files aren't in the repository, so for those we check whether
+/// the work tree file has the expected hash and use that instead.
+#[must_use]
+fn get_blob(git_objects: &GitObjects, hash: &str, path: &str) -> Option<Vec<u8>> {
+    if hash.chars().all(|c| c == '0') {
+        // File doesn't exist on this side
+        return Some(vec![]);
+    }
+
+    if let Some(blob) = git_objects.blob(hash) {
+        return Some(blob);
+    }
+
+    // Strip git's a/ or b/ prefix
+    let path = path
+        .strip_prefix("a/")
+        .or_else(|| path.strip_prefix("b/"))
+        .unwrap_or(path);
+    let output = Command::new("git")
+        .arg("hash-object")
+        .arg("--")
+        .arg(path)
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() || !output.stdout.starts_with(hash.as_bytes()) {
+        return None;
+    }
+
+    return fs::read(path).ok();
+}
+
+/// Run a converter command on some binary contents, returning its output
+#[must_use]
+fn convert(converter: &str, contents: &[u8]) -> Option<String> {
+    if contents.is_empty() {
+        return Some(String::new());
+    }
+
+    let mut argv: Vec<String> = converter.split_whitespace().map(str::to_string).collect();
+    if argv.is_empty() {
+        return None;
+    }
+
+    // Keep this alive until the converter is done with it
+    let mut tempfile: Option<tempfile::NamedTempFile> = None;
+    if argv.iter().any(|arg| arg == "{}") {
+        let mut file = tempfile::NamedTempFile::new().ok()?;
+        file.write_all(contents).ok()?;
+        let path = file.path().to_string_lossy().to_string();
+        for arg in argv.iter_mut() {
+            if arg == "{}" {
+                *arg = path.clone();
+            }
+        }
+        tempfile = Some(file);
+    }
+
+    let mut command = Command::new(&argv[0]);
+    command
+        .args(&argv[1..])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null());
+    if tempfile.is_none() {
+        command.stdin(Stdio::piped());
+    } else {
+        command.stdin(Stdio::null());
+    }
+
+    let mut child = command.spawn().ok()?;
+    let writer = child.stdin.take().map(|mut stdin| {
+        // Write from a separate thread so the converter can't deadlock on a
+        // full stdout pipe while we're still writing to its stdin
+        let contents = contents.to_vec();
+        thread::spawn(move || {
+            // Converters are allowed to stop reading early
+            let _ = stdin.write_all(&contents);
+        })
+    });
+
+    let output = child.wait_with_output().ok()?;
+    if let Some(writer) = writer {
+        writer.join().ok()?;
+    }
+    drop(tempfile);
+
+    if !output.status.success() {
+        return None;
+    }
+
+    return Some(String::from_utf8_lossy(&output.stdout).to_string());
+}
+
+/// For renamed files where the diff shows no changes even though the contents
+/// differ, like with `git diff -M -w`, diff the old and new blobs ourselves.
+///
+/// Returns `None` if the blobs can't be found, aren't text or are identical.
+#[must_use]
+pub fn diff_renamed_file(
+    git_objects: &GitObjects,
+    hashes: &(String, String),
+    old_path: &str,
+    new_path: &str,
+) -> Option<Vec<String>> {
+    if hashes.0 == hashes.1 {
+        return None;
+    }
+
+    let old_text = String::from_utf8(get_blob(git_objects, &hashes.0, old_path)?).ok()?;
+    let new_text = String::from_utf8(get_blob(git_objects, &hashes.1, new_path)?).ok()?;
+    let mut diff = unified_diff(&old_text, &new_text, CONTEXT_LINES, MAX_LINES_PRODUCT)?;
+    if diff.is_empty() {
+        return None;
+    }
+
+    let mut lines = vec![format!("--- a/{old_path}"), format!("+++ b/{new_path}")];
+    lines.append(&mut diff);
+    return Some(lines);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_parse_index_line() {
+        assert_eq!(
+            parse_index_line("index 25ab17b..1b5b67f 100644"),
+            Some(("25ab17b".to_string(), "1b5b67f".to_string()))
+        );
+        assert_eq!(
+            parse_index_line("index 0000000..1b5b67f"),
+            Some(("0000000".to_string(), "1b5b67f".to_string()))
+        );
+        assert_eq!(parse_index_line("index 1234567,89abcde..1b5b67f"), None);
+    }
+
+    #[test]
+    fn test_parse_binary_files_line() {
+        assert_eq!(
+            parse_binary_files_line("Binary files a/x.pdf and b/x.pdf differ"),
+            Some(("a/x.pdf", "b/x.pdf"))
+        );
+        assert_eq!(
+            parse_binary_files_line("Binary files /dev/null and b/x.pdf differ"),
+            Some(("/dev/null", "b/x.pdf"))
+        );
+        assert_eq!(parse_binary_files_line("Binary files are nice"), None);
+    }
+
+    #[test]
+    fn test_converter_for() {
+        let config = Config::from_toml("[converters]\npdf = \"pdftotext - -\"").unwrap();
+        assert!(converter_for(&config, "a/x.PDF", "b/x.PDF").is_some());
+        assert_eq!(
+            converter_for(&config, "a/x.pdf", "/dev/null"),
+            converter_for(&config, "/dev/null", "b/x.pdf")
+        );
+        assert_eq!(converter_for(&config, "a/x.png", "b/x.png"), None);
+        assert_eq!(converter_for(&config, "a/Makefile", "b/Makefile"), None);
+
+        // No converters unless configured
+        assert_eq!(
+            converter_for(&Config::default(), "a/x.pdf", "b/x.pdf"),
+            None
+        );
+    }
+}
@@ -0,0 +1,222 @@
+use crate::ansi::parts;
+use crate::eof_marker;
+use crate::hunk::Hunk;
+use crate::token_collector::{Style, StyledToken};
+use std::io::{self, Write};
+
+/// Drawn rather than read: similarity bars, rulers and line ending markers
+const DECORATIVE_CHARACTERS: &[char] = &['█', '░', '━', '⏎', '␍'];
+
+/// One row of refined tokens as text, like `removed: my old name; changed
+/// part: old`
+fn describe_row(description: &str, row: &[StyledToken]) -> String {
+    let text: String = row.iter().map(StyledToken::token).collect();
+    let mut described = format!("{description}{text}");
+    for run in row.chunk_by(|a, b| a.style() == b.style()) {
+        let run_text: String = run.iter().map(StyledToken::token).collect();
+        match run[0].style() {
+            Style::Highlighted if run_text.trim().is_empty() => {
+                described.push_str("; changed part: whitespace");
+            }
+            Style::Highlighted => {
+                described.push_str(&format!("; changed part: {}", run_text.trim()));
+            }
+            Style::Error => described.push_str("; whitespace error"),
+            _ => {}
+        }
+    }
+    return described;
+}
+
+/// With `--describe`, rows of refined tokens as text for screen readers.
+///
+/// Each row starts with `description`, like `removed: `, and ends with the
+/// parts of it that changed. Rows are separated by newlines, like
+/// [`render`](crate::token_collector::render) separates them.
+#[must_use]
+pub fn describe_rows(description: &str, tokens: &[StyledToken]) -> String {
+    let mut described = String::new();
+    let mut rows = tokens.split(|token| token.token() == "\n").peekable();
+    while let Some(row) = rows.next() {
+        let is_last = rows.peek().is_none();
+        if is_last && row.is_empty() {
+            break;
+        }
+        described.push_str(&describe_row(description, row));
+        if !is_last {
+            described.push('\n');
+        }
+    }
+    return described;
+}
+
+/// With `--describe`, removed and added lines that weren't refined, as text
+/// for screen readers
+#[must_use]
+pub fn describe_simple(hunk: &Hunk) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in hunk.old_line_texts() {
+        lines.push(format!("removed: {line}"));
+    }
+    if !hunk.old_lines.is_empty() && hunk.old_missing_eof_newline {
+        lines.push(eof_marker::get());
+    }
+    for line in hunk.new_line_texts() {
+        lines.push(format!("added: {line}"));
+    }
+    if !hunk.new_lines.is_empty() && hunk.new_missing_eof_newline {
+        lines.push(eof_marker::get());
+    }
+    return lines;
+}
+
+/// Make one line of output read well through a screen reader, by leaving out
+/// escape sequences and decorative characters.
+///
+/// Removed and added lines have been described already, see
+/// [`describe_rows`].
+#[must_use]
+pub fn describe_line(line: &str) -> String {
+    let text: String = parts(line)
+        .filter_map(Result::ok)
+        .filter(|c| !DECORATIVE_CHARACTERS.contains(c))
+        .collect();
+    return text.replace('⚠', "warning:").trim_end().to_string();
+}
+
+/// With `--describe`, converts the output into text for screen readers, see
+/// [`describe_line`].
+pub struct Describe<W: Write> {
+    output: W,
+
+    /// Output bytes since the last newline
+    line: Vec<u8>,
+}
+
+impl<W: Write> Describe<W> {
+    pub fn new(output: W) -> Describe<W> {
+        return Describe {
+            output,
+            line: Vec::new(),
+        };
+    }
+
+    fn write_line(&mut self) -> io::Result<()> {
+        let described = describe_line(&String::from_utf8_lossy(&self.line));
+        self.line.clear();
+        return self.output.write_all(described.as_bytes());
+    }
+}
+
+impl<W: Write> Write for Describe<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for byte in buf {
+            if *byte == b'\n' {
+                self.write_line()?;
+                self.output.write_all(b"\n")?;
+            } else {
+                self.line.push(*byte);
+            }
+        }
+        return Ok(buf.len());
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        return self.output.flush();
+    }
+}
+
+impl<W: Write> Drop for Describe<W> {
+    fn drop(&mut self) {
+        // The last line, if it didn't end in a newline
+        if !self.line.is_empty() {
+            let _ = self.write_line();
+        }
+        let _ = self.output.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    fn tokens(parts: &[(&str, Style)]) -> Vec<StyledToken> {
+        return parts
+            .iter()
+            .map(|(token, style)| StyledToken::new(token.to_string(), *style))
+            .collect();
+    }
+
+    #[test]
+    fn test_describe_rows() {
+        let old = tokens(&[
+            ("my", Style::Plain),
+            (" ", Style::Plain),
+            ("old", Style::Highlighted),
+            (" ", Style::Plain),
+            ("name", Style::Plain),
+            ("\n", Style::Plain),
+            ("x", Style::Plain),
+            ("  ", Style::Highlighted),
+            ("\n", Style::Plain),
+        ]);
+        assert_eq!(
+            describe_rows("removed: ", &old),
+            "removed: my old name; changed part: old\nremoved: x  ; changed part: whitespace\n"
+        );
+
+        let new = tokens(&[
+            ("a", Style::Highlighted),
+            (" ", Style::Highlighted),
+            ("b", Style::Highlighted),
+            ("c", Style::Plain),
+            (" ", Style::Error),
+        ]);
+        assert_eq!(
+            describe_rows("added: ", &new),
+            "added: a bc ; changed part: a b; whitespace error"
+        );
+    }
+
+    #[test]
+    fn test_describe_simple() {
+        assert_eq!(
+            describe_simple(&Hunk::from_texts("a\n", "b\nc\n")),
+            ["removed: a", "added: b", "added: c"]
+        );
+    }
+
+    #[test]
+    fn test_describe_line() {
+        // Bold file headers
+        assert_eq!(describe_line("\x1b[1m--- a/x.rs\x1b[0m"), "--- a/x.rs");
+        assert_eq!(describe_line(" context"), " context");
+
+        assert_eq!(
+            describe_line(
+                "\x1b[2msimilarity index \x1b[0m\x1b[32m92% █████████\x1b[0m\x1b[2m░\x1b[0m"
+            ),
+            "similarity index 92%"
+        );
+        assert_eq!(
+            describe_line("\x1b[1m\x1b[31m⚠ diff appears truncated\x1b[0m"),
+            "warning: diff appears truncated"
+        );
+    }
+
+    #[test]
+    fn test_describe_writer() {
+        let mut output = Vec::new();
+        {
+            let mut writer = Describe::new(&mut output);
+            writer
+                .write_all(b"\x1b[1m+++ b/x\x1b[0m\nadded: a")
+                .unwrap();
+            writer.write_all(b"c\x1b[0m").unwrap();
+        }
+        assert_eq!(String::from_utf8(output).unwrap(), "+++ b/x\nadded: ac");
+    }
+}
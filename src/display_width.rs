@@ -0,0 +1,45 @@
+use unicode_width::UnicodeWidthChar;
+
+/// Like `less`, we put TAB stops every eight columns
+const TAB_WIDTH: usize = 8;
+
+/// How many columns `c` occupies, if it starts at `column`. East Asian wide
+/// characters take two columns, combining marks and control characters none.
+#[must_use]
+pub fn char_width(c: char, column: usize) -> usize {
+    if c == '\t' {
+        return TAB_WIDTH - column % TAB_WIDTH;
+    }
+    return c.width().unwrap_or(0);
+}
+
+/// How many terminal columns `text` takes, starting at the first column. See
+/// [`char_width`].
+#[must_use]
+pub fn display_width(text: &str) -> usize {
+    let mut column = 0;
+    for c in text.chars() {
+        column += char_width(c, column);
+    }
+    return column;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_display_width() {
+        assert_eq!(display_width("abc"), 3);
+        assert_eq!(display_width("日本"), 4);
+        assert_eq!(display_width("e\u{301}"), 1);
+        assert_eq!(display_width(""), 0);
+
+        // TABs go to the next TAB stop
+        assert_eq!(display_width("\t"), 8);
+        assert_eq!(display_width("ab\tc"), 9);
+    }
+}
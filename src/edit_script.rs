@@ -0,0 +1,216 @@
+/// Which kind of edit script we're reading
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+    /// `diff --ed`: Commands like `3,5c` followed by text lines up to a lone
+    /// `.`
+    Ed,
+
+    /// `diff --rcs`: Commands like `a3 2` followed by exactly that many text
+    /// lines
+    Rcs,
+}
+
+/// What a line in an edit script is
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScriptLine {
+    /// A command removing lines
+    Delete,
+
+    /// A command adding or changing lines, followed by text
+    Insert,
+
+    /// Text added by the preceding command
+    Text,
+
+    /// End of text marker, ed only
+    End,
+
+    /// `s/.//`, which diff uses for adding lines consisting of a lone `.`.
+    /// Ed only.
+    Substitute,
+}
+
+/// Edit scripts (`diff --ed` and `diff --rcs`) contain only the new text, so
+/// there is nothing to refine. But we can still color them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EditScript {
+    format: Format,
+
+    /// Ed: `Some(0)` while reading text. RCS: Number of text lines left.
+    text_lines_left: Option<usize>,
+}
+
+/// Parse ed commands like `3,5c` into their command character
+fn parse_ed_command(line: &str) -> Option<char> {
+    let command = line.chars().last()?;
+    if !"acd".contains(command) {
+        return None;
+    }
+
+    let range = &line[..line.len() - 1];
+    let (first, last) = range.split_once(',').unwrap_or((range, "0"));
+    first.parse::<usize>().ok()?;
+    last.parse::<usize>().ok()?;
+    return Some(command);
+}
+
+/// Parse RCS commands like `a3 2` into their command character and line count
+fn parse_rcs_command(line: &str) -> Option<(char, usize)> {
+    let command = line.chars().next()?;
+    if command != 'a' && command != 'd' {
+        return None;
+    }
+
+    let (start, count) = line[1..].split_once(' ')?;
+    start.parse::<usize>().ok()?;
+    return Some((command, count.parse().ok()?));
+}
+
+impl EditScript {
+    /// Recognize `diff -e a b` style lines, after which a script will follow
+    #[must_use]
+    pub fn after_diff_line(line: &str) -> Option<EditScript> {
+        let mut words = line.split_whitespace();
+        if words.next() != Some("diff") {
+            return None;
+        }
+
+        let mut format = None;
+        for word in words {
+            match word {
+                "-e" | "--ed" => format = Some(Format::Ed),
+                "-n" | "--rcs" => format = Some(Format::Rcs),
+                _ => {}
+            }
+        }
+
+        return Some(EditScript {
+            format: format?,
+            text_lines_left: None,
+        });
+    }
+
+    /// Input starting with an edit script command is an edit script
+    #[must_use]
+    pub fn from_first_line(line: &str) -> Option<EditScript> {
+        let format = if parse_ed_command(line).is_some() {
+            Format::Ed
+        } else if parse_rcs_command(line).is_some() {
+            Format::Rcs
+        } else {
+            return None;
+        };
+
+        return Some(EditScript {
+            format,
+            text_lines_left: None,
+        });
+    }
+
+    /// Classify the next line of the script. Returns `None` if the script has
+    /// ended.
+    pub fn classify(&mut self, line: &str) -> Option<ScriptLine> {
+        match self.format {
+            Format::Ed => {
+                if self.text_lines_left.is_some() {
+                    if line == "." {
+                        self.text_lines_left = None;
+                        return Some(ScriptLine::End);
+                    }
+                    return Some(ScriptLine::Text);
+                }
+
+                if line == "s/.//" {
+                    return Some(ScriptLine::Substitute);
+                }
+
+                return match parse_ed_command(line)? {
+                    'd' => Some(ScriptLine::Delete),
+                    _ => {
+                        self.text_lines_left = Some(0);
+                        Some(ScriptLine::Insert)
+                    }
+                };
+            }
+
+            Format::Rcs => {
+                if let Some(lines_left) = self.text_lines_left {
+                    self.text_lines_left = Some(lines_left - 1).filter(|left| *left > 0);
+                    return Some(ScriptLine::Text);
+                }
+
+                return match parse_rcs_command(line)? {
+                    ('d', _) => Some(ScriptLine::Delete),
+                    (_, count) => {
+                        self.text_lines_left = Some(count).filter(|count| *count > 0);
+                        Some(ScriptLine::Insert)
+                    }
+                };
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    fn classify_all(script: &mut EditScript, lines: &[&str]) -> Vec<Option<ScriptLine>> {
+        return lines.iter().map(|line| script.classify(line)).collect();
+    }
+
+    #[test]
+    fn test_ed() {
+        use ScriptLine::*;
+
+        let mut script = EditScript::from_first_line("5,6c").unwrap();
+        assert_eq!(
+            classify_all(
+                &mut script,
+                &["5,6c", "new", "3d", "..", ".", "s/.//", "3d", "1a", ".", "not ed"]
+            ),
+            [
+                Some(Insert),
+                Some(Text),
+                Some(Text),
+                Some(Text),
+                Some(End),
+                Some(Substitute),
+                Some(Delete),
+                Some(Insert),
+                Some(End),
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rcs() {
+        use ScriptLine::*;
+
+        let mut script = EditScript::after_diff_line("diff -n a.txt b.txt").unwrap();
+        assert_eq!(
+            classify_all(&mut script, &["d3 1", "a4 2", "d1 1", "x", "a9 0", "x"]),
+            [
+                Some(Delete),
+                Some(Insert),
+                Some(Text),
+                Some(Text),
+                Some(Insert),
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_not_scripts() {
+        assert_eq!(EditScript::from_first_line("diff --git a/x b/x"), None);
+        assert_eq!(EditScript::from_first_line("commit 1234"), None);
+        assert_eq!(EditScript::from_first_line("c"), None);
+        assert_eq!(EditScript::after_diff_line("diff -u a b"), None);
+        assert_eq!(EditScript::after_diff_line("differs -e"), None);
+    }
+}
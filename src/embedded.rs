@@ -0,0 +1,232 @@
+use crate::eof_marker;
+use crate::hunk_header::HunkHeader;
+
+/// Lines that can come between a `diff` line and the first hunk
+const FILE_HEADER_PREFIXES: &[&str] = &[
+    "diff ",
+    "index ",
+    "old mode ",
+    "new mode ",
+    "new file mode ",
+    "deleted file mode ",
+    "similarity index ",
+    "dissimilarity index ",
+    "rename from ",
+    "rename to ",
+    "copy from ",
+    "copy to ",
+    "Binary files ",
+    "+++ ",
+];
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Verdict {
+    /// This line is part of a diff, highlight it
+    Diff,
+
+    /// This line is not part of a diff, pass it through unchanged
+    Text,
+
+    /// This might be the start of a diff, but we need to see the next line to
+    /// know. Call `classify()` with the next line to find out.
+    Undecided,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum State {
+    /// Not in a diff
+    Outside,
+
+    /// We just saw a `--- ` line while outside of a diff, a `+++ ` line would
+    /// make this a diff
+    MaybeFileHeader,
+
+    /// In a diff, after a `diff` or `---` / `+++` line but before any hunks
+    FileHeader,
+
+    /// In a hunk, with this many old and new lines remaining
+    Hunk(usize, usize),
+
+    /// In a diff, between hunks or after the last hunk of a file
+    BetweenHunks,
+}
+
+/// Finds diffs embedded in other text, like build logs or test framework
+/// output.
+///
+/// Feed it one line at a time through `classify()`.
+pub struct IslandDetector {
+    state: State,
+}
+
+impl IslandDetector {
+    pub fn new() -> IslandDetector {
+        return IslandDetector {
+            state: State::Outside,
+        };
+    }
+
+    #[must_use]
+    pub fn classify(&mut self, line: &str) -> Verdict {
+        if let State::Hunk(old_left, new_left) = self.state {
+            let (old_left, new_left) = if line.starts_with('-') && old_left > 0 {
+                (old_left - 1, new_left)
+            } else if line.starts_with('+') && new_left > 0 {
+                (old_left, new_left - 1)
+            } else if (line.starts_with(' ') || line.is_empty()) && old_left > 0 && new_left > 0 {
+                // Some tools strip the trailing space off of empty context lines
+                (old_left - 1, new_left - 1)
+            } else if eof_marker::is_marker(line) {
+                (old_left, new_left)
+            } else {
+                // Hunk ended prematurely, we're not in Kansas anymore
+                self.state = State::Outside;
+                return self.classify(line);
+            };
+
+            if old_left == 0 && new_left == 0 {
+                self.state = State::BetweenHunks;
+            } else {
+                self.state = State::Hunk(old_left, new_left);
+            }
+            return Verdict::Diff;
+        }
+
+        if let Some(hunk_header) = HunkHeader::parse(line) {
+            if hunk_header.old_count == 0 && hunk_header.new_count == 0 {
+                self.state = State::BetweenHunks;
+            } else {
+                self.state = State::Hunk(hunk_header.old_count, hunk_header.new_count);
+            }
+            return Verdict::Diff;
+        }
+
+        if line.starts_with("diff ") {
+            self.state = State::FileHeader;
+            return Verdict::Diff;
+        }
+
+        match self.state {
+            State::Outside => {
+                if line.starts_with("--- ") {
+                    self.state = State::MaybeFileHeader;
+                    return Verdict::Undecided;
+                }
+                return Verdict::Text;
+            }
+            State::MaybeFileHeader => {
+                if line.starts_with("+++ ") {
+                    self.state = State::FileHeader;
+                    return Verdict::Diff;
+                }
+
+                self.state = State::Outside;
+                return self.classify(line);
+            }
+            State::FileHeader | State::BetweenHunks => {
+                if line.starts_with("--- ")
+                    || (self.state == State::BetweenHunks && eof_marker::is_marker(line))
+                    || FILE_HEADER_PREFIXES
+                        .iter()
+                        .any(|prefix| line.starts_with(prefix))
+                {
+                    self.state = State::FileHeader;
+                    return Verdict::Diff;
+                }
+
+                self.state = State::Outside;
+                return self.classify(line);
+            }
+            State::Hunk(_, _) => unreachable!("Hunks are handled above"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    fn classify_all(lines: &[&str]) -> Vec<Verdict> {
+        let mut detector = IslandDetector::new();
+        return lines.iter().map(|line| detector.classify(line)).collect();
+    }
+
+    #[test]
+    fn test_plain_text() {
+        assert_eq!(
+            classify_all(&["Compiling...", "- not a diff", "+ neither is this"]),
+            vec![Verdict::Text, Verdict::Text, Verdict::Text]
+        );
+    }
+
+    #[test]
+    fn test_embedded_hunk() {
+        assert_eq!(
+            classify_all(&[
+                "Test failed:",
+                "--- expected",
+                "+++ actual",
+                "@@ -1,2 +1,2 @@",
+                " same",
+                "-old",
+                "+new",
+                "- list item after the diff",
+                "Done",
+            ]),
+            vec![
+                Verdict::Text,
+                Verdict::Undecided,
+                Verdict::Diff,
+                Verdict::Diff,
+                Verdict::Diff,
+                Verdict::Diff,
+                Verdict::Diff,
+                Verdict::Text,
+                Verdict::Text,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_minus_minus_minus_without_plus() {
+        assert_eq!(
+            classify_all(&["--- Summary ---", "All good"]),
+            vec![Verdict::Undecided, Verdict::Text]
+        );
+    }
+
+    #[test]
+    fn test_git_diff() {
+        assert_eq!(
+            classify_all(&[
+                "diff --git a/x b/x",
+                "index 1234567..89abcde 100644",
+                "--- a/x",
+                "+++ b/x",
+                "@@ -1 +1 @@",
+                "-a",
+                "+b",
+                "\\ No newline at end of file",
+                "diff --git a/y b/y",
+                "Binary files a/y and b/y differ",
+                "[INFO] Build finished",
+            ]),
+            vec![
+                Verdict::Diff,
+                Verdict::Diff,
+                Verdict::Diff,
+                Verdict::Diff,
+                Verdict::Diff,
+                Verdict::Diff,
+                Verdict::Diff,
+                Verdict::Diff,
+                Verdict::Diff,
+                Verdict::Diff,
+                Verdict::Text,
+            ]
+        );
+    }
+}
@@ -0,0 +1,245 @@
+use encoding_rs::{CoderResult, Decoder, Encoding, UTF_16BE, UTF_16LE, UTF_8};
+use std::io;
+
+/// Wraps an input stream and converts it into UTF-8.
+///
+/// UTF-8 and UTF-16 inputs are recognized by their byte order marks. UTF-16
+/// without a byte order mark is recognized by the zero bytes ASCII characters
+/// get in UTF-16.
+///
+/// Anything else is passed through unchanged, see [`decode_line`] for how we
+/// handle non-UTF-8 lines in there.
+pub struct DecodingReader<'a> {
+    input: &'a mut dyn io::Read,
+
+    /// `None` until we have looked at the start of the stream, and for inputs
+    /// that need no decoding
+    decoder: Option<Decoder>,
+    sniffed: bool,
+    end_of_input: bool,
+
+    /// Input bytes not yet decoded
+    raw: Vec<u8>,
+
+    /// Decoded bytes not yet returned to our caller
+    decoded: Vec<u8>,
+}
+
+impl<'a> DecodingReader<'a> {
+    pub fn new(input: &'a mut dyn io::Read) -> DecodingReader<'a> {
+        return DecodingReader {
+            input,
+            decoder: None,
+            sniffed: false,
+            end_of_input: false,
+            raw: Vec::new(),
+            decoded: Vec::new(),
+        };
+    }
+
+    /// Look at the first few bytes of the input to figure out its encoding
+    fn sniff(&mut self) -> io::Result<()> {
+        self.sniffed = true;
+
+        let mut buf = [0u8; 4];
+        while self.raw.len() < buf.len() {
+            let read_count = self.input.read(&mut buf[..4 - self.raw.len()])?;
+            if read_count == 0 {
+                self.end_of_input = true;
+                break;
+            }
+            self.raw.extend_from_slice(&buf[..read_count]);
+        }
+
+        let (encoding, bom_length) = match sniff_encoding(&self.raw) {
+            Some(found) => found,
+            None => return Ok(()),
+        };
+        self.raw.drain(..bom_length);
+        if encoding != UTF_8 {
+            self.decoder = Some(encoding.new_decoder_without_bom_handling());
+        }
+
+        return Ok(());
+    }
+
+    /// Decode whatever is in `self.raw` into `self.decoded`
+    fn decode(&mut self) {
+        let decoder = self.decoder.as_mut().unwrap();
+        let mut consumed = 0;
+        loop {
+            let needed = decoder
+                .max_utf8_buffer_length(self.raw.len() - consumed)
+                .unwrap_or(16384);
+            let start = self.decoded.len();
+            self.decoded.resize(start + needed, 0);
+
+            let (result, read, written, _) = decoder.decode_to_utf8(
+                &self.raw[consumed..],
+                &mut self.decoded[start..],
+                self.end_of_input,
+            );
+            consumed += read;
+            self.decoded.truncate(start + written);
+
+            if result == CoderResult::InputEmpty {
+                break;
+            }
+        }
+        self.raw.clear();
+    }
+}
+
+impl<'a> io::Read for DecodingReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if !self.sniffed {
+            self.sniff()?;
+        }
+
+        loop {
+            if !self.decoded.is_empty() {
+                let count = buf.len().min(self.decoded.len());
+                buf[..count].copy_from_slice(&self.decoded[..count]);
+                self.decoded.drain(..count);
+                return Ok(count);
+            }
+
+            if self.decoder.is_none() && !self.raw.is_empty() {
+                // Sniffed bytes that need no decoding
+                std::mem::swap(&mut self.raw, &mut self.decoded);
+                continue;
+            }
+
+            if self.end_of_input {
+                if self.decoder.is_some() && !self.raw.is_empty() {
+                    self.decode();
+                    continue;
+                }
+                return Ok(0);
+            }
+
+            if self.decoder.is_none() {
+                // Plain pass-through
+                let read_count = self.input.read(buf)?;
+                if read_count == 0 {
+                    self.end_of_input = true;
+                }
+                return Ok(read_count);
+            }
+
+            let mut input_buf = [0u8; 16384];
+            let read_count = self.input.read(&mut input_buf)?;
+            if read_count == 0 {
+                self.end_of_input = true;
+            }
+            self.raw.extend_from_slice(&input_buf[..read_count]);
+            self.decode();
+        }
+    }
+}
+
+/// Figure out the encoding of a stream starting with `head`.
+///
+/// Returns the encoding and the length of its byte order mark, or `None` if
+/// no conversion is needed.
+#[must_use]
+fn sniff_encoding(head: &[u8]) -> Option<(&'static Encoding, usize)> {
+    if let Some(found) = Encoding::for_bom(head) {
+        return Some(found);
+    }
+
+    // Without a BOM, UTF-16 encoded ASCII has every other byte zero
+    if let [a, b, c, d] = head {
+        if *a != 0 && *b == 0 && *c != 0 && *d == 0 {
+            return Some((UTF_16LE, 0));
+        }
+        if *a == 0 && *b != 0 && *c == 0 && *d != 0 {
+            return Some((UTF_16BE, 0));
+        }
+    }
+
+    return None;
+}
+
+/// If `line` isn't valid UTF-8, convert it into UTF-8 using the first of the
+/// fallback encodings that can decode it.
+///
+/// Lines that no fallback encoding can decode are left alone, and will get
+/// replacement characters for their broken parts later.
+pub fn decode_line(line: &mut Vec<u8>, fallbacks: &[&'static Encoding]) {
+    if std::str::from_utf8(line).is_ok() {
+        return;
+    }
+
+    for encoding in fallbacks {
+        if let Some(decoded) = encoding.decode_without_bom_handling_and_without_replacement(line) {
+            *line = decoded.into_owned().into_bytes();
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding_rs::WINDOWS_1252;
+    use std::io::Read;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    fn decode_all(bytes: &[u8]) -> String {
+        let mut input = bytes;
+        let mut reader = DecodingReader::new(&mut input);
+        let mut result = String::new();
+        reader.read_to_string(&mut result).unwrap();
+        return result;
+    }
+
+    #[test]
+    fn test_passthrough() {
+        assert_eq!(decode_all(b""), "");
+        assert_eq!(decode_all(b"a"), "a");
+        assert_eq!(decode_all(b"-a\n+b\n"), "-a\n+b\n");
+    }
+
+    #[test]
+    fn test_utf8_bom() {
+        assert_eq!(decode_all(b"\xef\xbb\xbf-a\n+b\n"), "-a\n+b\n");
+    }
+
+    #[test]
+    fn test_utf16() {
+        let text = "-hej\n+hallå\n";
+
+        let mut little_endian = vec![0xff, 0xfe];
+        let mut big_endian = vec![0xfe, 0xff];
+        for unit in text.encode_utf16() {
+            little_endian.extend_from_slice(&unit.to_le_bytes());
+            big_endian.extend_from_slice(&unit.to_be_bytes());
+        }
+
+        assert_eq!(decode_all(&little_endian), text);
+        assert_eq!(decode_all(&big_endian), text);
+
+        // Without the BOMs
+        assert_eq!(decode_all(&little_endian[2..]), text);
+        assert_eq!(decode_all(&big_endian[2..]), text);
+    }
+
+    #[test]
+    fn test_decode_line() {
+        let mut line = b"+hall\xe5".to_vec();
+        decode_line(&mut line, &[WINDOWS_1252]);
+        assert_eq!(String::from_utf8(line).unwrap(), "+hallå");
+
+        let mut line = "+hallå".as_bytes().to_vec();
+        decode_line(&mut line, &[WINDOWS_1252]);
+        assert_eq!(String::from_utf8(line).unwrap(), "+hallå");
+
+        // No fallbacks, leave it alone
+        let mut line = b"+hall\xe5".to_vec();
+        decode_line(&mut line, &[]);
+        assert_eq!(line, b"+hall\xe5");
+    }
+}
@@ -0,0 +1,58 @@
+use std::sync::Mutex;
+
+/// What `diff` and `git` say in English
+const ENGLISH_MARKER: &str = "\\ No newline at end of file";
+
+lazy_static! {
+    /// This is the `\ No newline at end of file` string. But since it can come
+    /// in not-English as well as English, we take it from the input and store it
+    /// in this variable. None means we haven't seen one yet.
+    ///
+    /// See also: https://github.com/walles/riff/issues/39
+    static ref MARKER: Mutex<Option<String>> = Mutex::new(None);
+}
+
+/// True for `\ No newline at end of file` lines, in any language.
+///
+/// Translations differ, but they all start with a backslash and a space.
+#[must_use]
+pub fn is_marker(line: &str) -> bool {
+    return line.starts_with("\\ ");
+}
+
+/// Remember how the marker is phrased in this particular diff.
+///
+/// Call this before queueing anything that could make a consumer thread ask
+/// for the marker, or it may get the previous phrasing.
+pub fn remember(line: &str) {
+    let mut marker = MARKER.lock().unwrap();
+    if marker.as_deref() != Some(line) {
+        *marker = Some(line.to_string());
+    }
+}
+
+/// The marker as last seen in the input, or the English one if we haven't
+/// seen any yet
+#[must_use]
+pub fn get() -> String {
+    return MARKER
+        .lock()
+        .unwrap()
+        .clone()
+        .unwrap_or_else(|| ENGLISH_MARKER.to_string());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_marker() {
+        assert!(is_marker("\\ No newline at end of file"));
+        assert!(is_marker("\\ Kein Zeilenumbruch am Dateiende."));
+        assert!(is_marker("\\ Brak znaku nowej linii na końcu pliku"));
+        assert!(!is_marker("\\"));
+        assert!(!is_marker("\\n"));
+        assert!(!is_marker(" \\ No newline at end of file"));
+    }
+}
@@ -0,0 +1,165 @@
+/// How git runs us when we're `GIT_EXTERNAL_DIFF` or `diff.external`:
+///
+/// `riff path old-file old-hex old-mode new-file new-hex new-mode`
+///
+/// Renames come with two more arguments, the new path and some `rename from`
+/// style lines describing the rename.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ExternalDiffArgs<'a> {
+    pub path: &'a str,
+    pub old_file: &'a str,
+    old_mode: &'a str,
+    pub new_file: &'a str,
+    new_mode: &'a str,
+    new_path: &'a str,
+    metadata: Option<&'a str>,
+}
+
+/// Git uses `.` for the hex and mode of files that don't exist
+fn is_hex(text: &str) -> bool {
+    return text == "." || (text.len() >= 40 && text.chars().all(|c| c.is_ascii_hexdigit()));
+}
+
+fn is_mode(text: &str) -> bool {
+    return text == "." || (text.len() == 6 && text.chars().all(|c| ('0'..='7').contains(&c)));
+}
+
+impl<'a> ExternalDiffArgs<'a> {
+    /// `args` are our command line arguments, without the program name
+    #[must_use]
+    pub fn parse(args: &'a [String]) -> Option<ExternalDiffArgs<'a>> {
+        if args.len() != 7 && args.len() != 9 {
+            return None;
+        }
+        if !is_hex(&args[2]) || !is_mode(&args[3]) || !is_hex(&args[5]) || !is_mode(&args[6]) {
+            return None;
+        }
+
+        return Some(ExternalDiffArgs {
+            path: &args[0],
+            old_file: &args[1],
+            old_mode: &args[3],
+            new_file: &args[4],
+            new_mode: &args[6],
+            new_path: args.get(7).unwrap_or(&args[0]),
+            metadata: args.get(8).map(String::as_str),
+        });
+    }
+
+    /// `diff --label` values for the old and new files
+    #[must_use]
+    pub fn labels(&self) -> (String, String) {
+        let old_label = if self.old_mode == "." {
+            "/dev/null".to_string()
+        } else {
+            format!("a/{}", self.path)
+        };
+        let new_label = if self.new_mode == "." {
+            "/dev/null".to_string()
+        } else {
+            format!("b/{}", self.new_path)
+        };
+        return (old_label, new_label);
+    }
+
+    /// The lines git would have put before the `---` line, with a trailing
+    /// newline
+    #[must_use]
+    pub fn header(&self) -> String {
+        let mut header = format!("diff --git a/{} b/{}\n", self.path, self.new_path);
+        if let Some(metadata) = self.metadata {
+            header.push_str(metadata);
+            if !metadata.ends_with('\n') {
+                header.push('\n');
+            }
+        }
+
+        if self.old_mode == "." {
+            header.push_str(&format!("new file mode {}\n", self.new_mode));
+        } else if self.new_mode == "." {
+            header.push_str(&format!("deleted file mode {}\n", self.old_mode));
+        } else if self.old_mode != self.new_mode {
+            header.push_str(&format!("old mode {}\n", self.old_mode));
+            header.push_str(&format!("new mode {}\n", self.new_mode));
+        }
+
+        return header;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    const HEX: &str = "d670460b4b4aece5915caf5c68d12f560a9fe3e4";
+
+    fn args(args: &[&str]) -> Vec<String> {
+        return args.iter().map(|arg| arg.to_string()).collect();
+    }
+
+    #[test]
+    fn test_parse() {
+        let changed = args(&[
+            "src/x.rs",
+            "/tmp/x.rs",
+            HEX,
+            "100644",
+            "src/x.rs",
+            HEX,
+            "100755",
+        ]);
+        let parsed = ExternalDiffArgs::parse(&changed).unwrap();
+        assert_eq!(
+            parsed.labels(),
+            ("a/src/x.rs".to_string(), "b/src/x.rs".to_string())
+        );
+        assert_eq!(
+            parsed.header(),
+            "diff --git a/src/x.rs b/src/x.rs\nold mode 100644\nnew mode 100755\n"
+        );
+
+        let added = args(&["y.txt", "/dev/null", ".", ".", "y.txt", HEX, "100644"]);
+        let parsed = ExternalDiffArgs::parse(&added).unwrap();
+        assert_eq!(
+            parsed.labels(),
+            ("/dev/null".to_string(), "b/y.txt".to_string())
+        );
+        assert_eq!(
+            parsed.header(),
+            "diff --git a/y.txt b/y.txt\nnew file mode 100644\n"
+        );
+
+        let renamed = args(&[
+            "old.txt",
+            "/tmp/old.txt",
+            HEX,
+            "100644",
+            "new.txt",
+            HEX,
+            "100644",
+            "new.txt",
+            "similarity index 90%\nrename from old.txt\nrename to new.txt\n",
+        ]);
+        let parsed = ExternalDiffArgs::parse(&renamed).unwrap();
+        assert_eq!(
+            parsed.labels(),
+            ("a/old.txt".to_string(), "b/new.txt".to_string())
+        );
+        assert_eq!(
+            parsed.header(),
+            "diff --git a/old.txt b/new.txt\nsimilarity index 90%\nrename from old.txt\nrename to new.txt\n"
+        );
+    }
+
+    #[test]
+    fn test_parse_other_command_lines() {
+        assert_eq!(ExternalDiffArgs::parse(&args(&["a.txt", "b.txt"])), None);
+        assert_eq!(
+            ExternalDiffArgs::parse(&args(&["a", "b", "c", "d", "e", "f", "g"])),
+            None
+        );
+    }
+}
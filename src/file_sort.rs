@@ -0,0 +1,204 @@
+use std::cmp::Reverse;
+
+/// How `--sort` orders the files of a diff
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SortOrder {
+    /// Alphabetically by path
+    Path,
+
+    /// Most added lines first
+    Additions,
+
+    /// Most removed lines first
+    Deletions,
+
+    /// Most added plus removed lines first
+    Changes,
+}
+
+impl SortOrder {
+    pub const NAMES: [(&'static str, SortOrder); 4] = [
+        ("path", SortOrder::Path),
+        ("additions", SortOrder::Additions),
+        ("deletions", SortOrder::Deletions),
+        ("changes", SortOrder::Changes),
+    ];
+
+    #[must_use]
+    pub fn parse(name: &str) -> Option<SortOrder> {
+        return Self::NAMES
+            .iter()
+            .find(|(candidate, _)| *candidate == name)
+            .map(|(_, value)| *value);
+    }
+}
+
+/// One file's part of a diff, from its `diff` line up to the next file or
+/// commit
+struct FileSection<'a> {
+    text: String,
+    path: &'a str,
+    additions: usize,
+    deletions: usize,
+}
+
+impl<'a> FileSection<'a> {
+    fn new(lines: &[&'a str]) -> FileSection<'a> {
+        let mut text = String::new();
+        for line in lines {
+            text.push_str(line);
+        }
+
+        // Header lines before the first hunk, the hunk lines after that
+        let first_hunk = lines
+            .iter()
+            .position(|line| line.starts_with("@@"))
+            .unwrap_or(lines.len());
+        let (header, hunks) = lines.split_at(first_hunk);
+
+        let count = |prefix: char| {
+            return hunks.iter().filter(|line| line.starts_with(prefix)).count();
+        };
+
+        return FileSection {
+            text,
+            path: section_path(header),
+            additions: count('+'),
+            deletions: count('-'),
+        };
+    }
+}
+
+/// The new path of a file section, or the old one for removed files
+fn section_path<'a>(header: &[&'a str]) -> &'a str {
+    let name = |prefix: &str, git_prefix: &str| -> Option<&'a str> {
+        let line = header.iter().find(|line| line.starts_with(prefix))?;
+        let name = line[prefix.len()..].trim_end();
+        let name = name.split('\t').next().unwrap_or_default();
+        if name == "/dev/null" {
+            return None;
+        }
+        return Some(name.strip_prefix(git_prefix).unwrap_or(name));
+    };
+
+    return name("+++ ", "b/")
+        .or_else(|| name("--- ", "a/"))
+        .unwrap_or_else(|| header.first().map_or("", |line| line.trim_end()));
+}
+
+fn sort_sections(sections: &mut [FileSection], order: SortOrder) {
+    // Stable sorts, so files with the same counts stay in diff order
+    match order {
+        SortOrder::Path => sections.sort_by(|a, b| a.path.cmp(b.path)),
+        SortOrder::Additions => sections.sort_by_key(|section| Reverse(section.additions)),
+        SortOrder::Deletions => sections.sort_by_key(|section| Reverse(section.deletions)),
+        SortOrder::Changes => {
+            sections.sort_by_key(|section| Reverse(section.additions + section.deletions));
+        }
+    }
+}
+
+/// Turn the lines collected so far into a file section
+fn end_section<'a>(sections: &mut Vec<FileSection<'a>>, section_lines: &mut Vec<&'a str>) {
+    if !section_lines.is_empty() {
+        sections.push(FileSection::new(section_lines));
+        section_lines.clear();
+    }
+}
+
+/// Reorder the files of a diff. In `git log -p` output, files are only
+/// reordered within their own commits.
+///
+/// Files start at `diff` lines, so plain diffs without those are returned
+/// unchanged.
+#[must_use]
+pub fn sort_files(diff: &str, order: SortOrder) -> String {
+    let mut sorted = String::with_capacity(diff.len());
+    let mut sections: Vec<FileSection> = Vec::new();
+    let mut section_lines: Vec<&str> = Vec::new();
+
+    for line in diff.split_inclusive('\n') {
+        if line.starts_with("diff ") {
+            end_section(&mut sections, &mut section_lines);
+            section_lines.push(line);
+            continue;
+        }
+
+        if line.starts_with("commit ") {
+            end_section(&mut sections, &mut section_lines);
+            sort_sections(&mut sections, order);
+            for section in sections.drain(..) {
+                sorted.push_str(&section.text);
+            }
+        }
+
+        if section_lines.is_empty() {
+            // Not part of any file, like commit messages
+            sorted.push_str(line);
+        } else {
+            section_lines.push(line);
+        }
+    }
+
+    end_section(&mut sections, &mut section_lines);
+    sort_sections(&mut sections, order);
+    for section in sections {
+        sorted.push_str(&section.text);
+    }
+
+    return sorted;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    const SMALL: &str = concat!(
+        "diff --git a/b.txt b/b.txt\n",
+        "--- a/b.txt\n",
+        "+++ b/b.txt\n",
+        "@@ -1 +1 @@\n",
+        "-x\n",
+        "+y\n",
+    );
+
+    const BIG: &str = concat!(
+        "diff --git a/a.txt b/a.txt\n",
+        "deleted file mode 100644\n",
+        "--- a/a.txt\n",
+        "+++ /dev/null\n",
+        "@@ -1,3 +0,0 @@\n",
+        "--- x\n",
+        "-y\n",
+        "-z\n",
+    );
+
+    #[test]
+    fn test_sort_files() {
+        let diff = format!("{SMALL}{BIG}");
+        assert_eq!(sort_files(&diff, SortOrder::Path), format!("{BIG}{SMALL}"));
+        assert_eq!(
+            sort_files(&diff, SortOrder::Deletions),
+            format!("{BIG}{SMALL}")
+        );
+        assert_eq!(
+            sort_files(&diff, SortOrder::Changes),
+            format!("{BIG}{SMALL}")
+        );
+
+        // Only the small file adds anything
+        assert_eq!(sort_files(&diff, SortOrder::Additions), diff);
+    }
+
+    #[test]
+    fn test_sort_files_per_commit() {
+        let diff = format!("commit 1\n\n    One\n\n{SMALL}{BIG}commit 2\n\n    Two\n\n{SMALL}");
+        assert_eq!(
+            sort_files(&diff, SortOrder::Path),
+            format!("commit 1\n\n    One\n\n{BIG}{SMALL}commit 2\n\n    Two\n\n{SMALL}")
+        );
+    }
+}
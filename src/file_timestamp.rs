@@ -0,0 +1,79 @@
+/// Weekdays starting traditional `Thu Jan  1 00:00:00 1970` timestamps
+const WEEKDAYS: &[&str] = &["Mon ", "Tue ", "Wed ", "Thu ", "Fri ", "Sat ", "Sun "];
+
+/// True if `text` starts like a timestamp, `2021-01-01 12:00:00`, `Thu Jan
+/// 1 00:00:00 1970`, or like Subversion's `(revision 123)` and `(working
+/// copy)`.
+///
+/// Nothing at all counts too. Git puts a tab after file names containing
+/// spaces, with nothing after it.
+fn looks_like_timestamp(text: &str) -> bool {
+    if text.is_empty() {
+        return true;
+    }
+    if text.starts_with(|c: char| c.is_ascii_digit() || c == '(') {
+        return true;
+    }
+    return WEEKDAYS.iter().any(|weekday| text.starts_with(weekday));
+}
+
+/// Byte index of the tab or double space separating the timestamp from a
+/// `---` or `+++` file name, `None` if there is no timestamp.
+///
+/// `diff --label` names can contain tabs and double spaces of their own, so
+/// those only start a timestamp if what comes after them looks like one.
+#[must_use]
+pub fn start(name: &str) -> Option<usize> {
+    let mut index = 0;
+    while let Some(separator) = name[index..].find(['\t', ' ']) {
+        let separator = index + separator;
+        let rest = &name[separator..];
+        let is_separator = rest.starts_with('\t') || rest.starts_with("  ");
+        if is_separator && looks_like_timestamp(rest.trim_start_matches(['\t', ' '])) {
+            return Some(separator);
+        }
+
+        index = separator + 1;
+    }
+    return None;
+}
+
+/// The file name or `diff --label` of a `---` or `+++` line, without any
+/// timestamp
+#[must_use]
+pub fn strip(name: &str) -> &str {
+    return match start(name) {
+        Some(start) => &name[..start],
+        None => name,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_strip() {
+        assert_eq!(strip("a/x.rs\t2021-01-01 12:00:00 +0100"), "a/x.rs");
+        assert_eq!(strip("/tmp/b.txt  2023-08-06 16:07:39"), "/tmp/b.txt");
+        assert_eq!(strip("x.c\tThu Jan  1 00:00:00 1970"), "x.c");
+        assert_eq!(strip("x.c\t(revision 1234)"), "x.c");
+        assert_eq!(strip("x.c\t(working copy)"), "x.c");
+        assert_eq!(strip("a/x.rs"), "a/x.rs");
+        assert_eq!(strip("a/sp ace.rs\t"), "a/sp ace.rs");
+    }
+
+    #[test]
+    fn test_strip_labels() {
+        // From `diff --label`, no timestamps
+        assert_eq!(strip("old version"), "old version");
+        assert_eq!(strip("old  version"), "old  version");
+        assert_eq!(strip("old\tversion"), "old\tversion");
+
+        // A label with a tab, followed by a timestamp
+        assert_eq!(strip("old\tversion\t2021-01-01 12:00:00"), "old\tversion");
+    }
+}
@@ -0,0 +1,138 @@
+use std::io::{self, BufRead, Write};
+use std::process::Command;
+
+/// Global git settings for using riff, same as in the help text
+const SETTINGS: &[&str] = &[
+    "pager.diff",
+    "pager.show",
+    "pager.log",
+    "interactive.diffFilter",
+];
+
+/// Current value of a global git setting
+fn get(key: &str) -> Option<String> {
+    let output = Command::new("git")
+        .args(["config", "--global", "--get", key].iter())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    return Some(String::from_utf8_lossy(&output.stdout).trim().to_string());
+}
+
+/// `git config` command lines for making riff the pager. Settings that are
+/// already `riff` are left out.
+fn plan_install(current: impl Fn(&str) -> Option<String>) -> Vec<Vec<String>> {
+    return SETTINGS
+        .iter()
+        .filter(|key| current(key).as_deref() != Some("riff"))
+        .map(|key| vec!["--global".to_string(), key.to_string(), "riff".to_string()])
+        .collect();
+}
+
+/// `git config` command lines for undoing [`plan_install`]. Settings that
+/// aren't `riff` are left alone, somebody else configured those.
+fn plan_uninstall(current: impl Fn(&str) -> Option<String>) -> Vec<Vec<String>> {
+    return SETTINGS
+        .iter()
+        .filter(|key| current(key).as_deref() == Some("riff"))
+        .map(|key| {
+            vec![
+                "--global".to_string(),
+                "--unset".to_string(),
+                key.to_string(),
+            ]
+        })
+        .collect();
+}
+
+/// Show the commands, ask for confirmation, then run them. Returns an error
+/// message if something failed.
+fn confirm_and_run(commands: &[Vec<String>]) -> Result<(), String> {
+    if commands.is_empty() {
+        println!("Nothing to change");
+        return Ok(());
+    }
+
+    println!("About to run:");
+    for args in commands {
+        println!("  git config {}", args.join(" "));
+    }
+    print!("Continue? [y/N] ");
+    io::stdout().flush().map_err(|error| error.to_string())?;
+
+    let mut answer = String::new();
+    io::stdin()
+        .lock()
+        .read_line(&mut answer)
+        .map_err(|error| error.to_string())?;
+    if !answer.trim().eq_ignore_ascii_case("y") {
+        return Err("Aborted, nothing changed".to_string());
+    }
+
+    for args in commands {
+        let status = Command::new("git")
+            .arg("config")
+            .args(args)
+            .status()
+            .map_err(|error| format!("Running git failed: {error}"))?;
+        if !status.success() {
+            return Err(format!("git config {} failed: {status}", args.join(" ")));
+        }
+    }
+
+    println!("Done");
+    return Ok(());
+}
+
+/// `riff --install-git-config`
+pub fn install() -> Result<(), String> {
+    return confirm_and_run(&plan_install(get));
+}
+
+/// `riff --uninstall-git-config`
+pub fn uninstall() -> Result<(), String> {
+    return confirm_and_run(&plan_uninstall(get));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    fn current(key: &str) -> Option<String> {
+        return match key {
+            "pager.diff" => Some("riff".to_string()),
+            "pager.show" => Some("less".to_string()),
+            _ => None,
+        };
+    }
+
+    #[test]
+    fn test_plan_install() {
+        let commands: Vec<String> = plan_install(current)
+            .iter()
+            .map(|args| args.join(" "))
+            .collect();
+        assert_eq!(
+            commands,
+            [
+                "--global pager.show riff",
+                "--global pager.log riff",
+                "--global interactive.diffFilter riff",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_plan_uninstall() {
+        let commands: Vec<String> = plan_uninstall(current)
+            .iter()
+            .map(|args| args.join(" "))
+            .collect();
+        assert_eq!(commands, ["--global --unset pager.diff"]);
+    }
+}
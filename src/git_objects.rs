@@ -0,0 +1,174 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::{Mutex, OnceLock};
+
+/// One long running `git cat-file --batch`, answering blob requests on its
+/// stdin
+struct CatFile {
+    // Exits when we exit and its stdin gets closed
+    _child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+#[derive(Default)]
+enum Backend {
+    #[default]
+    NotStarted,
+    Running(CatFile),
+
+    /// Not in a repository, or git isn't working
+    Unavailable,
+}
+
+/// Reads objects from the git repository we're in, through `git cat-file`.
+///
+/// Each line collector has one of these. Its `git cat-file` is started on the
+/// first blob request, and then reused for the rest of the input. Blobs are
+/// needed for binary file converters and renames, and starting a new git for
+/// each of those adds up.
+#[derive(Default)]
+pub struct GitObjects {
+    backend: Mutex<Backend>,
+
+    /// The top directory of the repository we're in, looked up once
+    toplevel: OnceLock<Option<String>>,
+}
+
+fn find_toplevel() -> Option<String> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--show-toplevel"].iter())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    return Some(
+        String::from_utf8_lossy(&output.stdout)
+            .trim_end()
+            .to_string(),
+    );
+}
+
+impl CatFile {
+    fn start() -> Option<CatFile> {
+        let mut child = Command::new("git")
+            .args(["cat-file", "--batch"].iter())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .ok()?;
+        let stdin = child.stdin.take()?;
+        let stdout = BufReader::new(child.stdout.take()?);
+        return Some(CatFile {
+            _child: child,
+            stdin,
+            stdout,
+        });
+    }
+
+    /// `Err` means the process is broken, `Ok(None)` that there's no such
+    /// blob
+    fn read_blob(&mut self, hash: &str) -> Result<Option<Vec<u8>>, ()> {
+        writeln!(self.stdin, "{hash}").map_err(|_| ())?;
+        self.stdin.flush().map_err(|_| ())?;
+
+        // "<hash> blob <size>", or "<hash> missing"
+        let mut header = String::new();
+        if self.stdout.read_line(&mut header).map_err(|_| ())? == 0 {
+            return Err(());
+        }
+        let fields: Vec<&str> = header.split_whitespace().collect();
+        let size = match fields.as_slice() {
+            [_, _, size] => size.parse::<usize>().map_err(|_| ())?,
+            _ => return Ok(None),
+        };
+
+        // The contents are followed by a linefeed
+        let mut contents = vec![0; size + 1];
+        self.stdout.read_exact(&mut contents).map_err(|_| ())?;
+        contents.truncate(size);
+
+        if fields[1] != "blob" {
+            return Ok(None);
+        }
+        return Ok(Some(contents));
+    }
+}
+
+impl GitObjects {
+    /// The top directory of the git repository we're in, `None` if we aren't
+    /// in one
+    #[must_use]
+    pub fn toplevel(&self) -> Option<&str> {
+        return self.toplevel.get_or_init(find_toplevel).as_deref();
+    }
+
+    /// Contents of a blob in the git repository we're in.
+    ///
+    /// Returns `None` if there is no such blob, or if we aren't in a
+    /// repository.
+    #[must_use]
+    pub fn blob(&self, hash: &str) -> Option<Vec<u8>> {
+        // A hash or an abbreviation of one, nothing that cat-file would treat
+        // as a revision expression
+        if hash.is_empty() || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+
+        let mut backend = self.backend.lock().unwrap();
+        if let Backend::NotStarted = *backend {
+            *backend = match self.toplevel().and_then(|_| CatFile::start()) {
+                Some(cat_file) => Backend::Running(cat_file),
+                None => Backend::Unavailable,
+            };
+        }
+
+        let cat_file = match &mut *backend {
+            Backend::Running(cat_file) => cat_file,
+            _ => return None,
+        };
+        match cat_file.read_blob(hash) {
+            Ok(blob) => return blob,
+            Err(()) => {
+                *backend = Backend::Unavailable;
+                return None;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_blob() {
+        let git_objects = GitObjects::default();
+        if git_objects.toplevel().is_none() {
+            // Not running in a clone of the riff repository
+            return;
+        }
+
+        let git = |args: &[&str]| {
+            return Command::new("git").args(args).output().unwrap().stdout;
+        };
+        let hash = String::from_utf8(git(&["rev-parse", "HEAD:Cargo.toml"])).unwrap();
+        let contents = git(&["show", "HEAD:Cargo.toml"]);
+        assert_eq!(git_objects.blob(hash.trim()), Some(contents.clone()));
+
+        // Again, from the same git process
+        assert_eq!(git_objects.blob(&hash.trim()[..10]), Some(contents));
+
+        assert_eq!(
+            git_objects.blob("0123456789012345678901234567890123456789"),
+            None
+        );
+        assert_eq!(git_objects.blob("HEAD"), None);
+    }
+}
@@ -0,0 +1,141 @@
+use crate::token_collector::{Style, StyledToken};
+use regex::Regex;
+
+lazy_static! {
+    /// `00000010: 4865 6c6c 6f0a  Hello.` from `xxd`, `00000010  48 65 6c 6c
+    /// |Hel|` from `hexdump -C`, plus the offset-only last line and the `*`
+    /// repetition marker of `hexdump`
+    static ref HEXDUMP_LINE: Regex =
+        Regex::new(r"^(\*|[0-9a-fA-F]{6,}(:? +[0-9a-fA-F]{2}([0-9a-fA-F]{2})* .*)?)$").unwrap();
+}
+
+/// True if every line of `text` looks like `xxd` or `hexdump -C` output
+#[must_use]
+pub fn is_hexdump(text: &str) -> bool {
+    if text.is_empty() {
+        return false;
+    }
+    return text.lines().all(|line| HEXDUMP_LINE.is_match(line));
+}
+
+/// Tokenize hex dumps so that every byte is a token of its own, both in the
+/// hex column and in the text column. The offset is a single token.
+#[must_use]
+pub fn tokenize(input: &str) -> Vec<&str> {
+    let mut result: Vec<&str> = Vec::with_capacity(input.len());
+    for line in input.split_inclusive('\n') {
+        if let Some(without_newline) = line.strip_suffix('\n') {
+            tokenize_line(without_newline, &mut result);
+            result.push(&line[without_newline.len()..]);
+        } else {
+            tokenize_line(line, &mut result);
+        }
+    }
+
+    return result;
+}
+
+fn tokenize_line<'a>(line: &'a str, result: &mut Vec<&'a str>) {
+    let offset_end = line.find(' ').unwrap_or(line.len());
+    result.push(&line[..offset_end]);
+    let rest = &line[offset_end..];
+
+    // hexdump -C puts the text column between pipes. xxd puts it after two
+    // spaces, but two spaces also lead up to the hex column of hexdump -C.
+    let hex_start = rest.len() - rest.trim_start().len();
+    let text_start = if let Some(pipe) = rest.find('|') {
+        pipe
+    } else if let Some(spaces) = rest[hex_start..].find("  ") {
+        hex_start + spaces
+    } else {
+        rest.len()
+    };
+
+    // Hex digits are ASCII, so byte indexing is fine here
+    let hex = &rest[..text_start];
+    let mut start = 0;
+    while start < hex.len() {
+        let is_space = hex.as_bytes()[start] == b' ';
+        let mut end = start + 1;
+        while end < hex.len() && (hex.as_bytes()[end] == b' ') == is_space {
+            if !is_space && end - start == 2 {
+                // One byte per token
+                break;
+            }
+            end += 1;
+        }
+        result.push(&hex[start..end]);
+        start = end;
+    }
+
+    let text = &rest[text_start..];
+    for (index, c) in text.char_indices() {
+        result.push(&text[index..index + c.len_utf8()]);
+    }
+}
+
+/// Offsets shift when bytes are inserted or removed, but that isn't the
+/// interesting part
+pub fn unhighlight_offsets(tokens: &mut [StyledToken]) {
+    let mut at_line_start = true;
+    for token in tokens.iter_mut() {
+        if at_line_start && token.token() != "\n" {
+            token.set_style(Style::Plain);
+        }
+        at_line_start = token.token() == "\n";
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_is_hexdump() {
+        assert!(is_hexdump(
+            "00000000: 4865 6c6c 6f2c 2077 6f72 6c64 0a         Hello, world.\n"
+        ));
+        assert!(is_hexdump(concat!(
+            "00000000  48 65 6c 6c 6f 0a                                 |Hello.|\n",
+            "*\n",
+            "00000006\n",
+        )));
+        assert!(!is_hexdump("fn main() {}\n"));
+        assert!(!is_hexdump("deadbeef\nhello\n"));
+        assert!(!is_hexdump(""));
+    }
+
+    #[test]
+    fn test_tokenize_xxd() {
+        assert_eq!(
+            tokenize("00000000: 4865 6c0a  Hel.\n"),
+            [
+                "00000000:",
+                " ",
+                "48",
+                "65",
+                " ",
+                "6c",
+                "0a",
+                " ",
+                " ",
+                "H",
+                "e",
+                "l",
+                ".",
+                "\n"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_hexdump_c() {
+        assert_eq!(
+            tokenize("00000000  48 65  6c  |H l|"),
+            ["00000000", "  ", "48", " ", "65", "  ", "6c", "  ", "|", "H", " ", "l", "|"]
+        );
+    }
+}
@@ -0,0 +1,340 @@
+use crate::ansi::{parts, sgr_parameters};
+
+/// The 16 basic terminal colors, as used by `xterm`
+const BASIC_COLORS: [&str; 16] = [
+    "#000000", "#cd0000", "#00cd00", "#cdcd00", "#0000ee", "#cd00cd", "#00cdcd", "#e5e5e5",
+    "#7f7f7f", "#ff0000", "#00ff00", "#ffff00", "#5c5cff", "#ff00ff", "#00ffff", "#ffffff",
+];
+
+/// Text color of inverse video text without a background color
+const INVERSE_FOREGROUND: &str = "#ffffff";
+
+/// Background color of inverse video text without a text color
+const INVERSE_BACKGROUND: &str = "#000000";
+
+/// Styling of the current ANSI SGR state
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct State {
+    foreground: Option<String>,
+    background: Option<String>,
+    bold: bool,
+    faint: bool,
+    inverse: bool,
+}
+
+/// CSS color for a 256-color palette index
+fn color_256(index: u8) -> String {
+    if index < 16 {
+        return BASIC_COLORS[index as usize].to_string();
+    }
+    if index >= 232 {
+        let gray = 8 + 10 * (index - 232) as u32;
+        return format!("#{gray:02x}{gray:02x}{gray:02x}");
+    }
+
+    let cube = index - 16;
+    let level = |value: u8| -> u32 {
+        if value == 0 {
+            return 0;
+        }
+        return 55 + 40 * value as u32;
+    };
+    return format!(
+        "#{:02x}{:02x}{:02x}",
+        level(cube / 36),
+        level((cube / 6) % 6),
+        level(cube % 6)
+    );
+}
+
+/// Parse the rest of a `38;5;N` or `38;2;R;G;B` extended color
+fn extended_color<'a>(params: &mut impl Iterator<Item = &'a str>) -> Option<String> {
+    return match params.next()? {
+        "5" => Some(color_256(params.next()?.parse().ok()?)),
+        "2" => {
+            let red: u8 = params.next()?.parse().ok()?;
+            let green: u8 = params.next()?.parse().ok()?;
+            let blue: u8 = params.next()?.parse().ok()?;
+            Some(format!("#{red:02x}{green:02x}{blue:02x}"))
+        }
+        _ => None,
+    };
+}
+
+impl State {
+    /// Apply the parameters of an SGR (`ESC [ ... m`) sequence
+    fn apply(&mut self, params: &str) {
+        let mut params = params.split(';');
+        while let Some(param) = params.next() {
+            let code: u8 = if param.is_empty() {
+                0
+            } else {
+                match param.parse() {
+                    Ok(code) => code,
+                    Err(_) => continue,
+                }
+            };
+            match code {
+                0 => *self = State::default(),
+                1 => self.bold = true,
+                2 => self.faint = true,
+                22 => {
+                    self.bold = false;
+                    self.faint = false;
+                }
+                7 => self.inverse = true,
+                27 => self.inverse = false,
+                30..=37 => self.foreground = Some(BASIC_COLORS[(code - 30) as usize].to_string()),
+                90..=97 => {
+                    self.foreground = Some(BASIC_COLORS[(code - 90 + 8) as usize].to_string());
+                }
+                38 => self.foreground = extended_color(&mut params),
+                39 => self.foreground = None,
+                40..=47 => self.background = Some(BASIC_COLORS[(code - 40) as usize].to_string()),
+                100..=107 => {
+                    self.background = Some(BASIC_COLORS[(code - 100 + 8) as usize].to_string());
+                }
+                48 => self.background = extended_color(&mut params),
+                49 => self.background = None,
+                _ => {}
+            }
+        }
+    }
+
+    /// Inline CSS for this state, email clients don't do style sheets
+    fn css(&self) -> String {
+        let (foreground, background) = if self.inverse {
+            (
+                Some(
+                    self.background
+                        .clone()
+                        .unwrap_or_else(|| INVERSE_FOREGROUND.to_string()),
+                ),
+                Some(
+                    self.foreground
+                        .clone()
+                        .unwrap_or_else(|| INVERSE_BACKGROUND.to_string()),
+                ),
+            )
+        } else {
+            (self.foreground.clone(), self.background.clone())
+        };
+
+        let mut css: Vec<String> = Vec::new();
+        if let Some(foreground) = foreground {
+            css.push(format!("color:{foreground}"));
+        }
+        if let Some(background) = background {
+            css.push(format!("background-color:{background}"));
+        }
+        if self.bold {
+            css.push("font-weight:bold".to_string());
+        }
+        if self.faint {
+            css.push("opacity:0.6".to_string());
+        }
+        return css.join(";");
+    }
+}
+
+fn push_escaped(html: &mut String, c: char) {
+    match c {
+        '&' => html.push_str("&amp;"),
+        '<' => html.push_str("&lt;"),
+        '>' => html.push_str("&gt;"),
+        '"' => html.push_str("&quot;"),
+        _ => html.push(c),
+    }
+}
+
+/// Convert riff's ANSI colored output into HTML with inline styles. Escape
+/// sequences other than colors are dropped.
+#[must_use]
+pub fn ansi_to_html(text: &str) -> String {
+    let mut html = String::with_capacity(text.len() * 2);
+    let mut state = State::default();
+    let mut span_open = false;
+
+    for part in parts(text) {
+        let c = match part {
+            Err(escape) => {
+                if let Some(params) = sgr_parameters(escape) {
+                    state.apply(params);
+                    if span_open {
+                        html.push_str("</span>");
+                        span_open = false;
+                    }
+                }
+                continue;
+            }
+            Ok(c) => c,
+        };
+
+        if c == '\n' {
+            if span_open {
+                html.push_str("</span>");
+                span_open = false;
+            }
+            html.push('\n');
+            continue;
+        }
+
+        if !span_open {
+            let css = state.css();
+            if !css.is_empty() {
+                html.push_str(&format!("<span style=\"{css}\">"));
+                span_open = true;
+            }
+        }
+        push_escaped(&mut html, c);
+    }
+
+    if span_open {
+        html.push_str("</span>");
+    }
+    return html;
+}
+
+/// Headers describing the contents, replaced by our own
+fn is_content_header(header: &str) -> bool {
+    let name = header.split(':').next().unwrap_or_default().to_lowercase();
+    return name == "mime-version" || name == "content-type" || name == "content-transfer-encoding";
+}
+
+/// Turn a patch, like from `git format-patch`, into an email with both the
+/// original patch and a highlighted HTML version of it. The email headers of
+/// the patch are kept.
+///
+/// `highlight` turns the patch body into riff's ANSI colored output.
+#[must_use]
+pub fn format_email(patch: &str, highlight: impl Fn(&str) -> String) -> String {
+    // git format-patch output starts with an mbox "From <hash> <date>" line,
+    // then email headers, then an empty line
+    let has_headers = patch.starts_with("From ") || patch.starts_with("From:");
+    let (headers, body) = if has_headers {
+        match patch.split_once("\n\n") {
+            Some((headers, body)) => (headers, body),
+            None => (patch, ""),
+        }
+    } else {
+        ("", patch)
+    };
+
+    let mut boundary = "riff-alternative".to_string();
+    while patch.contains(&boundary) {
+        boundary.push('-');
+    }
+
+    let mut email = String::with_capacity(patch.len() * 4);
+    let mut skipping_header = false;
+    for header in headers.lines() {
+        // Indented lines continue the previous header
+        let is_continuation = header.starts_with(' ') || header.starts_with('\t');
+        if !is_continuation {
+            skipping_header = is_content_header(header);
+        }
+        if !skipping_header {
+            email.push_str(header);
+            email.push('\n');
+        }
+    }
+    email.push_str("MIME-Version: 1.0\n");
+    email.push_str(&format!(
+        "Content-Type: multipart/alternative; boundary=\"{boundary}\"\n\n"
+    ));
+
+    email.push_str(&format!("--{boundary}\n"));
+    email.push_str("Content-Type: text/plain; charset=UTF-8\n");
+    email.push_str("Content-Transfer-Encoding: 8bit\n\n");
+    email.push_str(body);
+    if !body.ends_with('\n') {
+        email.push('\n');
+    }
+
+    email.push_str(&format!("--{boundary}\n"));
+    email.push_str("Content-Type: text/html; charset=UTF-8\n");
+    email.push_str("Content-Transfer-Encoding: 8bit\n\n");
+    email.push_str("<html><body>\n");
+    email.push_str(
+        "<pre style=\"font-family:monospace;font-size:13px;color:#000000;background-color:#ffffff\">",
+    );
+    email.push_str(&ansi_to_html(&highlight(body)));
+    email.push_str("</pre>\n</body></html>\n");
+    email.push_str(&format!("--{boundary}--\n"));
+
+    return email;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::*;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_ansi_to_html() {
+        assert_eq!(ansi_to_html("a < b"), "a &lt; b");
+        assert_eq!(
+            ansi_to_html(&format!("{OLD}-x{INVERSE_VIDEO}y{NORMAL}\nz")),
+            concat!(
+                "<span style=\"color:#cd0000\">-x</span>",
+                "<span style=\"color:#ffffff;background-color:#cd0000\">y</span>\n",
+                "z"
+            )
+        );
+        assert_eq!(
+            ansi_to_html(&format!("{BOLD}--- a{NORMAL}")),
+            "<span style=\"font-weight:bold\">--- a</span>"
+        );
+        assert_eq!(
+            ansi_to_html("\x1b[38;5;196mx\x1b]8;;https://x\x1b\\y\x1b]8;;\x1b\\"),
+            "<span style=\"color:#ff0000\">xy</span>"
+        );
+    }
+
+    #[test]
+    fn test_format_email() {
+        let patch = concat!(
+            "From 62da46c7b300321119d399bdc69bfb2d56d5da57 Mon Sep 17 00:00:00 2001\n",
+            "From: Adam <adam@example.com>\n",
+            "Subject: [PATCH] Fix\n",
+            "Content-Type: text/plain; charset=UTF-8\n",
+            "\n",
+            "-a\n",
+            "+b\n",
+        );
+        let email = format_email(patch, |body| {
+            return format!("{OLD}{body}{NORMAL}");
+        });
+        assert_eq!(
+            email,
+            concat!(
+                "From 62da46c7b300321119d399bdc69bfb2d56d5da57 Mon Sep 17 00:00:00 2001\n",
+                "From: Adam <adam@example.com>\n",
+                "Subject: [PATCH] Fix\n",
+                "MIME-Version: 1.0\n",
+                "Content-Type: multipart/alternative; boundary=\"riff-alternative\"\n",
+                "\n",
+                "--riff-alternative\n",
+                "Content-Type: text/plain; charset=UTF-8\n",
+                "Content-Transfer-Encoding: 8bit\n",
+                "\n",
+                "-a\n",
+                "+b\n",
+                "--riff-alternative\n",
+                "Content-Type: text/html; charset=UTF-8\n",
+                "Content-Transfer-Encoding: 8bit\n",
+                "\n",
+                "<html><body>\n",
+                "<pre style=\"font-family:monospace;font-size:13px;color:#000000;background-color:#ffffff\">",
+                "<span style=\"color:#cd0000\">-a</span>\n",
+                "<span style=\"color:#cd0000\">+b</span>\n",
+                "</pre>\n",
+                "</body></html>\n",
+                "--riff-alternative--\n",
+            )
+        );
+    }
+}
@@ -0,0 +1,226 @@
+use std::borrow::Cow;
+
+/// Stands in for the CR of CR LF line endings in hunks that mix line endings,
+/// so that a line only changing its line ending still shows as changed
+pub const CR_MARKER: &str = "␍";
+
+/// One removed or added line
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HunkLine {
+    /// The line without its leading `-` or `+`
+    pub text: String,
+
+    /// Line number in the old or new file, if the hunk header told us
+    pub line_number: Option<usize>,
+
+    /// With `--age-heatmap`, the background color of this line's gutter
+    pub gutter_background: Option<String>,
+
+    /// True if this line ended with CR LF in the input
+    pub crlf: bool,
+}
+
+/// A block of consecutive removed and added lines, waiting to be refined
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Hunk {
+    /// Name of the changed file, from the `+++` line, or from the `---` line
+    /// for removed files
+    pub file: Option<String>,
+
+    /// The `@@ -1,2 +1,2 @@` line these changes came after
+    pub header: Option<String>,
+
+    /// Put before every output line, like the indentation of the diffs in
+    /// `git range-diff` output
+    pub indent: &'static str,
+
+    pub old_lines: Vec<HunkLine>,
+    pub new_lines: Vec<HunkLine>,
+
+    /// Set by `\ No newline at end of file` markers
+    pub old_missing_eof_newline: bool,
+    pub new_missing_eof_newline: bool,
+}
+
+/// The text of each line. With `mark_crlf`, CR LF line endings get a
+/// [`CR_MARKER`].
+fn line_texts(lines: &[HunkLine], mark_crlf: bool) -> impl Iterator<Item = Cow<'_, str>> {
+    return lines.iter().map(move |line| {
+        if mark_crlf && line.crlf {
+            return Cow::Owned(format!("{}{CR_MARKER}", line.text));
+        }
+        return Cow::Borrowed(line.text.as_str());
+    });
+}
+
+/// Join lines into text with a linefeed after each line, except possibly the
+/// last one. With `mark_crlf`, CR LF line endings get a [`CR_MARKER`].
+fn to_text(lines: &[HunkLine], missing_eof_newline: bool, mark_crlf: bool) -> String {
+    let mut text = String::new();
+    for line in line_texts(lines, mark_crlf) {
+        text.push_str(&line);
+        text.push('\n');
+    }
+    if missing_eof_newline {
+        text.pop();
+    }
+    return text;
+}
+
+impl Hunk {
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        return self.old_lines.is_empty() && self.new_lines.is_empty();
+    }
+
+    /// True if some lines end with CR LF and some with just LF.
+    ///
+    /// Consistent CR LF line endings are just how the file is, and not worth
+    /// showing.
+    #[must_use]
+    fn has_mixed_line_endings(&self) -> bool {
+        let mut lines = self.old_lines.iter().chain(self.new_lines.iter());
+        let first_crlf = match lines.next() {
+            Some(line) => line.crlf,
+            None => return false,
+        };
+        return lines.any(|line| line.crlf != first_crlf);
+    }
+
+    /// The text of each removed line, for showing them without refining
+    pub fn old_line_texts(&self) -> impl Iterator<Item = Cow<'_, str>> {
+        return line_texts(&self.old_lines, self.has_mixed_line_endings());
+    }
+
+    /// The text of each added line, for showing them without refining
+    pub fn new_line_texts(&self) -> impl Iterator<Item = Cow<'_, str>> {
+        return line_texts(&self.new_lines, self.has_mixed_line_endings());
+    }
+
+    /// The removed lines as one text, for refining
+    #[must_use]
+    pub fn old_text(&self) -> String {
+        return to_text(
+            &self.old_lines,
+            self.old_missing_eof_newline,
+            self.has_mixed_line_endings(),
+        );
+    }
+
+    /// The added lines as one text, for refining
+    #[must_use]
+    pub fn new_text(&self) -> String {
+        return to_text(
+            &self.new_lines,
+            self.new_missing_eof_newline,
+            self.has_mixed_line_endings(),
+        );
+    }
+
+    /// Lengths of the old and new texts, without building them
+    #[must_use]
+    pub fn text_lengths(&self) -> (usize, usize) {
+        let length = |lines: &[HunkLine]| -> usize {
+            return lines.iter().map(|line| line.text.len() + 1).sum();
+        };
+        return (length(&self.old_lines), length(&self.new_lines));
+    }
+
+    /// A hunk of `old_text` removed and `new_text` added, like
+    /// [`old_text`](Self::old_text) and [`new_text`](Self::new_text) would
+    /// return them
+    #[cfg(test)]
+    #[must_use]
+    pub fn from_texts(old_text: &str, new_text: &str) -> Hunk {
+        let lines = |text: &str| -> Vec<HunkLine> {
+            return text
+                .lines()
+                .map(|line| HunkLine {
+                    text: line.to_string(),
+                    ..HunkLine::default()
+                })
+                .collect();
+        };
+        return Hunk {
+            old_lines: lines(old_text),
+            new_lines: lines(new_text),
+            old_missing_eof_newline: !old_text.is_empty() && !old_text.ends_with('\n'),
+            new_missing_eof_newline: !new_text.is_empty() && !new_text.ends_with('\n'),
+            ..Hunk::default()
+        };
+    }
+
+    /// Move the lines out into a hunk of their own, keeping the file and the
+    /// header for the lines that come next
+    pub fn take_lines(&mut self) -> Hunk {
+        let next = Hunk {
+            file: self.file.clone(),
+            header: self.header.clone(),
+            ..Hunk::default()
+        };
+        return std::mem::replace(self, next);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    fn line(text: &str) -> HunkLine {
+        return HunkLine {
+            text: text.to_string(),
+            ..HunkLine::default()
+        };
+    }
+
+    #[test]
+    fn test_text() {
+        let mut hunk = Hunk {
+            file: Some("b/x.txt".to_string()),
+            old_lines: vec![line("a"), line("")],
+            new_lines: vec![line("b")],
+            new_missing_eof_newline: true,
+            ..Hunk::default()
+        };
+        assert_eq!(hunk.old_text(), "a\n\n");
+        assert_eq!(hunk.new_text(), "b");
+
+        let taken = hunk.take_lines();
+        assert_eq!(taken.new_text(), "b");
+        assert!(hunk.is_empty());
+        assert!(!hunk.new_missing_eof_newline);
+        assert_eq!(hunk.file, Some("b/x.txt".to_string()));
+    }
+
+    #[test]
+    fn test_line_endings() {
+        let crlf = |text: &str| {
+            return HunkLine {
+                crlf: true,
+                ..line(text)
+            };
+        };
+
+        // Consistent CR LF line endings aren't shown
+        let hunk = Hunk {
+            old_lines: vec![crlf("a")],
+            new_lines: vec![crlf("b")],
+            ..Hunk::default()
+        };
+        assert_eq!(hunk.old_text(), "a\n");
+        assert_eq!(hunk.new_text(), "b\n");
+
+        // LF to CR LF is
+        let hunk = Hunk {
+            old_lines: vec![line("a")],
+            new_lines: vec![crlf("a"), line("b")],
+            ..Hunk::default()
+        };
+        assert_eq!(hunk.old_text(), "a\n");
+        assert_eq!(hunk.new_text(), "a␍\nb\n");
+        assert_eq!(hunk.new_line_texts().collect::<Vec<_>>(), ["a␍", "b"]);
+    }
+}
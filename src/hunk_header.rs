@@ -0,0 +1,260 @@
+use crate::palette::Palette;
+
+/// Placeholders available in `hunk-header` templates
+pub const PLACEHOLDERS: [&str; 5] = ["header", "function", "old_lines", "new_lines", "ranges"];
+
+/// A parsed `@@ -1,2 +3,4 @@ function` line
+#[derive(Debug, PartialEq, Eq)]
+pub struct HunkHeader<'a> {
+    /// The `@@ -1,2 +3,4 @@` part, as it came
+    pub ranges: &'a str,
+
+    pub old_start: usize,
+    pub old_count: usize,
+    pub new_start: usize,
+    pub new_count: usize,
+
+    /// Whatever comes after the second `@@`, usually a function name. May be
+    /// empty.
+    pub function: &'a str,
+}
+
+/// Part of a `hunk-header` template
+#[derive(Debug, PartialEq, Eq)]
+enum TemplatePart<'a> {
+    Text(&'a str),
+    Placeholder(&'a str),
+}
+
+/// Split `{function} at {old_lines}` into text and placeholders
+fn parse_template(template: &str) -> Result<Vec<TemplatePart<'_>>, String> {
+    let mut parts = Vec::new();
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        if open > 0 {
+            parts.push(TemplatePart::Text(&rest[..open]));
+        }
+        let close = rest[open..]
+            .find('}')
+            .ok_or(format!("Unclosed {{ in \"{template}\""))?;
+        let name = &rest[open + 1..open + close];
+        if !PLACEHOLDERS.contains(&name) {
+            return Err(format!(
+                "Unknown placeholder {{{name}}}, must be one of {}",
+                PLACEHOLDERS
+                    .iter()
+                    .map(|placeholder| format!("{{{placeholder}}}"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+        parts.push(TemplatePart::Placeholder(name));
+        rest = &rest[open + close + 1..];
+    }
+    if !rest.is_empty() {
+        parts.push(TemplatePart::Text(rest));
+    }
+
+    return Ok(parts);
+}
+
+/// Verify that a `hunk-header` template only uses known placeholders
+pub fn check_template(template: &str) -> Result<(), String> {
+    return parse_template(template).map(|_| ());
+}
+
+/// `3–5` for lines 3 to 5, `3` for only line 3, `none` for no lines
+fn format_lines(start: usize, count: usize) -> String {
+    return match count {
+        0 => "none".to_string(),
+        1 => start.to_string(),
+        _ => format!("{start}–{}", start + count - 1),
+    };
+}
+
+/// Add `text` in `color`, then go back to the hunk header color
+fn push_colored(rendered: &mut String, text: &str, color: &str, palette: &Palette) {
+    rendered.push_str(color);
+    rendered.push_str(text);
+    rendered.push_str(&palette.hunk_header);
+}
+
+/// Parse `12` or `12,3` into `(12, 1)` and `(12, 3)` respectively
+fn parse_range(range: &str) -> Option<(usize, usize)> {
+    if let Some((start, count)) = range.split_once(',') {
+        return Some((start.parse().ok()?, count.parse().ok()?));
+    }
+
+    // No count means one line
+    return Some((range.parse().ok()?, 1));
+}
+
+impl<'a> HunkHeader<'a> {
+    #[must_use]
+    pub fn parse(line: &'a str) -> Option<HunkHeader<'a>> {
+        let ranges_and_function = line.strip_prefix("@@ -")?;
+        let (ranges, function) = match ranges_and_function.split_once(" @@") {
+            Some((ranges, function)) => (ranges, function.strip_prefix(' ').unwrap_or(function)),
+            None => return None,
+        };
+
+        let (old_range, new_range) = ranges.split_once(" +")?;
+        let (old_start, old_count) = parse_range(old_range)?;
+        let (new_start, new_count) = parse_range(new_range)?;
+
+        return Some(HunkHeader {
+            ranges: &line[.."@@ -".len() + ranges.len() + " @@".len()],
+            old_start,
+            old_count,
+            new_start,
+            new_count,
+            function,
+        });
+    }
+
+    /// Render this header according to a `hunk-header` template, with the
+    /// function name in bold, line ranges in the old and new colors and
+    /// everything else faint. The template must have passed
+    /// `check_template()`.
+    #[must_use]
+    pub fn render(&self, template: &str, palette: &Palette) -> String {
+        let mut rendered = palette.hunk_header.clone();
+        rendered.push_str(&palette.faint);
+        for part in parse_template(template).unwrap() {
+            match part {
+                TemplatePart::Text(text) => rendered.push_str(text),
+                TemplatePart::Placeholder("header") => rendered.push_str(self.ranges),
+                TemplatePart::Placeholder("function") => {
+                    rendered.push_str(&palette.bold);
+                    rendered.push_str(self.function);
+                    rendered.push_str(&palette.normal);
+                    rendered.push_str(&palette.hunk_header);
+                    rendered.push_str(&palette.faint);
+                }
+                TemplatePart::Placeholder("old_lines") => {
+                    let lines = format_lines(self.old_start, self.old_count);
+                    push_colored(&mut rendered, &lines, &palette.old, palette);
+                }
+                TemplatePart::Placeholder("new_lines") => {
+                    let lines = format_lines(self.new_start, self.new_count);
+                    push_colored(&mut rendered, &lines, &palette.new, palette);
+                }
+                TemplatePart::Placeholder("ranges") => {
+                    let old = format!("old: {}", format_lines(self.old_start, self.old_count));
+                    let new = format!("new: {}", format_lines(self.new_start, self.new_count));
+                    push_colored(&mut rendered, &old, &palette.old, palette);
+                    rendered.push_str(" | ");
+                    push_colored(&mut rendered, &new, &palette.new, palette);
+                }
+                TemplatePart::Placeholder(name) => panic!("Unchecked placeholder {{{}}}", name),
+            }
+        }
+        rendered.push_str(&palette.normal);
+
+        return rendered;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ansi::remove_ansi_escape_codes;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            HunkHeader::parse("@@ -1,2 +3,4 @@ fn main() {"),
+            Some(HunkHeader {
+                ranges: "@@ -1,2 +3,4 @@",
+                old_start: 1,
+                old_count: 2,
+                new_start: 3,
+                new_count: 4,
+                function: "fn main() {",
+            })
+        );
+
+        assert_eq!(
+            HunkHeader::parse("@@ -5 +0,0 @@"),
+            Some(HunkHeader {
+                ranges: "@@ -5 +0,0 @@",
+                old_start: 5,
+                old_count: 1,
+                new_start: 0,
+                new_count: 0,
+                function: "",
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert_eq!(HunkHeader::parse("@@ nothing to see here @@"), None);
+        assert_eq!(HunkHeader::parse("@@ -1,2 +3,4"), None);
+        assert_eq!(HunkHeader::parse("@@@ -1,2 -1,2 +1,3 @@@"), None);
+    }
+
+    #[test]
+    fn test_render() {
+        let palette = Palette::default();
+        let render = |line: &str, template: &str| {
+            let rendered = HunkHeader::parse(line).unwrap().render(template, &palette);
+            let mut bytes = rendered.into_bytes();
+            remove_ansi_escape_codes(&mut bytes);
+            return String::from_utf8(bytes).unwrap();
+        };
+
+        assert_eq!(
+            render("@@ -10,3 +12,5 @@ fn main() {", "{function}"),
+            "fn main() {"
+        );
+        assert_eq!(
+            render(
+                "@@ -10,3 +12,5 @@ fn main() {",
+                "lines {old_lines} → {new_lines} {function}"
+            ),
+            "lines 10–12 → 12–16 fn main() {"
+        );
+        assert_eq!(
+            render("@@ -5 +4,0 @@", "{header} lines {old_lines} → {new_lines}"),
+            "@@ -5 +4,0 @@ lines 5 → none"
+        );
+        assert_eq!(
+            render("@@ -120,16 +120,20 @@ fn main() {", "{ranges}  {function}"),
+            "old: 120–135 | new: 120–139  fn main() {"
+        );
+
+        // Old ranges in the old color, new ranges in the new color
+        assert_eq!(
+            HunkHeader::parse("@@ -1 +1,0 @@")
+                .unwrap()
+                .render("{ranges}", &palette),
+            format!(
+                "{}{}{}old: 1{} | {}new: none{}{}",
+                palette.hunk_header,
+                palette.faint,
+                palette.old,
+                palette.hunk_header,
+                palette.new,
+                palette.hunk_header,
+                palette.normal
+            )
+        );
+    }
+
+    #[test]
+    fn test_check_template() {
+        assert_eq!(check_template("{header} {function}"), Ok(()));
+        assert_eq!(
+            check_template("{funktion}"),
+            Err("Unknown placeholder {funktion}, must be one of {header}, {function}, {old_lines}, {new_lines}, {ranges}".to_string())
+        );
+        assert_eq!(
+            check_template("{function"),
+            Err("Unclosed { in \"{function\"".to_string())
+        );
+    }
+}
@@ -0,0 +1,179 @@
+use crate::token_collector::{Style, StyledToken};
+use crate::tokenizer;
+use std::collections::HashMap;
+
+/// Tokenize JSON so that keys like `"name":` and numbers like `-1.5e3` are
+/// single tokens.
+///
+/// This way, renaming a key highlights the whole key, and changing `1.5` into
+/// `1.25` highlights the whole number. String values are refined as usual.
+pub fn tokenize(input: &str) -> Vec<&str> {
+    let mut result: Vec<&str> = Vec::with_capacity(input.len());
+    let mut rest = input;
+    while !rest.is_empty() {
+        if rest.starts_with('"') {
+            let length = string_length(rest);
+            let is_key = rest[length..]
+                .trim_start_matches([' ', '\t'])
+                .starts_with(':');
+            if is_key {
+                result.push(&rest[..length]);
+            } else {
+                // Refine string values word by word
+                result.append(&mut tokenizer::tokenize(&rest[..length]));
+            }
+            rest = &rest[length..];
+            continue;
+        }
+
+        if let Some(length) = number_length(rest) {
+            result.push(&rest[..length]);
+            rest = &rest[length..];
+            continue;
+        }
+
+        // Everything up to the next string or number
+        let length = match rest.find(|c: char| c == '"' || c == '-' || c.is_ascii_digit()) {
+            // A minus without a number after it
+            Some(0) => 1,
+            Some(length) => length,
+            None => rest.len(),
+        };
+        result.append(&mut tokenizer::tokenize(&rest[..length]));
+        rest = &rest[length..];
+    }
+
+    return result;
+}
+
+/// Length of the string at the start of `text`, including its quotes. Strings
+/// missing their closing quote end at the end of the line.
+fn string_length(text: &str) -> usize {
+    let mut escaped = false;
+    for (index, c) in text.char_indices().skip(1) {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match c {
+            '\\' => escaped = true,
+            '"' => return index + 1,
+            '\n' => return index,
+            _ => {}
+        }
+    }
+    return text.len();
+}
+
+/// Length of the number at the start of `text`, if there is one
+fn number_length(text: &str) -> Option<usize> {
+    let digits = text.strip_prefix('-').unwrap_or(text);
+    if !digits.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    let length = digits
+        .find(|c: char| !(c.is_ascii_digit() || ['.', 'e', 'E', '+', '-'].contains(&c)))
+        .unwrap_or(digits.len());
+    return Some(text.len() - digits.len() + length);
+}
+
+/// A line compared without its indentation or trailing comma, so that moving
+/// the last entry of an object up doesn't make it look different
+fn comparison_key(row: &[StyledToken]) -> String {
+    let text: String = row.iter().map(StyledToken::token).collect();
+    return text.trim().trim_end_matches(',').to_string();
+}
+
+/// Lowlight lines that moved rather than changed, like when a tool
+/// serializing JSON reorders keys. The lines that did change keep their
+/// highlights.
+pub fn lowlight_moved_lines(old_tokens: &mut [StyledToken], new_tokens: &mut [StyledToken]) {
+    let mut old_counts: HashMap<String, usize> = HashMap::new();
+    for row in old_tokens.split(|token| token.token() == "\n") {
+        *old_counts.entry(comparison_key(row)).or_default() += 1;
+    }
+    let mut new_counts: HashMap<String, usize> = HashMap::new();
+    for row in new_tokens.split(|token| token.token() == "\n") {
+        *new_counts.entry(comparison_key(row)).or_default() += 1;
+    }
+
+    // Lines on both sides, and how many of them
+    let mut moved: HashMap<String, usize> = old_counts
+        .into_iter()
+        .filter(|(key, _)| !key.is_empty())
+        .filter_map(|(key, old_count)| {
+            let count = old_count.min(*new_counts.get(&key)?);
+            return Some((key, count));
+        })
+        .collect();
+    if moved.is_empty() {
+        return;
+    }
+
+    let mut moved_new = moved.clone();
+    for (tokens, left) in [(old_tokens, &mut moved), (new_tokens, &mut moved_new)] {
+        for row in tokens.split_mut(|token| token.token() == "\n") {
+            let count = match left.get_mut(&comparison_key(row)) {
+                Some(count) if *count > 0 => count,
+                _ => continue,
+            };
+            *count -= 1;
+            for token in row.iter_mut() {
+                token.set_style(Style::Lowlighted);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(
+            tokenize("  \"max size\": -1.5e3,\n"),
+            [" ", " ", "\"max size\"", ":", " ", "-1.5e3", ",", "\n"]
+        );
+        assert_eq!(
+            tokenize("[\"new value\", true]"),
+            ["[", "\"", "new", " ", "value", "\"", ",", " ", "true", "]"]
+        );
+
+        // Escaped quotes don't end strings
+        assert_eq!(tokenize(r#""a\"b": 1"#), [r#""a\"b""#, ":", " ", "1"]);
+    }
+
+    #[test]
+    fn test_lowlight_moved_lines() {
+        // Lines with the tokens of each line in one style
+        let styled = |lines: &[(&str, Style)]| {
+            let mut tokens: Vec<StyledToken> = Vec::new();
+            for (line, style) in lines {
+                for token in tokenize(line) {
+                    tokens.push(StyledToken::new(token.to_string(), *style));
+                }
+                tokens.push(StyledToken::new("\n".to_string(), Style::Highlighted));
+            }
+            return tokens;
+        };
+        let (a_1, b_2) = ("  \"a\": 1,", "  \"b\": 2");
+        let (b_2_comma, a_3) = ("  \"b\": 2,", "  \"a\": 3");
+        let mut old_tokens = styled(&[(a_1, Style::Highlighted), (b_2, Style::Highlighted)]);
+        let mut new_tokens = styled(&[(b_2_comma, Style::Highlighted), (a_3, Style::Highlighted)]);
+        lowlight_moved_lines(&mut old_tokens, &mut new_tokens);
+
+        // "b" moved, "a" changed its value
+        assert_eq!(
+            old_tokens,
+            styled(&[(a_1, Style::Highlighted), (b_2, Style::Lowlighted)])
+        );
+        assert_eq!(
+            new_tokens,
+            styled(&[(b_2_comma, Style::Lowlighted), (a_3, Style::Highlighted)])
+        );
+    }
+}
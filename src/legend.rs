@@ -0,0 +1,98 @@
+use crate::ansi::remove_ansi_escape_codes;
+use crate::display_width::display_width;
+use crate::palette::Palette;
+
+/// What riff's styling means, shown at the top of the output with `--legend`.
+/// For people looking at riff output for the first time, like in screenshots.
+#[must_use]
+pub fn legend(palette: &Palette) -> String {
+    let p = palette;
+    let rows = [
+        (
+            format!("{}-Removed line{}", p.old, p.normal),
+            "Removed lines are red",
+        ),
+        (
+            format!("{}+Added line{}", p.new, p.normal),
+            "Added lines are green",
+        ),
+        (
+            format!(
+                "{}+Some {}changed{} part{}",
+                p.new, p.inverse_video, p.not_inverse_video, p.normal
+            ),
+            "Changed parts of lines are in inverse video",
+        ),
+        (
+            format!("{}{}-Faint line{}", p.faint, p.old, p.normal),
+            "Faint removed lines: Nothing removed, only added",
+        ),
+        (
+            format!("{}+Error{}{}  {}", p.new, p.inverse_video, p.old, p.normal),
+            "Red inverse video: Trailing whitespace or TAB",
+        ),
+        (
+            format!("{}+++ b/file.txt{}", p.bold, p.normal),
+            "File names are bold",
+        ),
+        (
+            format!(
+                "{}@@ -1 +1 @@ {}fn main(){}",
+                p.hunk_header, p.bold, p.normal
+            ),
+            "Hunk headers, with the function name in bold",
+        ),
+    ];
+
+    // Line the descriptions up two columns after the widest sample
+    let widths: Vec<usize> = rows
+        .iter()
+        .map(|(sample, _)| display_width(&strip_escapes(sample)))
+        .collect();
+    let widest = widths.iter().copied().max().unwrap_or_default();
+
+    let mut legend = format!("{}Legend{}\n", p.bold, p.normal);
+    for ((sample, description), width) in rows.iter().zip(widths) {
+        legend.push_str(&format!(
+            "  {sample}{}{}{description}{}\n",
+            " ".repeat(widest + 2 - width),
+            p.faint,
+            p.normal
+        ));
+    }
+    legend.push('\n');
+
+    return legend;
+}
+
+fn strip_escapes(text: &str) -> String {
+    let mut bytes = text.as_bytes().to_vec();
+    remove_ansi_escape_codes(&mut bytes);
+    return String::from_utf8(bytes).unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_legend() {
+        assert_eq!(
+            strip_escapes(&legend(&Palette::default())),
+            concat!(
+                "Legend\n",
+                "  -Removed line          Removed lines are red\n",
+                "  +Added line            Added lines are green\n",
+                "  +Some changed part     Changed parts of lines are in inverse video\n",
+                "  -Faint line            Faint removed lines: Nothing removed, only added\n",
+                "  +Error                 Red inverse video: Trailing whitespace or TAB\n",
+                "  +++ b/file.txt         File names are bold\n",
+                "  @@ -1 +1 @@ fn main()  Hunk headers, with the function name in bold\n",
+                "\n",
+            )
+        );
+    }
+}
@@ -0,0 +1,90 @@
+/// The `less` status line prompt, like `riff (12 files) line 40/900 4%`.
+///
+/// `less` can't tell which file is at the top of the screen, but it can show
+/// the line number and how far into the diff that is. The number of files is
+/// only known when the whole diff has been read before paging.
+#[must_use]
+pub fn prompt(file_count: Option<usize>) -> String {
+    let files = match file_count {
+        Some(1) => " (1 file)".to_string(),
+        Some(file_count) => format!(" ({file_count} files)"),
+        None => String::new(),
+    };
+
+    // See PROMPTS in `man less` for the syntax
+    return format!("riff{files}?lt line %lt?L/%L..?pB %pB\\%.?e (END).%t");
+}
+
+/// True if the `$LESS` options already pick a prompt, which we shouldn't
+/// override
+#[must_use]
+pub fn has_prompt_option(less_options: &str) -> bool {
+    return less_options.split_whitespace().any(|option| {
+        return option.trim_start_matches('-').starts_with('P') || option.starts_with("--prompt");
+    });
+}
+
+/// `$LESS` value for paging with our prompt. Everything up to a `$` is the
+/// prompt.
+#[must_use]
+pub fn less_options(less_options: &str, file_count: Option<usize>) -> String {
+    if has_prompt_option(less_options) {
+        return less_options.to_string();
+    }
+    return format!("{less_options} -Ps{}$", prompt(file_count));
+}
+
+/// Count the files in a diff, by their `diff` lines or by their `+++` lines
+/// for diffs without `diff` lines
+#[must_use]
+pub fn count_files(diff: &[u8]) -> usize {
+    let count = |prefix: &[u8]| {
+        return diff
+            .split(|byte| *byte == b'\n')
+            .filter(|line| line.starts_with(prefix))
+            .count();
+    };
+    return count(b"diff ").max(count(b"+++ "));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_less_options() {
+        assert_eq!(
+            less_options("FRX", Some(2)),
+            "FRX -Psriff (2 files)?lt line %lt?L/%L..?pB %pB\\%.?e (END).%t$"
+        );
+        assert_eq!(
+            less_options("FRX", None),
+            "FRX -Psriff?lt line %lt?L/%L..?pB %pB\\%.?e (END).%t$"
+        );
+
+        // The user's own prompt wins
+        assert_eq!(less_options("-R -Ps%f", Some(2)), "-R -Ps%f");
+        assert_eq!(less_options("-R --prompt=s%f", Some(2)), "-R --prompt=s%f");
+    }
+
+    #[test]
+    fn test_count_files() {
+        let git_diff = concat!(
+            "diff --git a/x b/x\n",
+            "--- a/x\n",
+            "+++ b/x\n",
+            "@@ -1 +1 @@\n",
+            "-a\n",
+            "+b\n",
+            "diff --git a/y.png b/y.png\n",
+            "Binary files a/y.png and b/y.png differ\n",
+        );
+        assert_eq!(count_files(git_diff.as_bytes()), 2);
+
+        let plain_diff = "--- x\n+++ y\n@@ -1 +1 @@\n-a\n+b\n";
+        assert_eq!(count_files(plain_diff.as_bytes()), 1);
+    }
+}
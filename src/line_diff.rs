@@ -0,0 +1,235 @@
+use crate::refiner::{diff_edits, Edit};
+
+/// How many lines of context to show around changes in diffs we make, same as
+/// the `diff -u` default
+pub const CONTEXT_LINES: usize = 3;
+
+/// Line diffing is O(m * n), don't try it on texts with more lines than this.
+/// Numbers have been invented, not measured.
+pub const MAX_LINES_PRODUCT: usize = 3_000 * 3_000;
+
+/// One line of a line diff, without its ` `, `-` or `+` prefix
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineEdit<'a> {
+    Context(&'a str),
+    Old(&'a str),
+    New(&'a str),
+}
+
+/// Line diff `old` against `new`.
+///
+/// Returns `None` if the number of old lines times the number of new lines is
+/// larger than `max_lines_product`.
+#[must_use]
+pub fn line_edits<'a>(
+    old: &[&'a str],
+    new: &[&'a str],
+    max_lines_product: usize,
+) -> Option<Vec<LineEdit<'a>>> {
+    if old.len() * new.len() > max_lines_product {
+        return None;
+    }
+
+    let mut old_position = 0;
+    let mut new_position = 0;
+    let mut edits: Vec<LineEdit> = Vec::with_capacity(old.len().max(new.len()));
+    for edit in diff_edits(old, new) {
+        match edit {
+            Edit::Same => {
+                edits.push(LineEdit::Context(old[old_position]));
+                old_position += 1;
+                new_position += 1;
+            }
+            Edit::Removed => {
+                edits.push(LineEdit::Old(old[old_position]));
+                old_position += 1;
+            }
+            Edit::Added => {
+                edits.push(LineEdit::New(new[new_position]));
+                new_position += 1;
+            }
+        }
+    }
+    return Some(edits);
+}
+
+/// `1,3`, or just `5` for single lines, like `diff -u` does it
+fn format_range(start: usize, count: usize) -> String {
+    if count == 1 {
+        return format!("{start}");
+    }
+    if count == 0 {
+        // Empty ranges point at the line before
+        return format!("{},0", start - 1);
+    }
+    return format!("{start},{count}");
+}
+
+/// Unified diff hunks for `edits`, each starting with an `@@` line and with
+/// `context_lines` lines of context around the changes
+#[must_use]
+pub fn to_hunks(edits: &[LineEdit], context_lines: usize) -> Vec<String> {
+    let changes: Vec<usize> = (0..edits.len())
+        .filter(|index| !matches!(edits[*index], LineEdit::Context(_)))
+        .collect();
+
+    let mut lines: Vec<String> = Vec::new();
+    let mut change_index = 0;
+    while change_index < changes.len() {
+        let start = changes[change_index].saturating_sub(context_lines);
+        let mut end = changes[change_index] + 1;
+        change_index += 1;
+        while change_index < changes.len() && changes[change_index] - end <= 2 * context_lines {
+            end = changes[change_index] + 1;
+            change_index += 1;
+        }
+        let end = (end + context_lines).min(edits.len());
+
+        let old_count = |edits: &[LineEdit]| {
+            return edits
+                .iter()
+                .filter(|edit| !matches!(edit, LineEdit::New(_)))
+                .count();
+        };
+        let new_count = |edits: &[LineEdit]| {
+            return edits
+                .iter()
+                .filter(|edit| !matches!(edit, LineEdit::Old(_)))
+                .count();
+        };
+        let (before, hunk) = (&edits[..start], &edits[start..end]);
+        lines.push(format!(
+            "@@ -{} +{} @@",
+            format_range(old_count(before) + 1, old_count(hunk)),
+            format_range(new_count(before) + 1, new_count(hunk)),
+        ));
+        for edit in hunk {
+            lines.push(match edit {
+                LineEdit::Context(line) => format!(" {line}"),
+                LineEdit::Old(line) => format!("-{line}"),
+                LineEdit::New(line) => format!("+{line}"),
+            });
+        }
+    }
+    return lines;
+}
+
+/// Line diff two texts into unified diff hunks, see [`to_hunks`].
+///
+/// Returns `None` if the texts are too large for diffing, see
+/// [`line_edits`].
+#[must_use]
+pub fn unified_diff(
+    old_text: &str,
+    new_text: &str,
+    context_lines: usize,
+    max_lines_product: usize,
+) -> Option<Vec<String>> {
+    let old_lines: Vec<&str> = old_text.lines().collect();
+    let new_lines: Vec<&str> = new_text.lines().collect();
+    let edits = line_edits(&old_lines, &new_lines, max_lines_product)?;
+    return Some(to_hunks(&edits, context_lines));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_unified_diff() {
+        assert_eq!(
+            unified_diff("a\nb\n", "a\nb\n", CONTEXT_LINES, MAX_LINES_PRODUCT),
+            Some(vec![])
+        );
+
+        assert_eq!(
+            unified_diff(
+                "1\n2\n3\n4\n5\n6\n7\n8\n9\n",
+                "1\n2\n3\n4\nfive\n6\n7\n8\n9\n",
+                CONTEXT_LINES,
+                MAX_LINES_PRODUCT
+            )
+            .unwrap(),
+            [
+                "@@ -2,7 +2,7 @@",
+                " 2",
+                " 3",
+                " 4",
+                "-5",
+                "+five",
+                " 6",
+                " 7",
+                " 8"
+            ]
+        );
+
+        // One line of context
+        assert_eq!(
+            unified_diff("1\n2\n3\n4\n", "1\n2\nthree\n4\n", 1, MAX_LINES_PRODUCT).unwrap(),
+            ["@@ -2,3 +2,3 @@", " 2", "-3", "+three", " 4"]
+        );
+
+        assert_eq!(unified_diff("a\nb\n", "c\nd\n", CONTEXT_LINES, 3), None);
+    }
+
+    #[test]
+    fn test_unified_diff_two_hunks() {
+        let old = "a\n1\n2\n3\n4\n5\n6\n7\n8\nb\n";
+        let new = "A\n1\n2\n3\n4\n5\n6\n7\n8\nB\n";
+        assert_eq!(
+            unified_diff(old, new, CONTEXT_LINES, MAX_LINES_PRODUCT).unwrap(),
+            [
+                "@@ -1,4 +1,4 @@",
+                "-a",
+                "+A",
+                " 1",
+                " 2",
+                " 3",
+                "@@ -7,4 +7,4 @@",
+                " 6",
+                " 7",
+                " 8",
+                "-b",
+                "+B",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unified_diff_from_nothing() {
+        assert_eq!(
+            unified_diff("", "a\n", CONTEXT_LINES, MAX_LINES_PRODUCT).unwrap(),
+            ["@@ -0,0 +1 @@", "+a"]
+        );
+    }
+
+    #[test]
+    fn test_to_hunks() {
+        let old = ["1", "2", "3", "4", "5", "6", "7", "8", "9", "10"];
+        let mut new = old.to_vec();
+        new[0] = "one";
+        new.truncate(9);
+        assert_eq!(
+            to_hunks(
+                &line_edits(&old, &new, MAX_LINES_PRODUCT).unwrap(),
+                CONTEXT_LINES
+            ),
+            [
+                "@@ -1,4 +1,4 @@",
+                "-1",
+                "+one",
+                " 2",
+                " 3",
+                " 4",
+                "@@ -7,4 +7,3 @@",
+                " 7",
+                " 8",
+                " 9",
+                "-10",
+            ]
+        );
+    }
+}
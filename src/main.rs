@@ -9,29 +9,102 @@
 extern crate lazy_static;
 
 use backtrace::Backtrace;
+use config::{Config, NewFiles, NoisyLines, SpacesBetweenHighlights, Whitespace, WordRegex};
+use constants::{BOLD, NORMAL};
+use describe::Describe;
+use encoding::DecodingReader;
+use external_diff::ExternalDiffArgs;
+use file_sort::SortOrder;
 use git_version::git_version;
 use line_collector::LineCollector;
-use std::io::{self, IsTerminal};
+use plain_refined::PlainRefined;
+use progress::ProgressReader;
+use regex::Regex;
+use std::io::{self, IsTerminal, Read, Seek, SeekFrom};
 use std::panic;
 use std::path;
 use std::process::exit;
 use std::process::{Command, Stdio};
 use std::str;
 use std::{env, fs::File};
+use tee::Tee;
+use theme::Theme;
 
 mod ansi;
+mod blame;
+mod comments;
 mod commit_line;
+mod compact;
+mod completions;
+mod config;
 mod constants;
+mod control_chars;
+mod converters;
+mod csv;
+mod describe;
+mod display_width;
+mod edit_script;
+mod embedded;
+mod encoding;
+mod eof_marker;
+mod external_diff;
+mod file_sort;
+mod file_timestamp;
+mod git_config;
+mod git_objects;
+mod hexdump;
+mod html;
+mod hunk;
+mod hunk_header;
+mod json;
+mod legend;
+mod less_prompt;
 mod line_collector;
+mod line_diff;
+mod markdown;
+mod memory_budget;
+mod new_file;
+mod palette;
+mod path_shortener;
+mod plain_refined;
+mod plain_renames;
+mod profile;
+mod progress;
+mod python;
+mod quoted_path;
+mod range_diff;
 mod refiner;
+mod reject;
+mod rename_chain;
+mod repeated_files;
+mod review;
+mod riff_output;
+mod ruler;
+mod rust;
+mod secrets;
+#[cfg(feature = "self-update")]
+mod self_update;
+mod staging;
+mod stat;
+mod tee;
+mod terminal_title;
+mod theme;
+mod token_cache;
 mod token_collector;
+mod token_style;
 mod tokenizer;
+mod truncate;
+mod whitespace_summary;
+mod yaml;
 
 const HELP_TEXT: &str = r#"
 Usage:
   diff ... | riff
-  riff [-b] [--no-pager] <file1> <file2>
-  riff [-b] [--no-pager] <directory1> <directory2>
+  diff ... | riff --embedded
+  riff [-b | --iw] [--no-pager] <file1> <file2>
+  riff [-b | --iw] [--no-pager] <directory1> <directory2>
+  riff [options] diff|show|log|blame [git options...]
+  riff [options] add [git diff options...]
 
 Colors diff output, highlighting the changed parts of every line.
 
@@ -41,9 +114,102 @@ Git integration:
     git config --global pager.log riff
     git config --global interactive.diffFilter riff
 
+`riff --install-git-config` runs those commands for you after asking, and
+`riff --uninstall-git-config` undoes them.
+
+Or without touching the git configuration, `riff diff -U5 main` runs `git diff
+-U5 main` and highlights the result. Riff options go before `diff`. `riff show`
+and `riff log` work the same way, and `riff blame` colors lines by age.
+
+`riff diff --all` shows unstaged changes, staged changes and untracked files in
+one go, for reviewing everything before committing.
+
+`riff add` works like `git add -p`, asking about each highlighted hunk whether
+to stage it.
+
 Options:
-    -b:         Ignore changes in amount of whitespace
+    -b, --ignore-space-change: Ignore changes in amount of whitespace when
+                highlighting changed parts of lines
+    --iw:       Ignore all whitespace when highlighting changed parts of lines
+    --word-regex <regex>: What a word is when highlighting changed parts of
+                lines, like git's --word-diff-regex. Also see the [word-regex]
+                config section.
+    --plain-spaces: Don't highlight the spaces between highlighted words. Also
+                see the spaces-between-highlights setting.
+    --keep-noisy-highlights: Keep the highlights of changed lines that would
+                be mostly highlighted. Also see the noisy-lines setting.
     --no-pager: Don't page the result
+    --embedded: Highlight diffs embedded in other output, like build logs,
+                passing everything else through unchanged
+    --input-fd <fd>: Read the diff from this file descriptor rather than from
+                stdin, like `riff --input-fd 3 3< <(git diff)`. `riff
+                /dev/fd/3` works too.
+    --progress: Show progress on stderr while paging large inputs
+    --only-changes: Show only file headers, hunk headers and changed lines,
+                for piping into other tools
+    -q, --quiet: Leave out decorative lines, like index, file mode, similarity
+                index and binary file lines
+    --refine-renames: For renamed and copied files shown without changes,
+                like with `git diff -M -w`, diff their contents anyway
+    --detect-renames: Show removed and added files with similar contents as
+                renames, for diffs without rename detection like `diff -ruN`
+    --sort <order>: Show the files of the diff ordered by path, or with the
+                most additions, deletions or changes first
+    --max-memory <size>: Use at most about this much memory, like 512M or 2G.
+                When short on memory, riff reads ahead less and highlights
+                changed lines without refining them.
+    --collapse-removed <lines>: For longer runs of removed lines, show only
+                this many lines at the start and at the end, and how many
+                lines were left out in between
+    --max-line-length <columns>: Truncate lines wider than this, rather than
+                relying on the pager for horizontal scrolling
+    --ruler <columns>: Mark where added lines cross these columns, like
+                `--ruler 80` or `--ruler 80,100`
+    --output <file>: Also write the highlighted output to this file, for
+                viewing later with `less -R`
+    --width <columns>: Format for this terminal width, overriding $COLUMNS.
+                For when the output isn't going to a terminal.
+    --single-threaded: Refine changes in one thread rather than one per CPU,
+                for debugging and benchmarking
+    --html-email: Turn a patch from `git format-patch --stdout` into an email
+                with a highlighted HTML part, for `git send-email`
+    --keep-input-colors: Keep the colors of input lines that aren't part of
+                any diff, like the graph in `git log --color --graph -p`
+    --legend:   Start the output with an explanation of the colors, for
+                sharing screenshots with people who don't know riff
+    --whitespace-summary: End the output with how many lines with trailing
+                whitespace and non-leading TABs each file adds
+    --dim-repeated-files: In `git log -p` output, dim the file headers of
+                files that the previous commit also changed, and mark them
+                with "(again)"
+    --new-file-banner: Show added files with a "new file: path (N lines)"
+                line rather than with ---, +++ and @@ lines. Also see the
+                new-files setting.
+    --compact:  No - and + prefixes and one line file headers, for narrow
+                terminals. Also see the compact-below setting.
+    --no-header: Leave out the diff, index, --- and +++ lines, for embedding
+                diffs of single files in docs or commit messages
+    --terminal-title: Show the file currently being displayed in the
+                terminal title, and in tmux window lists
+    --plain-refined: Output plain text, with changed parts of lines marked as
+                [-removed-] and {+added+}, for pasting where colors get lost
+    --describe: Output text for screen readers, with removed: and added:
+                prefixes instead of colors, changed parts of lines listed
+                after each line, and no decorative characters
+    --age-heatmap: Color the gutters of context and removed lines by how long
+                ago they last changed, according to `git blame HEAD`
+
+    --list-themes: Show what the built-in themes and the ones from the config
+                file look like
+
+    --dark, --light, --line-numbers, --paging=never, --color-only, --patch:
+                Accepted for compatibility with delta and diff-so-fancy
+
+    --generate-completions <shell>: Print completions for bash, zsh or fish
+    --generate-config [--write]: Print a commented default config file, or
+                write it to ~/.config/riff/config.toml
+    --self-update: Replace this riff binary with the latest release, if
+                built with the self-update feature
 
     --help:     Print this text
     --version:  Print version number
@@ -67,8 +233,26 @@ const PAGER_FORKBOMB_STOP: &str = "_RIFF_IGNORE_PAGER";
 // Ref: https://github.com/walles/riff/issues/26#issuecomment-1120294897
 const GIT_VERSION: &str = git_version!(cargo_prefix = "");
 
-fn highlight_diff<W: io::Write + Send + 'static>(input: &mut dyn io::Read, output: W) {
-    let mut line_collector = LineCollector::new(output);
+fn highlight_diff<W: io::Write + Send + 'static>(
+    input: &mut dyn io::Read,
+    output: W,
+    config: &Config,
+) {
+    let output: Box<dyn io::Write + Send> = match &config.output_file {
+        Some(path) => Box::new(Tee::new(output, path)),
+        None => Box::new(output),
+    };
+    let output: Box<dyn io::Write + Send> = if config.describe {
+        Box::new(Describe::new(output))
+    } else if config.plain_refined {
+        Box::new(PlainRefined::new(output))
+    } else {
+        output
+    };
+    let mut line_collector = LineCollector::new(output, config.clone());
+
+    // Convert UTF-16 input into UTF-8 before splitting it into lines
+    let mut input = DecodingReader::new(input);
 
     // Read input line by line, using from_utf8_lossy() to convert lines into
     // strings while handling invalid UTF-8 without crashing
@@ -80,6 +264,11 @@ fn highlight_diff<W: io::Write + Send + 'static>(input: &mut dyn io::Read, outpu
             panic!("Error reading input stream: {:?}", result.err().unwrap());
         }
 
+        if line_collector.is_cancelled() {
+            // Somebody quit their pager, stop reading
+            break;
+        }
+
         let read_count = result.unwrap();
         if read_count == 0 {
             // End of stream
@@ -92,10 +281,6 @@ fn highlight_diff<W: io::Write + Send + 'static>(input: &mut dyn io::Read, outpu
 
         for byte in buf.iter().take(read_count) {
             let byte = *byte;
-            if byte == b'\r' {
-                // MS-DOS file, LF coming up, just ignore this
-                continue;
-            }
             if byte != b'\n' {
                 // Line contents, store and continue
                 line.push(byte);
@@ -108,13 +293,30 @@ fn highlight_diff<W: io::Write + Send + 'static>(input: &mut dyn io::Read, outpu
             continue;
         }
     }
+
+    let cancelled = line_collector.is_cancelled();
+    drop(line_collector);
+    if cancelled {
+        // Don't hang around waiting for whatever is feeding us diffs, just
+        // exit like we've always done when the pager goes away.
+        exit(0);
+    }
 }
 
 /// Try paging using the named pager (`$PATH` will be searched).
 ///
 /// Returns `true` if the pager was found, `false` otherwise.
+///
+/// `input_size` is used for showing an ETA with `--progress`, and
+/// `file_count` for showing the number of files in the `less` prompt.
 #[must_use]
-fn try_pager(input: &mut dyn io::Read, pager_name: &str) -> bool {
+fn try_pager(
+    input: &mut dyn io::Read,
+    input_size: Option<u64>,
+    file_count: Option<usize>,
+    pager_name: &str,
+    config: &Config,
+) -> bool {
     let mut command = Command::new(pager_name);
 
     if env::var(PAGER_FORKBOMB_STOP).is_ok() {
@@ -123,10 +325,9 @@ fn try_pager(input: &mut dyn io::Read, pager_name: &str) -> bool {
     }
     command.env(PAGER_FORKBOMB_STOP, "1");
 
-    if env::var("LESS").is_err() {
-        // Set by git when paging
-        command.env("LESS", "FRX");
-    }
+    // Set by git when paging
+    let less = env::var("LESS").unwrap_or_else(|_| "FRX".to_string());
+    command.env("LESS", less_prompt::less_options(&less, file_count));
 
     if env::var("LV").is_err() {
         // Set by git when paging
@@ -139,7 +340,12 @@ fn try_pager(input: &mut dyn io::Read, pager_name: &str) -> bool {
         Ok(mut pager) => {
             let pager_stdin = pager.stdin.unwrap();
             pager.stdin = None;
-            highlight_diff(input, pager_stdin);
+            if config.progress && io::stderr().is_terminal() {
+                let mut progress_reader = ProgressReader::new(input, input_size);
+                highlight_diff(&mut progress_reader, pager_stdin, config);
+            } else {
+                highlight_diff(input, pager_stdin, config);
+            }
 
             // FIXME: Report pager exit status if non-zero, together with
             // contents of pager stderr as well if possible.
@@ -168,6 +374,139 @@ fn consume(option: &str, argv: &mut Vec<String>) -> bool {
     return true;
 }
 
+/// Consume an option and the value following it, like `--option value` or
+/// `--option=value`.
+///
+/// Returns `None` if the option wasn't found, and `Some("")` if it was found
+/// without any value.
+fn consume_value(option: &str, argv: &mut Vec<String>) -> Option<String> {
+    let prefix = format!("{option}=");
+    for index in 0..argv.len() {
+        if let Some(value) = argv[index].strip_prefix(&prefix) {
+            let value = value.to_string();
+            argv.remove(index);
+            return Some(value);
+        }
+
+        if argv[index] == option {
+            argv.remove(index);
+            if index < argv.len() {
+                return Some(argv.remove(index));
+            }
+            return Some(String::new());
+        }
+    }
+
+    return None;
+}
+
+/// Consume an option taking a positive number of columns, like
+/// `--max-line-length 80`. Exits with an error message if the value isn't a
+/// positive number.
+fn consume_columns(option: &str, argv: &mut Vec<String>) -> Option<usize> {
+    let value = consume_value(option, argv)?;
+    match value.parse::<usize>() {
+        Ok(columns) if columns > 0 => return Some(columns),
+        _ => {
+            eprintln!("ERROR: {option} needs a positive number of columns");
+            eprintln!();
+            print_help(&mut io::stderr());
+            exit(1);
+        }
+    }
+}
+
+/// Consume `--ruler 80,100`. Exits with an error message if the value isn't
+/// a list of positive numbers.
+fn consume_rulers(argv: &mut Vec<String>) -> Vec<usize> {
+    let value = match consume_value("--ruler", argv) {
+        Some(value) => value,
+        None => return Vec::new(),
+    };
+
+    let rulers: Option<Vec<usize>> = value
+        .split(',')
+        .map(|column| {
+            column
+                .trim()
+                .parse::<usize>()
+                .ok()
+                .filter(|column| *column > 0)
+        })
+        .collect();
+    match rulers {
+        Some(rulers) => return rulers,
+        None => {
+            eprintln!("ERROR: --ruler needs comma separated positive column numbers, like 80,100");
+            eprintln!();
+            print_help(&mut io::stderr());
+            exit(1);
+        }
+    }
+}
+
+/// `git add -p` runs its `interactive.diffFilter` with `GIT_INDEX_FILE` set,
+/// and reads the filter's output itself rather than showing it in a terminal
+fn is_interactive_diff_filter() -> bool {
+    return env::var_os("GIT_INDEX_FILE").is_some() && !io::stdout().is_terminal();
+}
+
+/// Git commands that can be run as `riff <command> [git options...]`
+const GIT_SUBCOMMANDS: &[&str] = &["diff", "show", "log", "blame", "add"];
+
+/// If there is a git subcommand among the options, like in `riff --no-pager
+/// diff -U5 main`, remove it and its options from `argv` and return them.
+///
+/// Only riff options may come before the subcommand, so `riff file1 diff` is
+/// still about a file named `diff`.
+fn consume_git_subcommand(argv: &mut Vec<String>) -> Option<(String, Vec<String>)> {
+    let mut index = 1;
+    while argv.get(index)?.starts_with('-') {
+        if completions::takes_value(&argv[index]) {
+            // Skip the value
+            index += 1;
+        }
+        index += 1;
+    }
+    if !GIT_SUBCOMMANDS.contains(&argv[index].as_str()) {
+        return None;
+    }
+
+    let git_args = argv.split_off(index + 1);
+    let subcommand = argv.pop().unwrap();
+    return Some((subcommand, git_args));
+}
+
+/// Consume flags from `delta` and `diff-so-fancy`, so that switching to riff
+/// doesn't require rewriting git configs and scripts right away.
+///
+/// `--dark`, `--light` and `--line-numbers` are ignored. Returns whether
+/// `--paging=never` was given, and whether `--color-only` or `--patch` asked
+/// for `interactive.diffFilter` mode. Exits with an error message on unknown
+/// `--paging` values.
+#[must_use]
+fn consume_compatibility_flags(argv: &mut Vec<String>) -> (bool, bool) {
+    for ignored in ["--dark", "--light", "--line-numbers"].iter() {
+        let _ = consume(ignored, argv);
+    }
+
+    let paging_never = match consume_value("--paging", argv).as_deref() {
+        None | Some("auto") | Some("always") => false,
+        Some("never") => true,
+        Some(_) => {
+            eprintln!("ERROR: --paging must be one of always, never or auto");
+            eprintln!();
+            print_help(&mut io::stderr());
+            exit(1);
+        }
+    };
+
+    // delta's and diff-so-fancy's ways of saying interactive.diffFilter
+    let diff_filter = consume("--color-only", argv) | consume("--patch", argv);
+
+    return (paging_never, diff_filter);
+}
+
 fn print_help(output: &mut dyn io::Write) {
     output.write_all(HELP_TEXT.trim().as_bytes()).unwrap();
     output.write_all(b"\n").unwrap();
@@ -187,7 +526,7 @@ fn print_help(output: &mut dyn io::Write) {
     output.write_all(b"\n").unwrap();
 }
 
-fn panic_handler(panic_info: &panic::PanicInfo) {
+fn panic_handler(panic_info: &panic::PanicHookInfo) {
     eprintln!("\n\n-v-v-v----------- RIFF CRASHED ---------------v-v-v-\n",);
 
     // Panic message
@@ -207,20 +546,76 @@ fn panic_handler(panic_info: &panic::PanicInfo) {
     eprintln!("{CRASH_FOOTER}");
 }
 
-fn highlight_stream(input: &mut dyn io::Read, no_pager: bool) {
+/// With `--detect-renames` or `--sort`, read the whole input, turn similar
+/// removed and added files into renames and reorder the files
+fn with_whole_diff_changes(input: &mut dyn io::Read, config: &Config) -> Vec<u8> {
+    let mut diff = Vec::new();
+    if let Err(error) = input.read_to_end(&mut diff) {
+        eprintln!("ERROR: Reading the diff failed: {error}");
+        exit(1);
+    }
+
+    // Leave input that isn't UTF-8 to the usual decoding
+    let text = match str::from_utf8(&diff) {
+        Ok(text) => text,
+        Err(_) => return diff,
+    };
+
+    let renamed = if config.detect_renames {
+        plain_renames::detect_renames(text)
+    } else {
+        None
+    };
+    let text = renamed.as_deref().unwrap_or(text);
+
+    return match config.sort_files {
+        Some(order) => file_sort::sort_files(text, order).into_bytes(),
+        None => text.as_bytes().to_vec(),
+    };
+}
+
+fn highlight_stream(
+    input: &mut dyn io::Read,
+    input_size: Option<u64>,
+    no_pager: bool,
+    config: &Config,
+) {
+    let changed;
+    let mut changed_input;
+    let (input, input_size, file_count): (&mut dyn io::Read, Option<u64>, Option<usize>) =
+        if config.detect_renames || config.sort_files.is_some() {
+            changed = with_whole_diff_changes(input, config);
+            changed_input = changed.as_slice();
+            let file_count = less_prompt::count_files(&changed);
+            (
+                &mut changed_input,
+                Some(changed.len() as u64),
+                Some(file_count),
+            )
+        } else {
+            (input, input_size, None)
+        };
+
     if !io::stdout().is_terminal() {
         // We're being piped, just do stdin -> stdout
-        highlight_diff(input, io::stdout());
+        highlight_diff(input, io::stdout(), config);
         return;
     }
 
     if no_pager {
-        highlight_diff(input, io::stdout());
+        highlight_diff(input, io::stdout(), config);
         return;
     }
 
+    // Pagers read ahead of what they show, and show title sequences as
+    // garbage
+    let config = &Config {
+        terminal_title: false,
+        ..config.clone()
+    };
+
     if let Ok(pager_value) = env::var("PAGER") {
-        if try_pager(input, &pager_value) {
+        if try_pager(input, input_size, file_count, &pager_value, config) {
             return;
         }
 
@@ -228,16 +623,16 @@ fn highlight_stream(input: &mut dyn io::Read, no_pager: bool) {
         // doesn't exist.
     }
 
-    if try_pager(input, "moar") {
+    if try_pager(input, input_size, file_count, "moar", config) {
         return;
     }
 
-    if try_pager(input, "less") {
+    if try_pager(input, input_size, file_count, "less", config) {
         return;
     }
 
     // No pager found, wth?
-    highlight_diff(input, io::stdout());
+    highlight_diff(input, io::stdout(), config);
 }
 
 pub fn type_string(path: &path::Path) -> &str {
@@ -253,6 +648,34 @@ pub fn type_string(path: &path::Path) -> &str {
     return "Not file not dir";
 }
 
+/// Open a diff to highlight, like `/dev/fd/3` from `--input-fd 3`. Also
+/// returns the size of the diff if it's a regular file. Exits with an error
+/// message if the diff can't be opened.
+fn open_input(path: &str) -> (File, Option<u64>) {
+    let file = File::open(path).unwrap_or_else(|error| {
+        eprintln!("ERROR: Can't read {path}: {error}");
+        exit(1);
+    });
+    let size = file
+        .metadata()
+        .ok()
+        .filter(|metadata| metadata.is_file())
+        .map(|metadata| metadata.len());
+    return (file, size);
+}
+
+/// If stdin has been redirected from a file, return the size of that file
+fn stdin_size() -> Option<u64> {
+    #[cfg(unix)]
+    if let Ok(metadata) = std::fs::metadata("/dev/stdin") {
+        if metadata.is_file() {
+            return Some(metadata.len());
+        }
+    }
+
+    return None;
+}
+
 fn ensure_readable(path: &path::Path) {
     if let Err(why) = File::open(path) {
         eprintln!("ERROR: {}: {}", why, path.to_string_lossy());
@@ -267,7 +690,185 @@ fn ensure_listable(path: &path::Path) {
     }
 }
 
-fn exec_diff_highlight(path1: &str, path2: &str, ignore_space_change: bool, no_pager: bool) {
+/// Highlight `input` into a string rather than onto the terminal
+fn highlight_to_string(input: &str, config: &Config) -> String {
+    // Nothing for the --output file here, this isn't what is being reviewed
+    let config = Config {
+        output_file: None,
+        legend: false,
+        plain_refined: false,
+        terminal_title: false,
+        ..config.clone()
+    };
+
+    let mut file = tempfile::tempfile().unwrap();
+    highlight_diff(&mut input.as_bytes(), file.try_clone().unwrap(), &config);
+
+    let mut highlighted = String::new();
+    file.seek(SeekFrom::Start(0)).unwrap();
+    file.read_to_string(&mut highlighted).unwrap();
+    return highlighted;
+}
+
+/// Shown by `--list-themes`, with identifiers, numbers, punctuation and
+/// strings for the themes to style
+const THEME_SAMPLE_DIFF: &str = concat!(
+    "--- a/greet.rs\n",
+    "+++ b/greet.rs\n",
+    "@@ -1,4 +1,4 @@\n",
+    " fn greet(name: &str, times: usize) {\n",
+    "     for _ in 0..times {\n",
+    "-        println!(\"Hello, {}!\", name);\n",
+    "+        println!(\"Hi there, {}!\", name);\n",
+    "     }\n",
+);
+
+/// `riff --list-themes`: Show the built-in themes and the ones from the config
+/// file, each one highlighting a sample diff
+fn print_themes(config: &Config) {
+    let built_in_themes = Theme::built_in_themes().map(|(name, theme)| (name.to_string(), theme));
+    for (name, theme) in built_in_themes.chain(config.themes.iter().cloned()) {
+        let current = if theme == config.theme {
+            " (current)"
+        } else {
+            ""
+        };
+        println!("{BOLD}{name}{NORMAL}{current}");
+
+        let config = Config {
+            theme,
+            ..config.clone()
+        };
+        print!("{}", highlight_to_string(THEME_SAMPLE_DIFF, &config));
+        println!();
+    }
+}
+
+/// `git format-patch --stdout | riff --html-email`
+fn print_html_email(input: &mut dyn io::Read, config: &Config) {
+    let mut patch = Vec::new();
+    if let Err(error) = input.read_to_end(&mut patch) {
+        eprintln!("ERROR: Reading the patch failed: {error}");
+        exit(1);
+    }
+    let patch = String::from_utf8_lossy(&patch);
+
+    let email = html::format_email(&patch, |body| highlight_to_string(body, config));
+    print!("{email}");
+}
+
+/// Run `git <subcommand> <args...>` and highlight its output
+fn exec_git_highlight(subcommand: &str, args: &[String], no_pager: bool, config: &Config) {
+    if subcommand == "diff" && args.iter().any(|arg| arg == "--all") {
+        // "riff diff --all", not a git option
+        let args: Vec<String> = args.iter().filter(|arg| *arg != "--all").cloned().collect();
+        match review::collect_all_changes(&args, config.width) {
+            Ok(collected) => highlight_stream(&mut collected.as_slice(), None, no_pager, config),
+            Err(message) => {
+                eprintln!("ERROR: {message}");
+                exit(1);
+            }
+        }
+        return;
+    }
+
+    let mut config = config.clone();
+    let mut command = Command::new("git");
+    command.arg(subcommand);
+    if subcommand == "blame" {
+        // Timestamps are easier to compute ages from
+        command.arg("--date=unix");
+        config.blame = true;
+    }
+    let command = command.args(args).stdout(Stdio::piped());
+
+    let pretty_command = format!("{command:#?}");
+    let mut git_subprocess = match command.spawn() {
+        Ok(subprocess) => subprocess,
+        Err(err) => {
+            eprintln!("ERROR: Spawning git failed:\n  {pretty_command}\n  {err}\n");
+            exit(1);
+        }
+    };
+
+    let git_stdout = git_subprocess.stdout.as_mut().unwrap();
+    highlight_stream(git_stdout, None, no_pager, &config);
+
+    // git diff --exit-code uses 1 for "differences found", pass that on
+    let git_result = git_subprocess.wait().unwrap();
+    let git_exit_code = git_result.code().unwrap_or(2);
+    if git_exit_code != 0 {
+        exit(git_exit_code);
+    }
+}
+
+/// `diff` with the whitespace options matching our config
+fn diff_command(config: &Config) -> Command {
+    let mut command = Command::new("diff");
+    match config.whitespace {
+        Whitespace::Exact => {}
+        Whitespace::IgnoreChange => {
+            let _ = command.arg("-b");
+        }
+        Whitespace::IgnoreAll => {
+            let _ = command.arg("-w");
+        }
+    }
+    return command;
+}
+
+/// Wait for `diff` to finish, exiting if it failed. Differences found is not a
+/// failure.
+fn wait_for_diff(mut diff_subprocess: std::process::Child, pretty_command: &str) {
+    let diff_result = diff_subprocess.wait().unwrap();
+    let diff_exit_code = diff_result.code().unwrap_or(2);
+    if diff_exit_code != 0 && diff_exit_code != 1 {
+        // diff exit code was neither 0 (comparees identical) or 1 (differences
+        // found), this means trouble.
+        eprintln!("Exit code {diff_exit_code}: {pretty_command}");
+        exit(diff_exit_code);
+    }
+}
+
+/// Git runs us like this once per changed file when we're `GIT_EXTERNAL_DIFF`
+/// or `diff.external`. Git pages our output itself, so no pager here.
+fn exec_external_diff(args: &ExternalDiffArgs, config: &Config) {
+    let (old_label, new_label) = args.labels();
+    let mut command = diff_command(config);
+    let command = command
+        .arg("-u")
+        .arg("--show-c-function")
+        .arg("--label")
+        .arg(&old_label)
+        .arg("--label")
+        .arg(&new_label)
+        .arg(args.old_file)
+        .arg(args.new_file)
+        .stdout(Stdio::piped());
+
+    let pretty_command = format!("{command:#?}");
+    let mut diff_subprocess = match command.spawn() {
+        Ok(subprocess) => subprocess,
+        Err(err) => {
+            eprintln!("ERROR: Spawning diff failed:\n  {pretty_command}\n  {err}\n");
+            exit(1);
+        }
+    };
+
+    let header = args.header();
+    let diff_stdout = diff_subprocess.stdout.take().unwrap();
+    highlight_stream(
+        &mut header.as_bytes().chain(diff_stdout),
+        None,
+        true,
+        config,
+    );
+
+    // Git gives up on non-zero exit codes, so differences found must be 0
+    wait_for_diff(diff_subprocess, &pretty_command);
+}
+
+fn exec_diff_highlight(path1: &str, path2: &str, no_pager: bool, config: &Config) {
     let path1 = path::Path::new(path1);
     let path2 = path::Path::new(path2);
     let both_paths_are_non_dirs = !path1.is_dir() && !path2.is_dir();
@@ -289,12 +890,7 @@ fn exec_diff_highlight(path1: &str, path2: &str, ignore_space_change: bool, no_p
     }
 
     // Run "diff -ur file1 file2"
-    let mut command: &mut Command = &mut Command::new("diff");
-
-    if ignore_space_change {
-        command = command.arg("-b");
-    }
-
+    let mut command = diff_command(config);
     let command = command
         .arg("-ur") // "-u = unified diff, -r = recurse subdirectories"
         .arg("--show-c-function")
@@ -314,20 +910,50 @@ fn exec_diff_highlight(path1: &str, path2: &str, ignore_space_change: bool, no_p
     }
 
     let diff_stdout = diff_subprocess.stdout.as_mut().unwrap();
-    highlight_stream(diff_stdout, no_pager);
+    highlight_stream(diff_stdout, None, no_pager, config);
 
-    let diff_result = diff_subprocess.wait().unwrap();
-    let diff_exit_code = diff_result.code().unwrap_or(2);
-    if diff_exit_code != 0 && diff_exit_code != 1 {
-        // diff exit code was neither 0 (comparees identical) or 1 (differences
-        // found), this means trouble.
-        eprintln!("Exit code {diff_exit_code}: {pretty_command}");
-        exit(diff_exit_code);
+    wait_for_diff(diff_subprocess, &pretty_command);
+}
+
+/// Print a commented default config file, or write it to where we would
+/// load it from.
+fn generate_config(write: bool) {
+    let toml_text = config::default_config_toml();
+    if !write {
+        print!("{toml_text}");
+        return;
     }
+
+    let path = match config::default_config_path() {
+        Some(path) => path,
+        None => {
+            eprintln!("ERROR: Unable to figure out where the config file should go, set $HOME");
+            exit(1);
+        }
+    };
+
+    if path.exists() {
+        eprintln!(
+            "ERROR: Not overwriting existing config file: {}",
+            path.to_string_lossy()
+        );
+        exit(1);
+    }
+
+    let result = path
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|_| std::fs::write(&path, toml_text));
+    if let Err(error) = result {
+        eprintln!("ERROR: {}: {}", path.to_string_lossy(), error);
+        exit(1);
+    }
+
+    eprintln!("Config file written to {}", path.to_string_lossy());
 }
 
 fn main() {
-    panic::set_hook(Box::new(|panic_info: &panic::PanicInfo| {
+    panic::set_hook(Box::new(|panic_info: &panic::PanicHookInfo| {
         panic_handler(panic_info);
     }));
 
@@ -344,39 +970,240 @@ fn main() {
         return;
     }
 
-    let ignore_space_change = consume("-b", &mut args);
+    if let Some(shell) = consume_value("--generate-completions", &mut args) {
+        match completions::generate(&shell) {
+            Some(script) => print!("{script}"),
+            None => {
+                eprintln!(
+                    "ERROR: --generate-completions needs one of: {}",
+                    completions::SHELLS.join(", ")
+                );
+                exit(1);
+            }
+        }
+        return;
+    }
+
+    #[cfg(feature = "self-update")]
+    if consume("--self-update", &mut args) {
+        match self_update::self_update(GIT_VERSION) {
+            Ok(message) => println!("{message}"),
+            Err(message) => {
+                eprintln!("ERROR: {message}");
+                exit(1);
+            }
+        }
+        return;
+    }
+
+    let install = consume("--install-git-config", &mut args);
+    if install || consume("--uninstall-git-config", &mut args) {
+        let result = if install {
+            git_config::install()
+        } else {
+            git_config::uninstall()
+        };
+        if let Err(message) = result {
+            eprintln!("ERROR: {message}");
+            exit(1);
+        }
+        return;
+    }
+
+    if consume("--generate-config", &mut args) {
+        generate_config(consume("--write", &mut args));
+        return;
+    }
 
     if consume("--please-panic", &mut args) {
         panic!("Panicking on purpose");
     }
 
-    let no_pager = consume("--no-pager", &mut args);
+    let git_subcommand = consume_git_subcommand(&mut args);
+    let (paging_never, compatible_diff_filter) = consume_compatibility_flags(&mut args);
+    let no_pager = consume("--no-pager", &mut args) || paging_never;
+
+    let mut config = Config::load();
+    config.embedded = consume("--embedded", &mut args);
+    config.progress = consume("--progress", &mut args);
+    config.only_changes = consume("--only-changes", &mut args);
+    config.quiet = consume("-q", &mut args) | consume("--quiet", &mut args);
+    config.refine_renames = consume("--refine-renames", &mut args);
+    config.detect_renames = consume("--detect-renames", &mut args);
+    config.sort_files = consume_value("--sort", &mut args).map(|order| {
+        return SortOrder::parse(&order).unwrap_or_else(|| {
+            let names: Vec<&str> = SortOrder::NAMES.iter().map(|(name, _)| *name).collect();
+            eprintln!("ERROR: --sort must be one of {}", names.join(", "));
+            eprintln!();
+            print_help(&mut io::stderr());
+            exit(1);
+        });
+    });
+    config.single_threaded = consume("--single-threaded", &mut args);
+    config.age_heatmap = consume("--age-heatmap", &mut args);
+    config.keep_input_colors = consume("--keep-input-colors", &mut args);
+    config.legend = consume("--legend", &mut args);
+    config.whitespace_summary = consume("--whitespace-summary", &mut args);
+    config.dim_repeated_files = consume("--dim-repeated-files", &mut args);
+    config.no_header = consume("--no-header", &mut args);
+    // Title sequences are just noise in files and other programs' input
+    config.terminal_title = consume("--terminal-title", &mut args) && io::stdout().is_terminal();
+    config.plain_refined = consume("--plain-refined", &mut args);
+    config.describe = consume("--describe", &mut args);
+    let html_email = consume("--html-email", &mut args);
+    let list_themes = consume("--list-themes", &mut args);
+    config.max_line_length = consume_columns("--max-line-length", &mut args);
+    config.max_memory = consume_value("--max-memory", &mut args).map(|size| {
+        return memory_budget::parse_size(&size).unwrap_or_else(|| {
+            eprintln!("ERROR: --max-memory needs a size, like 512M or 2G");
+            eprintln!();
+            print_help(&mut io::stderr());
+            exit(1);
+        });
+    });
+    let input_fd = consume_value("--input-fd", &mut args).map(|fd| {
+        return fd.parse::<u32>().unwrap_or_else(|_| {
+            eprintln!("ERROR: --input-fd needs a file descriptor number, like 3");
+            eprintln!();
+            print_help(&mut io::stderr());
+            exit(1);
+        });
+    });
+    let mut input_path = input_fd.map(|fd| format!("/dev/fd/{fd}"));
+    config.collapse_removed = consume_value("--collapse-removed", &mut args).map(|lines| {
+        return lines.parse::<usize>().unwrap_or_else(|_| {
+            eprintln!("ERROR: --collapse-removed needs a number of lines");
+            eprintln!();
+            print_help(&mut io::stderr());
+            exit(1);
+        });
+    });
+    config.rulers = consume_rulers(&mut args);
+    config.output_file = consume_value("--output", &mut args).map(|path| {
+        if path.is_empty() {
+            eprintln!("ERROR: --output needs a file name");
+            eprintln!();
+            print_help(&mut io::stderr());
+            exit(1);
+        }
+        return path::PathBuf::from(path);
+    });
+    config.width = consume_columns("--width", &mut args).or_else(|| {
+        return env::var("COLUMNS")
+            .ok()
+            .and_then(|columns| columns.parse().ok())
+            .filter(|columns| *columns > 0);
+    });
+    // Without - and + prefixes, colors would be all that tells removed and
+    // added lines apart
+    config.compact = compact::is_compact(
+        consume("--compact", &mut args),
+        config.width,
+        config.compact_below,
+    ) && !config.describe;
+    let ignore_all_space = consume("--iw", &mut args);
+    let ignore_space_change =
+        consume("-b", &mut args) | consume("--ignore-space-change", &mut args);
+    if let Some(regex) = consume_value("--word-regex", &mut args) {
+        let regex = Regex::new(&regex).unwrap_or_else(|error| {
+            eprintln!("ERROR: --word-regex: {error}");
+            eprintln!();
+            print_help(&mut io::stderr());
+            exit(1);
+        });
+        config.word_regexes.push(WordRegex {
+            extension: None,
+            regex,
+        });
+    }
+    if consume("--plain-spaces", &mut args) {
+        config.spaces_between_highlights = SpacesBetweenHighlights::Plain;
+    }
+    if consume("--keep-noisy-highlights", &mut args) {
+        config.noisy_lines = NoisyLines::Keep;
+    }
+    if consume("--new-file-banner", &mut args) {
+        config.new_files = NewFiles::Banner;
+    }
+    if ignore_all_space {
+        config.whitespace = Whitespace::IgnoreAll;
+    } else if ignore_space_change {
+        config.whitespace = Whitespace::IgnoreChange;
+    }
+
+    if list_themes {
+        print_themes(&config);
+        return;
+    }
+
+    if compatible_diff_filter || (git_subcommand.is_none() && is_interactive_diff_filter()) {
+        config.enable_diff_filter_mode();
+    }
+
+    if let Some((subcommand, git_args)) = git_subcommand {
+        if args.len() != 1 {
+            eprintln!("ERROR: Unknown command line: {args:?}");
+            eprintln!();
+            print_help(&mut io::stderr());
+            exit(1);
+        }
+
+        if subcommand == "add" {
+            // "riff add src/", not git's own add
+            let highlight = |text: &str, with_header: bool| {
+                let config = Config {
+                    no_header: !with_header,
+                    ..config.clone()
+                };
+                return highlight_to_string(text, &config);
+            };
+            if let Err(message) = staging::add_interactively(&git_args, &highlight) {
+                eprintln!("ERROR: {message}");
+                exit(1);
+            }
+            return;
+        }
+
+        // "riff diff -U5 main"
+        exec_git_highlight(&subcommand, &git_args, no_pager, &config);
+        return;
+    }
+
+    if let Some(external_diff_args) = ExternalDiffArgs::parse(&args[1..]) {
+        // GIT_EXTERNAL_DIFF=riff git diff
+        exec_external_diff(&external_diff_args, &config);
+        return;
+    }
 
     if args.len() == 3 {
         // "riff file1 file2"
         exec_diff_highlight(
             args.get(1).unwrap(),
             args.get(2).unwrap(),
-            ignore_space_change,
             no_pager,
+            &config,
         );
         return;
     }
 
-    if ignore_space_change {
-        eprintln!(
-            "ERROR: -b is only supported when diffing two named paths (\"riff -b a.txt b.txt\")"
-        );
+    if args.len() == 2 && input_fd.is_none() {
+        // "riff /dev/fd/3" or "riff <(git diff)"
+        input_path = Some(args[1].clone());
+    } else if args.len() != 1 {
+        eprintln!("ERROR: Unknown command line: {args:?}");
         eprintln!();
         print_help(&mut io::stderr());
         exit(1);
     }
 
-    if args.len() != 1 {
-        eprintln!("ERROR: Unknown command line: {args:?}");
-        eprintln!();
-        print_help(&mut io::stderr());
-        exit(1);
+    if let Some(input_path) = input_path {
+        let (mut input, input_size) = open_input(&input_path);
+        if html_email {
+            print_html_email(&mut input, &config);
+            return;
+        }
+        highlight_stream(&mut input, input_size, no_pager, &config);
+        return;
     }
 
     if io::stdin().is_terminal() {
@@ -386,26 +1213,42 @@ fn main() {
         exit(1);
     }
 
-    highlight_stream(&mut io::stdin().lock(), no_pager);
+    if html_email {
+        print_html_email(&mut io::stdin().lock(), &config);
+        return;
+    }
+
+    highlight_stream(&mut io::stdin().lock(), stdin_size(), no_pager, &config);
 }
 
 #[cfg(test)]
 mod tests {
     use crate::constants::*;
+    use crate::palette::Palette;
 
     use super::*;
-    use std::{fs, path::PathBuf};
+    use std::{
+        fs,
+        path::{Path, PathBuf},
+    };
 
     #[cfg(test)]
     use pretty_assertions::assert_eq;
 
+    /// Highlight `input` with `config`, like riff would print it
+    fn highlight(input: &[u8], config: &Config) -> String {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        highlight_diff(&mut &input[..], file.reopen().unwrap(), config);
+        return fs::read_to_string(file.path()).unwrap();
+    }
+
     fn new(text: &str) -> String {
         return format!("{NEW}{text}{NORMAL}");
     }
 
     #[test]
     fn test_trailing_newline_context() {
-        let mut input = "+bepa\n apa\n\\ No newline at end of file\n".as_bytes();
+        let input = "+bepa\n apa\n\\ No newline at end of file\n".as_bytes();
 
         let expected = format!(
             "{}\n{}\n{}\\ No newline at end of file{}\n",
@@ -415,9 +1258,7 @@ mod tests {
             NORMAL
         );
 
-        let file = tempfile::NamedTempFile::new().unwrap();
-        highlight_diff(&mut input, file.reopen().unwrap());
-        let actual = fs::read_to_string(file.path()).unwrap();
+        let actual = highlight(input, &Config::default());
         // collect()ing into line vectors inside of this assert() statement
         // splits test failure output into lines, making it easier to digest.
         assert_eq!(
@@ -426,6 +1267,519 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_embedded() {
+        let input = concat!(
+            "\x1b[33mRunning tests...\x1b[0m\n",
+            "- not a diff\n",
+            "--- expected\n",
+            "+++ actual\n",
+            "@@ -1 +1 @@\n",
+            "-old\n",
+            "+new\n",
+            "+ not a diff either\n",
+        )
+        .as_bytes();
+
+        let expected = [
+            "\x1b[33mRunning tests...\x1b[0m".to_string(),
+            "- not a diff".to_string(),
+            format!("{BOLD}--- expected{NORMAL}"),
+            format!("{BOLD}+++ actual{NORMAL}"),
+            format!("{CYAN}@@ -1 +1 @@{NORMAL}"),
+            format!("{OLD}-old{NORMAL}"),
+            format!("{NEW}+new{NORMAL}"),
+            "+ not a diff either".to_string(),
+        ];
+
+        let config = Config {
+            embedded: true,
+            ..Config::default()
+        };
+        let actual = highlight(input, &config);
+        assert_eq!(actual.lines().collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn test_embedded_sanitizing() {
+        // OSC 52 writes to the clipboard, colors should still come through
+        let input = concat!(
+            "\x1b[33mbuild\x1b[0m \x1b]52;c;ZXZpbA==\x07\n",
+            "- \x1b]52;c;ZXZpbA==\x07\n",
+            "--- expected\n",
+        )
+        .as_bytes();
+
+        let config = Config {
+            embedded: true,
+            ..Config::default()
+        };
+        let actual = highlight(input, &config);
+        assert_eq!(
+            actual.lines().collect::<Vec<_>>(),
+            [
+                "\x1b[33mbuild\x1b[0m ^[]52;c;ZXZpbA==^G",
+                "- ^[]52;c;ZXZpbA==^G",
+                "--- expected",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_truncation_warning() {
+        let input = concat!(
+            "--- a/x.txt\n",
+            "+++ b/x.txt\n",
+            "@@ -1,3 +1,3 @@\n",
+            " context\n",
+            "-old\n",
+        )
+        .as_bytes();
+
+        let actual = highlight(input, &Config::default());
+        assert_eq!(
+            actual.lines().last().unwrap(),
+            format!("{BOLD}{OLD}⚠ diff appears truncated, the last hunk is incomplete{NORMAL}")
+        );
+
+        // Complete hunks get no warning
+        let input = concat!(
+            "--- a/x.txt\n",
+            "+++ b/x.txt\n",
+            "@@ -1,2 +1,2 @@\n",
+            " context\n",
+            "-old\n",
+            "+new\n",
+        )
+        .as_bytes();
+
+        let actual = highlight(input, &Config::default());
+        assert!(!actual.contains("truncated"), "{}", actual);
+    }
+
+    #[test]
+    fn test_custom_palette() {
+        let input = concat!(
+            "--- a/x.txt\n",
+            "+++ b/x.txt\n",
+            "@@ -1 +1 @@\n",
+            "-old\n",
+            "+new\n",
+        )
+        .as_bytes();
+
+        let config = Config {
+            palette: Palette {
+                old: "<old>".to_string(),
+                new: "<new>".to_string(),
+                bold: "<bold>".to_string(),
+                hunk_header: "<hunk>".to_string(),
+                normal: "</>".to_string(),
+                ..Palette::default()
+            },
+            ..Config::default()
+        };
+        let actual = highlight(input, &config);
+        assert_eq!(
+            actual.lines().collect::<Vec<_>>(),
+            [
+                "<bold>--- a/x.txt</>",
+                "<bold>+++ b/x.txt</>",
+                "<hunk>@@ -1 +1 @@</>",
+                "<old>-old</>",
+                "<new>+new</>",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_only_changes() {
+        let input = concat!(
+            "--- a/x.txt\n",
+            "+++ b/x.txt\n",
+            "@@ -1,3 +1,3 @@\n",
+            " context\n",
+            "-old\n",
+            "+new\n",
+            "\n",
+            " more context\n",
+            "\\ No newline at end of file\n",
+        )
+        .as_bytes();
+
+        let expected = [
+            format!("{BOLD}--- a/x.txt{NORMAL}"),
+            format!("{BOLD}+++ b/x.txt{NORMAL}"),
+            format!("{CYAN}@@ -1,3 +1,3 @@{NORMAL}"),
+            format!("{OLD}-old{NORMAL}"),
+            format!("{NEW}+new{NORMAL}"),
+        ];
+
+        let config = Config {
+            only_changes: true,
+            ..Config::default()
+        };
+        let actual = highlight(input, &config);
+        assert_eq!(actual.lines().collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn test_quiet() {
+        let input = concat!(
+            "diff --git a/x.txt b/x.txt\n",
+            "old mode 100644\n",
+            "new mode 100755\n",
+            "index 1234567..89abcde\n",
+            "--- a/x.txt\n",
+            "+++ b/x.txt\n",
+            "@@ -1 +1 @@\n",
+            "-old\n",
+            "+new\n",
+            "diff --git a/x.png b/y.png\n",
+            "similarity index 90%\n",
+            "rename from x.png\n",
+            "rename to y.png\n",
+            "Binary files a/x.png and b/y.png differ\n",
+        )
+        .as_bytes();
+
+        let expected = [
+            format!("{FAINT}diff --git a/x.txt b/x.txt{NORMAL}"),
+            format!("{BOLD}--- a/x.txt{NORMAL}"),
+            format!("{BOLD}+++ b/x.txt{NORMAL}"),
+            format!("{CYAN}@@ -1 +1 @@{NORMAL}"),
+            format!("{OLD}-old{NORMAL}"),
+            format!("{NEW}+new{NORMAL}"),
+            format!("{FAINT}diff --git a/x.png b/y.png{NORMAL}"),
+            format!("{FAINT}rename from x.png{NORMAL}"),
+            format!("{BOLD}rename to y.png{NORMAL}"),
+        ];
+
+        let config = Config {
+            quiet: true,
+            ..Config::default()
+        };
+        let actual = highlight(input, &config);
+        assert_eq!(actual.lines().collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn test_no_header() {
+        let input = concat!(
+            "diff --git a/x.txt b/x.txt\n",
+            "index 1234567..89abcde 100644\n",
+            "--- a/x.txt\n",
+            "+++ b/x.txt\n",
+            "@@ -1 +1 @@\n",
+            "-old\n",
+            "+new\n",
+        )
+        .as_bytes();
+
+        let expected = [
+            format!("{CYAN}@@ -1 +1 @@{NORMAL}"),
+            format!("{OLD}-old{NORMAL}"),
+            format!("{NEW}+new{NORMAL}"),
+        ];
+
+        let config = Config {
+            no_header: true,
+            ..Config::default()
+        };
+        let actual = highlight(input, &config);
+        assert_eq!(actual.lines().collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn test_label_headers() {
+        // From `diff --label "old  version\tof x" --label ...`, no timestamps
+        let input = concat!(
+            "--- old  version\tof x\n",
+            "+++ new  version\tof x\n",
+            "@@ -1 +1 @@\n",
+            "-old\n",
+            "+new\n",
+        )
+        .as_bytes();
+
+        let actual = highlight(input, &Config::default());
+        assert_eq!(
+            actual.lines().take(2).collect::<Vec<_>>(),
+            [
+                format!("{BOLD}--- {INVERSE_VIDEO}{OLD}old{NOT_INVERSE_VIDEO}{DEFAULT_COLOR}  version\tof x{NORMAL}"),
+                format!("{BOLD}+++ {INVERSE_VIDEO}{NEW}new{NOT_INVERSE_VIDEO}{DEFAULT_COLOR}  version\tof x{NORMAL}"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_collapse_removed() {
+        let input = concat!(
+            "@@ -1,6 +1 @@\n",
+            "-1\n",
+            "-2\n",
+            "-3\n",
+            "-4\n",
+            "-5\n",
+            "-6\n",
+            "+new\n",
+        )
+        .as_bytes();
+
+        let expected = [
+            format!("{CYAN}@@ -1,6 +1 @@{NORMAL}"),
+            format!("{OLD}-1{NORMAL}"),
+            format!("{OLD}-2{NORMAL}"),
+            format!("{OLD}{FAINT}… 2 more removed lines …{NORMAL}"),
+            format!("{OLD}-5{NORMAL}"),
+            format!("{OLD}-6{NORMAL}"),
+            format!("{NEW}+new{NORMAL}"),
+        ];
+
+        let config = Config {
+            collapse_removed: Some(2),
+            ..Config::default()
+        };
+        let actual = highlight(input, &config);
+        assert_eq!(actual.lines().collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn test_context_whitespace_warnings() {
+        let input = "@@ -1,2 +1,2 @@\n context \n-old\n+new \n".as_bytes();
+
+        let config = Config {
+            whitespace_errors: config::WhitespaceErrors::AddedAndContext,
+            ..Config::default()
+        };
+        let actual = highlight(input, &config);
+        let lines: Vec<&str> = actual.lines().collect();
+        assert_eq!(lines[1], format!(" context{INVERSE_VIDEO}{FAINT} {NORMAL}"));
+    }
+
+    #[test]
+    fn test_describe() {
+        let input = concat!(
+            "diff --git a/x.txt b/x.txt\n",
+            "--- a/x.txt\n",
+            "+++ b/x.txt\n",
+            "@@ -1,2 +1,2 @@\n",
+            " context\n",
+            "-my old name\n",
+            "+my new name\n",
+            "@@ -10,0 +11 @@\n",
+            "+added\n",
+        )
+        .as_bytes();
+
+        // Colors are configurable, so the descriptions must not depend on them
+        let config = Config {
+            describe: true,
+            palette: Palette {
+                old: "\x1b[35m".to_string(),
+                new: "\x1b[36m".to_string(),
+                ..Palette::default()
+            },
+            ..Config::default()
+        };
+        let actual = highlight(input, &config);
+        assert_eq!(
+            actual.lines().collect::<Vec<_>>(),
+            [
+                "diff --git a/x.txt b/x.txt",
+                "--- a/x.txt",
+                "+++ b/x.txt",
+                "@@ -1,2 +1,2 @@",
+                " context",
+                "removed: my old name; changed part: old",
+                "added: my new name; changed part: new",
+                "@@ -10,0 +11 @@",
+                "added: added",
+            ]
+        );
+    }
+
+    /// Run `input` through riff the way `git add -p` does with riff as its
+    /// `interactive.diffFilter`
+    fn diff_filter(input: &str, config: Config) -> String {
+        let mut config = config;
+        config.enable_diff_filter_mode();
+
+        return highlight(input.as_bytes(), &config);
+    }
+
+    #[test]
+    fn test_diff_filter_keeps_lines() {
+        let input = concat!(
+            "diff --git a/x.rs b/x.rs\n",
+            "index 0123456..789abcd 100644\n",
+            "--- a/x.rs\n",
+            "+++ b/x.rs\n",
+            "@@ -1,4 +1,4 @@ fn main() {\n",
+            " context\n",
+            "-old\n",
+            "+new\n",
+            " context\n",
+            "@@ -10 +10 @@\n",
+            "-last\n",
+            "+last\n",
+            "\\ No newline at end of file\n",
+            "# Manual hunk edit mode -- see bottom for a quick guide.\n",
+            "# Lines starting with # will be removed.\n",
+            "diff --git a/image.png b/image.png\n",
+            "index 0123456..789abcd 100644\n",
+            "Binary files a/image.png and b/image.png differ\n",
+            "diff --cc z.txt\n",
+            "--- a/z.txt\n",
+            "+++ b/z.txt\n",
+            "@@@ -1,1 -1,1 +1,2 @@@\n",
+            "  context\n",
+            "++both\n",
+            "diff --git a/y.txt b/y.txt\n",
+            "--- a/y.txt\n",
+            "+++ b/y.txt\n",
+            "@@ -1,3 +1,3 @@\n",
+            "-truncated\n",
+        );
+
+        // Options that add or remove lines must not get in the way
+        let config = Config {
+            only_changes: true,
+            refine_renames: true,
+            ..Config::default()
+        };
+        let mut output = diff_filter(input, config).into_bytes();
+        crate::ansi::remove_ansi_escape_codes(&mut output);
+        let output = String::from_utf8(output).unwrap();
+
+        // Git maps its hunks onto our output by counting lines
+        assert_eq!(output.lines().count(), input.lines().count());
+
+        // Comments and hunk headers are parsed by git, so they must stay the
+        // same. Other lines may get decorations like a ⏎ for missing newlines.
+        for (output_line, input_line) in output.lines().zip(input.lines()) {
+            if input_line.starts_with('#') || input_line.starts_with("@@") {
+                assert_eq!(output_line, input_line);
+            }
+        }
+    }
+
+    #[test]
+    fn test_diff_filter_comments() {
+        let output = diff_filter(
+            "# Lines starting with # will be removed.\n",
+            Config::default(),
+        );
+        assert_eq!(
+            output,
+            format!("{FAINT}# Lines starting with # will be removed.{NORMAL}\n")
+        );
+    }
+
+    #[test]
+    fn test_keep_input_colors() {
+        let input = concat!(
+            "\x1b[33m*\x1b[m \x1b[33mcommit 62da46c\x1b[m\n",
+            "\x1b[31m|\x1b[m Author: Adam\n",
+            "--- a/x.txt\n",
+            "+++ b/x.txt\n",
+            "@@ -1 +1 @@\n",
+            "\x1b[31m-old\x1b[m\n",
+            "\x1b[32m+new\x1b[m\n",
+        )
+        .as_bytes();
+
+        let expected = [
+            format!("\x1b[33m*\x1b[m \x1b[33mcommit 62da46c\x1b[m{NORMAL}"),
+            format!("\x1b[31m|\x1b[m Author: Adam{NORMAL}"),
+            format!("{BOLD}--- a/x.txt{NORMAL}"),
+            format!("{BOLD}+++ b/x.txt{NORMAL}"),
+            format!("{CYAN}@@ -1 +1 @@{NORMAL}"),
+            format!("{OLD}-old{NORMAL}"),
+            format!("{NEW}+new{NORMAL}"),
+        ];
+
+        let config = Config {
+            keep_input_colors: true,
+            ..Config::default()
+        };
+        let actual = highlight(input, &config);
+        assert_eq!(actual.lines().collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn test_consume_git_subcommand() {
+        fn argv(args: &[&str]) -> Vec<String> {
+            return args.iter().map(|arg| arg.to_string()).collect();
+        }
+
+        let mut args = argv(&["riff", "--no-pager", "diff", "-U5", "-b", "main"]);
+        assert_eq!(
+            consume_git_subcommand(&mut args),
+            Some(("diff".to_string(), argv(&["-U5", "-b", "main"])))
+        );
+        assert_eq!(args, argv(&["riff", "--no-pager"]));
+
+        let mut args = argv(&["riff", "--max-line-length", "80", "diff"]);
+        assert_eq!(
+            consume_git_subcommand(&mut args),
+            Some(("diff".to_string(), vec![]))
+        );
+        assert_eq!(args, argv(&["riff", "--max-line-length", "80"]));
+
+        let mut args = argv(&["riff", "--width", "100", "diff", "--all"]);
+        assert_eq!(
+            consume_git_subcommand(&mut args),
+            Some(("diff".to_string(), argv(&["--all"])))
+        );
+        assert_eq!(args, argv(&["riff", "--width", "100"]));
+
+        // Files named like subcommands are still files
+        let mut args = argv(&["riff", "file1", "diff"]);
+        assert_eq!(consume_git_subcommand(&mut args), None);
+        assert_eq!(args, argv(&["riff", "file1", "diff"]));
+
+        let mut args = argv(&["riff", "--no-pager"]);
+        assert_eq!(consume_git_subcommand(&mut args), None);
+    }
+
+    #[test]
+    fn test_value_options_are_known_to_take_values() {
+        // Options consumed with a value must be skipped with their value when
+        // looking for git subcommands
+        let source = include_str!("main.rs");
+        for consumer in ["consume_value(\"", "consume_columns(\""].iter() {
+            for (start, _) in source.match_indices(consumer) {
+                let option = &source[start + consumer.len()..];
+                let option = &option[..option.find('"').unwrap()];
+                if !option.starts_with("--") {
+                    // The definitions of the consumers themselves
+                    continue;
+                }
+                assert!(
+                    completions::takes_value(option),
+                    "{} takes a value, but completions::OPTIONS doesn't say so",
+                    option
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_consume_compatibility_flags() {
+        fn argv(args: &[&str]) -> Vec<String> {
+            return args.iter().map(|arg| arg.to_string()).collect();
+        }
+
+        let mut args = argv(&["riff", "--dark", "--line-numbers", "--paging=never", "x"]);
+        assert_eq!(consume_compatibility_flags(&mut args), (true, false));
+        assert_eq!(args, argv(&["riff", "x"]));
+
+        let mut args = argv(&["riff", "--color-only", "--paging", "always"]);
+        assert_eq!(consume_compatibility_flags(&mut args), (false, true));
+        assert_eq!(args, argv(&["riff"]));
+    }
+
     #[test]
     fn test_testdata_examples() {
         // Example value: `/Users/johan/src/riff/target/debug/deps/riff-7a8916c06b0d3d6c`
@@ -452,7 +1806,12 @@ mod tests {
         }
         assert!(testdata_path.is_dir());
 
-        // Find all .diff example files
+        // Find all .diff example files, plus the .colored-diff ones captured
+        // from colored output like `git diff --color`
+        let is_example = |path: &Path| {
+            let extension = path.extension().unwrap();
+            return extension == "diff" || extension == "colored-diff";
+        };
         let mut diff_example_files: Vec<PathBuf> = vec![];
         for diff in fs::read_dir(&testdata_path).unwrap() {
             let diff = diff.unwrap();
@@ -461,7 +1820,7 @@ mod tests {
                 continue;
             }
 
-            if diff.extension().unwrap() != "diff" {
+            if !is_example(&diff) {
                 continue;
             }
 
@@ -479,19 +1838,24 @@ mod tests {
                 continue;
             }
 
-            if diff.extension().unwrap() != "diff" {
+            if !is_example(diff) {
                 continue;
             }
 
             println!("Evaluating example file <{}>...", diff.to_str().unwrap());
 
-            // Run highlighting on the file into a memory buffer
-            let file = tempfile::NamedTempFile::new().unwrap();
-            highlight_diff(&mut fs::File::open(diff).unwrap(), file.reopen().unwrap());
-            let actual_result = fs::read_to_string(file.path()).unwrap();
+            // Examples with a .toml file next to them get highlighted with
+            // those settings
+            let basename = diff.file_stem().unwrap().to_str().unwrap();
+            let config_path = testdata_path.join(format!("{basename}.toml"));
+            let config = match fs::read_to_string(config_path) {
+                Ok(toml_text) => Config::from_toml(&toml_text).unwrap(),
+                Err(_) => Config::default(),
+            };
+
+            let actual_result = highlight(&fs::read(diff).unwrap(), &config);
 
             // Load the corresponding .riff-output file into a string
-            let basename = diff.file_stem().unwrap().to_str().unwrap();
             let expected_path = format!(
                 "{}/{}.riff-output",
                 testdata_path.to_str().unwrap(),
@@ -515,9 +1879,9 @@ mod tests {
             }
         }
 
-        if failing_example.is_some() {
+        if let Some(failing_example) = failing_example {
             println!();
-            println!("Example: {}", failing_example.unwrap());
+            println!("Example: {failing_example}");
             assert_eq!(failing_example_actual, failing_example_expected);
         }
     }
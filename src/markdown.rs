@@ -0,0 +1,144 @@
+use crate::token_collector::{self, Style, StyledToken};
+use crate::tokenizer;
+
+/// Characters that come in runs in Markdown markup, like `**` or ```` ``` ````
+const MARKUP_CHARS: &[char] = &['*', '_', '`', '~'];
+
+/// Tokenize Markdown so that markup is separate from the text it marks up.
+///
+/// Emphasis markers like `**` and backtick runs become single tokens, as does
+/// the `](` between link texts and their targets. Underscores at the edges of
+/// words are markup rather than parts of the words, so `_word_` becomes `_`,
+/// `word` and `_`.
+pub fn tokenize(input: &str) -> Vec<&str> {
+    // Byte ranges into input, the default tokenizer doesn't skip anything
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    let mut offset = 0;
+    for token in tokenizer::tokenize(input) {
+        let start = offset;
+        offset += token.len();
+
+        let core = token.trim_matches('_');
+        if core.is_empty() || core.len() == token.len() {
+            ranges.push((start, offset));
+            continue;
+        }
+
+        let core_start = start + token.len() - token.trim_start_matches('_').len();
+        let core_end = core_start + core.len();
+        for range in [
+            (start, core_start),
+            (core_start, core_end),
+            (core_end, offset),
+        ]
+        .iter()
+        {
+            if range.0 != range.1 {
+                ranges.push(*range);
+            }
+        }
+    }
+
+    let mut result: Vec<&str> = Vec::with_capacity(ranges.len());
+    let mut previous: Option<(usize, usize)> = None;
+    for range in ranges {
+        if let Some(previous_range) = previous {
+            let previous_token = &input[previous_range.0..previous_range.1];
+            let token = &input[range.0..range.1];
+            if is_mergeable(previous_token, token) {
+                previous = Some((previous_range.0, range.1));
+                continue;
+            }
+            result.push(previous_token);
+        }
+        previous = Some(range);
+    }
+    if let Some(previous_range) = previous {
+        result.push(&input[previous_range.0..previous_range.1]);
+    }
+
+    return result;
+}
+
+/// Whether `token` should be appended to `previous` to make one markup token
+fn is_mergeable(previous: &str, token: &str) -> bool {
+    if previous == "]" && token == "(" {
+        return true;
+    }
+
+    let mut chars = token.chars();
+    let (first, rest) = (chars.next(), chars.next());
+    let markup_char = match (first, rest) {
+        (Some(first), None) if MARKUP_CHARS.contains(&first) => first,
+        _ => return false,
+    };
+    return previous.chars().all(|c| c == markup_char);
+}
+
+/// Like [`token_collector::highlight_trailing_whitespace`], but exactly two
+/// trailing spaces are a hard line break rather than an error
+pub fn highlight_trailing_whitespace(tokens: &mut [StyledToken]) {
+    for row in tokens.split_mut(|token| token.token() == "\n") {
+        let trailer_length = row
+            .iter()
+            .rev()
+            .take_while(|token| token.is_whitespace())
+            .count();
+        let is_hard_line_break = trailer_length == 2
+            && row[row.len() - 2..]
+                .iter()
+                .all(|token| token.token() == " ");
+        if is_hard_line_break {
+            continue;
+        }
+
+        token_collector::highlight_trailing_whitespace(row, Style::Error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_tokenize_emphasis() {
+        assert_eq!(
+            tokenize("**bold** _it_ snake_case"),
+            ["**", "bold", "**", " ", "_", "it", "_", " ", "snake_case"]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_code_and_links() {
+        assert_eq!(
+            tokenize("```rust `x` [a](b)"),
+            ["```", "rust", " ", "`", "x", "`", " ", "[", "a", "](", "b", ")"]
+        );
+    }
+
+    #[test]
+    fn test_hard_line_break() {
+        fn styled(line: &str) -> Vec<StyledToken> {
+            return tokenize(line)
+                .into_iter()
+                .map(|token| StyledToken::new(token.to_string(), Style::Plain))
+                .collect();
+        }
+
+        // Two spaces are a line break
+        let mut tokens = styled("break  \n");
+        highlight_trailing_whitespace(&mut tokens);
+        assert_eq!(tokens, styled("break  \n"));
+
+        // One or three spaces are errors
+        for line in ["x \n", "x   "].iter() {
+            let mut tokens = styled(line);
+            highlight_trailing_whitespace(&mut tokens);
+            let error = StyledToken::new(" ".to_string(), Style::Error);
+            assert!(tokens.contains(&error), "{}", line);
+        }
+    }
+}
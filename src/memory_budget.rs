@@ -0,0 +1,125 @@
+use std::sync::{Condvar, Mutex};
+
+/// Rough bytes of memory per byte of diff text queued for output: the input
+/// lines, the refined output with its escape codes, and bookkeeping
+const QUEUED_BYTES_PER_TEXT_BYTE: usize = 4;
+
+/// Parse a `--max-memory` size like `512M`, `2G`, `100k` or `1000000`
+#[must_use]
+pub fn parse_size(size: &str) -> Option<usize> {
+    let size = size.trim();
+    let (number, multiplier) = match size.char_indices().last()? {
+        (index, 'k' | 'K') => (&size[..index], 1 << 10),
+        (index, 'm' | 'M') => (&size[..index], 1 << 20),
+        (index, 'g' | 'G') => (&size[..index], 1 << 30),
+        _ => (size, 1),
+    };
+    let bytes = number.parse::<usize>().ok()?.checked_mul(multiplier)?;
+    if bytes == 0 {
+        return None;
+    }
+    return Some(bytes);
+}
+
+/// With `--max-memory`, keeps track of how much memory the output queue uses.
+///
+/// The line collector reserves memory for each queued section, and the output
+/// thread releases it after printing. When the budget runs out, the line
+/// collector waits for the output to catch up, and stops refining until it
+/// has.
+pub struct MemoryBudget {
+    limit: usize,
+    used: Mutex<usize>,
+    released: Condvar,
+}
+
+impl MemoryBudget {
+    #[must_use]
+    pub fn new(limit: usize) -> MemoryBudget {
+        return MemoryBudget {
+            limit,
+            used: Mutex::new(0),
+            released: Condvar::new(),
+        };
+    }
+
+    /// How much memory queueing this much text would take
+    #[must_use]
+    pub fn cost(text_length: usize) -> usize {
+        return text_length.saturating_mul(QUEUED_BYTES_PER_TEXT_BYTE);
+    }
+
+    /// True if `bytes` more would fit right now
+    #[must_use]
+    pub fn has_room_for(&self, bytes: usize) -> bool {
+        let used = self.used.lock().unwrap();
+        return used.saturating_add(bytes) <= self.limit;
+    }
+
+    /// True if refining old and new texts of these lengths could use more
+    /// than the whole budget. Refining is quadratic in the text lengths.
+    #[must_use]
+    pub fn too_large_to_refine(&self, old_length: usize, new_length: usize) -> bool {
+        return old_length.saturating_mul(new_length) > self.limit;
+    }
+
+    /// Reserve `bytes`, waiting for earlier reservations to be released if
+    /// needed. Something larger than the whole budget waits until nothing
+    /// else is reserved.
+    pub fn reserve(&self, bytes: usize) {
+        let mut used = self.used.lock().unwrap();
+        while *used > 0 && used.saturating_add(bytes) > self.limit {
+            used = self.released.wait(used).unwrap();
+        }
+        *used += bytes;
+    }
+
+    pub fn release(&self, bytes: usize) {
+        let mut used = self.used.lock().unwrap();
+        *used = used.saturating_sub(bytes);
+        self.released.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("1000"), Some(1000));
+        assert_eq!(parse_size("100k"), Some(100 * 1024));
+        assert_eq!(parse_size("512M"), Some(512 * 1024 * 1024));
+        assert_eq!(parse_size("2G"), Some(2 * 1024 * 1024 * 1024));
+        assert_eq!(parse_size("0"), None);
+        assert_eq!(parse_size("M"), None);
+        assert_eq!(parse_size("lots"), None);
+    }
+
+    #[test]
+    fn test_reserve_waits_for_release() {
+        let budget = Arc::new(MemoryBudget::new(100));
+        budget.reserve(80);
+        assert!(!budget.has_room_for(30));
+
+        let releaser = budget.clone();
+        let release_thread = thread::spawn(move || releaser.release(80));
+
+        // Blocks until the release
+        budget.reserve(30);
+        release_thread.join().unwrap();
+        assert!(budget.has_room_for(70));
+        assert!(!budget.has_room_for(71));
+
+        // Larger than the whole budget, but nothing else is reserved
+        budget.release(30);
+        budget.reserve(1000);
+        budget.release(1000);
+        assert!(budget.has_room_for(100));
+    }
+}
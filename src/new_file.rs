@@ -0,0 +1,45 @@
+use crate::config::Config;
+use crate::file_timestamp;
+
+/// `new file: path (N lines)`, shown instead of the `---`, `+++` and `@@` lines
+/// of an added file. `line_count` is `None` if the file came without a hunk
+/// header.
+#[must_use]
+pub fn banner(new_name: &str, line_count: Option<usize>, config: &Config) -> String {
+    let name = file_timestamp::strip(new_name);
+    let name = name.strip_prefix("b/").unwrap_or(name);
+    let count = match line_count {
+        Some(1) => " (1 line)".to_string(),
+        Some(count) => format!(" ({count} lines)"),
+        None => String::new(),
+    };
+
+    let palette = &config.palette;
+    return format!("{}new file: {name}{}{count}", palette.bold, palette.normal);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_banner() {
+        let config = Config::default();
+        let palette = &config.palette;
+        let bold = |text: &str| {
+            return format!("{}{text}{}", palette.bold, palette.normal);
+        };
+        assert_eq!(
+            banner("b/src/x.rs", Some(12), &config),
+            format!("{} (12 lines)", bold("new file: src/x.rs"))
+        );
+        assert_eq!(
+            banner("x.txt\t2021-01-01 12:00:00", Some(1), &config),
+            format!("{} (1 line)", bold("new file: x.txt"))
+        );
+        assert_eq!(banner("b/empty", None, &config), bold("new file: empty"));
+    }
+}
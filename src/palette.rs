@@ -0,0 +1,127 @@
+use crate::ansi::{Color, Weight};
+use crate::constants::*;
+
+/// The ANSI escape sequences we color our output with.
+///
+/// This is passed through the render pipeline as part of the
+/// [`Config`](crate::config::Config), so that tests and embedders can inject
+/// their own palettes without any process global state.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Palette {
+    /// Removed lines
+    pub old: String,
+
+    /// Added lines
+    pub new: String,
+
+    /// Removed and added lines without a counterpart on the other side, with
+    /// `unpaired-lines = "shaded"`
+    pub old_unpaired: String,
+    pub new_unpaired: String,
+
+    pub default_color: String,
+
+    pub inverse_video: String,
+    pub not_inverse_video: String,
+
+    pub bold: String,
+    pub faint: String,
+
+    /// Neither bold nor faint
+    pub normal_intensity: String,
+
+    /// `\ No newline at end of file`
+    pub no_eof_newline: String,
+
+    /// `@@ -1,2 +1,2 @@`
+    pub hunk_header: String,
+
+    /// `commit 0123abc` lines
+    pub commit: String,
+
+    /// Background for `commit` lines following a diff, so that they stand out
+    /// in `git log -p` output
+    pub commit_background: String,
+
+    /// Branch names in `commit` lines
+    pub current_branch: String,
+    pub other_branch: String,
+    pub head: String,
+
+    /// Blame columns by age, from the most recent changes to the oldest
+    pub blame_heat: Vec<String>,
+
+    /// `--age-heatmap` gutter backgrounds by age, from the most recent changes
+    /// to the oldest
+    pub age_heat_background: Vec<String>,
+    pub default_background: String,
+
+    /// `--ruler` columns in added lines
+    pub ruler_background: String,
+
+    /// `similarity index` bars, from the most similar files to the least
+    /// similar
+    pub similarity: Vec<String>,
+
+    /// Likely secrets in added lines, with `secrets = "flag"`
+    pub secret_background: String,
+
+    /// Resets everything
+    pub normal: String,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        return Palette {
+            old: OLD.to_string(),
+            new: NEW.to_string(),
+            old_unpaired: OLD_UNPAIRED.to_string(),
+            new_unpaired: NEW_UNPAIRED.to_string(),
+            default_color: DEFAULT_COLOR.to_string(),
+            inverse_video: INVERSE_VIDEO.to_string(),
+            not_inverse_video: NOT_INVERSE_VIDEO.to_string(),
+            bold: BOLD.to_string(),
+            faint: FAINT.to_string(),
+            normal_intensity: NORMAL_INTENSITY.to_string(),
+            no_eof_newline: NO_EOF_NEWLINE_COLOR.to_string(),
+            hunk_header: CYAN.to_string(),
+            commit: YELLOW.to_string(),
+            commit_background: BLUE_TO_END_OF_LINE.to_string(),
+            current_branch: GREEN.to_string(),
+            other_branch: RED.to_string(),
+            head: CYAN.to_string(),
+            blame_heat: BLAME_HEAT.iter().map(|color| color.to_string()).collect(),
+            age_heat_background: AGE_HEAT_BACKGROUND
+                .iter()
+                .map(|color| color.to_string())
+                .collect(),
+            default_background: DEFAULT_BACKGROUND.to_string(),
+            ruler_background: RULER_BACKGROUND.to_string(),
+            similarity: vec![GREEN.to_string(), YELLOW.to_string(), RED.to_string()],
+            secret_background: SECRET_BACKGROUND.to_string(),
+            normal: NORMAL.to_string(),
+        };
+    }
+}
+
+impl Palette {
+    #[must_use]
+    pub fn color(&self, color: Color) -> &str {
+        return match color {
+            Color::Default => &self.default_color,
+            Color::Red => &self.old,
+            Color::Green => &self.new,
+            Color::DarkRed => &self.old_unpaired,
+            Color::DarkGreen => &self.new_unpaired,
+        };
+    }
+
+    #[must_use]
+    pub fn weight(&self, weight: Weight) -> &str {
+        return match weight {
+            Weight::Normal => &self.normal_intensity,
+            Weight::Bold => &self.bold,
+            Weight::Faint => &self.faint,
+        };
+    }
+}
@@ -0,0 +1,146 @@
+use crate::display_width::display_width;
+use crate::file_timestamp;
+
+/// Header lines start with this or with `--- `. That decides where TAB stops
+/// in the names end up.
+const HEADER_PREFIX: &str = "+++ ";
+
+/// Splits `a/src/main.rs\t2021-01-01` into `a/`, `src/main.rs` and `\t2021-01-01`
+fn split_name(name: &str) -> (&str, &str, &str) {
+    let (path, timestamp) = match file_timestamp::start(name) {
+        Some(timestamp_start) => name.split_at(timestamp_start),
+        None => (name, ""),
+    };
+    for git_prefix in ["a/", "b/"].iter() {
+        if let Some(rest) = path.strip_prefix(git_prefix) {
+            return (git_prefix, rest, timestamp);
+        }
+    }
+    return ("", path, timestamp);
+}
+
+/// Path components of a `---` or `+++` file name, `None` for `/dev/null`
+fn components(name: &str) -> Option<Vec<&str>> {
+    if name == "/dev/null" {
+        return None;
+    }
+    let (_, path, _) = split_name(name);
+    return Some(path.split('/').collect());
+}
+
+/// How many leading directories `old` and `new` have in common. `None` means
+/// `/dev/null`, which has all directories in common with the other path.
+fn common_directories(old: Option<&[&str]>, new: Option<&[&str]>) -> usize {
+    let (old, new) = match (old, new) {
+        (Some(old), Some(new)) => (old, new),
+        (Some(only), None) | (None, Some(only)) => (only, only),
+        (None, None) => return 0,
+    };
+
+    // The last component is the file name, not a directory
+    let directories = old.len().min(new.len()).saturating_sub(1);
+    return old
+        .iter()
+        .zip(new.iter())
+        .take(directories)
+        .take_while(|(old, new)| old == new)
+        .count();
+}
+
+/// Replace the first `count` directories of a file name with an ellipsis
+fn collapse(name: &str, count: usize) -> String {
+    if count == 0 || name == "/dev/null" {
+        return name.to_string();
+    }
+    let (git_prefix, path, timestamp) = split_name(name);
+    let components: Vec<&str> = path.split('/').collect();
+    return format!("{git_prefix}…/{}{timestamp}", components[count..].join("/"));
+}
+
+/// Shorten the `---` and `+++` file names to fit in `width` columns, by
+/// collapsing the leading directories they have in common into an ellipsis:
+/// `a/…/deep/dir/file.rs`.
+///
+/// The parts of the paths that differ are always kept, so renames and moves
+/// stay visible. Names that already fit are returned unchanged.
+#[must_use]
+pub fn shorten_paths(old_name: &str, new_name: &str, width: usize) -> (String, String) {
+    let fits = |name: &str| display_width(&format!("{HEADER_PREFIX}{name}")) <= width;
+    if fits(old_name) && fits(new_name) {
+        return (old_name.to_string(), new_name.to_string());
+    }
+
+    let old_components = components(old_name);
+    let new_components = components(new_name);
+    let collapsible = common_directories(old_components.as_deref(), new_components.as_deref());
+
+    // Collapse the same directories in both names so they still line up. If
+    // even collapsing all common directories isn't enough, collapse them all
+    // anyway.
+    for count in 1..collapsible {
+        let (old, new) = (collapse(old_name, count), collapse(new_name, count));
+        if fits(&old) && fits(&new) {
+            return (old, new);
+        }
+    }
+    return (
+        collapse(old_name, collapsible),
+        collapse(new_name, collapsible),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_shorten_paths() {
+        // Fits, nothing to do
+        assert_eq!(
+            shorten_paths("a/src/x.rs", "b/src/x.rs", 80),
+            ("a/src/x.rs".to_string(), "b/src/x.rs".to_string())
+        );
+
+        // Collapse just enough of the common directories to fit
+        assert_eq!(
+            shorten_paths(
+                "a/very/deep/directory/tree/file.rs",
+                "b/very/deep/directory/tree/file.rs",
+                30
+            ),
+            (
+                "a/…/directory/tree/file.rs".to_string(),
+                "b/…/directory/tree/file.rs".to_string()
+            )
+        );
+
+        // Keep the differing part of a move visible, even if it doesn't fit
+        assert_eq!(
+            shorten_paths(
+                "a/very/deep/old-directory/file.rs",
+                "b/very/deep/new-directory/file.rs",
+                20
+            ),
+            (
+                "a/…/old-directory/file.rs".to_string(),
+                "b/…/new-directory/file.rs".to_string()
+            )
+        );
+
+        // Timestamps are kept, and /dev/null is left alone
+        assert_eq!(
+            shorten_paths(
+                "/dev/null",
+                "very/deep/directory/file.rs\t2021-01-01 12:00:00",
+                45
+            ),
+            (
+                "/dev/null".to_string(),
+                "…/directory/file.rs\t2021-01-01 12:00:00".to_string()
+            )
+        );
+    }
+}
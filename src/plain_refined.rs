@@ -0,0 +1,171 @@
+use crate::ansi::{parts, sgr_parameters};
+use std::io::{self, Write};
+
+/// Skip the rest of a `38;5;N` or `38;2;R;G;B` extended color
+fn skip_extended_color<'a>(params: &mut impl Iterator<Item = &'a str>) {
+    let count = match params.next() {
+        Some("5") => 1,
+        Some("2") => 3,
+        _ => 0,
+    };
+    for _ in 0..count {
+        let _ = params.next();
+    }
+}
+
+/// Whether an SGR (`ESC [ ... m`) sequence leaves inverse video on
+fn apply_inverse(params: &str, mut inverse: bool) -> bool {
+    let mut params = params.split(';');
+    while let Some(param) = params.next() {
+        match param {
+            "" | "0" | "27" => inverse = false,
+            "7" => inverse = true,
+            "38" | "48" => skip_extended_color(&mut params),
+            _ => {}
+        }
+    }
+    return inverse;
+}
+
+/// Convert one line of riff's ANSI colored output into plain text, with the
+/// inverse video parts marked as `[-removed-]` on `-` lines and as
+/// `{+added+}` on other lines. Other escape sequences are dropped.
+#[must_use]
+pub fn ansi_to_marked(line: &str) -> String {
+    let mut marked = String::with_capacity(line.len());
+    let mut inverse = false;
+    let mut marker_open = false;
+    let mut markers = ("{+", "+}");
+
+    for part in parts(line) {
+        let c = match part {
+            Err(escape) => {
+                if let Some(params) = sgr_parameters(escape) {
+                    inverse = apply_inverse(params, inverse);
+                    if marker_open && !inverse {
+                        marked.push_str(markers.1);
+                        marker_open = false;
+                    }
+                }
+                continue;
+            }
+            Ok(c) => c,
+        };
+
+        if marked.is_empty() && c == '-' {
+            markers = ("[-", "-]");
+        }
+
+        if inverse && !marker_open {
+            marked.push_str(markers.0);
+            marker_open = true;
+        }
+        marked.push(c);
+    }
+
+    if marker_open {
+        marked.push_str(markers.1);
+    }
+    return marked;
+}
+
+/// With `--plain-refined`, converts the highlighted output into plain text
+/// with the changed parts of lines marked with `[-` and `{+` markers, for
+/// pasting into places that don't do colors.
+pub struct PlainRefined<W: Write> {
+    output: W,
+
+    /// Output bytes since the last newline
+    line: Vec<u8>,
+}
+
+impl<W: Write> PlainRefined<W> {
+    pub fn new(output: W) -> PlainRefined<W> {
+        return PlainRefined {
+            output,
+            line: Vec::new(),
+        };
+    }
+
+    fn write_line(&mut self) -> io::Result<()> {
+        let marked = ansi_to_marked(&String::from_utf8_lossy(&self.line));
+        self.line.clear();
+        return self.output.write_all(marked.as_bytes());
+    }
+}
+
+impl<W: Write> Write for PlainRefined<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for byte in buf {
+            if *byte == b'\n' {
+                self.write_line()?;
+                self.output.write_all(b"\n")?;
+            } else {
+                self.line.push(*byte);
+            }
+        }
+        return Ok(buf.len());
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        return self.output.flush();
+    }
+}
+
+impl<W: Write> Drop for PlainRefined<W> {
+    fn drop(&mut self) {
+        // The last line, if it didn't end in a newline
+        if !self.line.is_empty() {
+            let _ = self.write_line();
+        }
+        let _ = self.output.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_ansi_to_marked() {
+        assert_eq!(
+            ansi_to_marked("\x1b[31m-Hello, my \x1b[7m\x1b[31mname\x1b[0m is Johan"),
+            "-Hello, my [-name-] is Johan"
+        );
+        assert_eq!(
+            ansi_to_marked(
+                "\x1b[2m\x1b[32m+\x1b[0mHello, my \x1b[7m\x1b[32mfirst \x1b[0mname is Johan"
+            ),
+            "+Hello, my {+first +}name is Johan"
+        );
+        assert_eq!(
+            ansi_to_marked("\x1b[32m+Some \x1b[7m**\x1b[27mtext\x1b[7m**\x1b[0m"),
+            "+Some {+**+}text{+**+}"
+        );
+
+        // Extended colors don't turn on inverse video
+        assert_eq!(ansi_to_marked("\x1b[38;5;7m apa\x1b[0m"), " apa");
+
+        // Hyperlinks are dropped
+        assert_eq!(
+            ansi_to_marked("\x1b]8;;https://example.com\x1b\\ apa\x1b]8;;\x07"),
+            " apa"
+        );
+    }
+
+    #[test]
+    fn test_plain_refined_writer() {
+        let mut output = Vec::new();
+        {
+            let mut writer = PlainRefined::new(&mut output);
+            writer
+                .write_all(b"\x1b[31m-a\x1b[7m\x1b[31mb\x1b[0m\n\x1b[32m+a\x1b[7m")
+                .unwrap();
+            writer.write_all(b"\x1b[32mc\x1b[0m").unwrap();
+        }
+        assert_eq!(String::from_utf8(output).unwrap(), "-a[-b-]\n+a{+c+}");
+    }
+}
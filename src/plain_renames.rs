@@ -0,0 +1,286 @@
+use crate::file_timestamp;
+use crate::hunk_header::HunkHeader;
+use crate::line_diff::{line_edits, to_hunks, LineEdit, CONTEXT_LINES, MAX_LINES_PRODUCT};
+use std::cmp::Reverse;
+use std::ops::Range;
+
+/// Like `git diff -M`, files need to have at least this share of their lines
+/// in common to count as renamed
+const MIN_SIMILARITY_PERCENT: usize = 50;
+
+/// A file that was added or removed as a whole, like in `diff -ruN` output
+struct WholeFile<'a> {
+    /// Indices of this file's lines in the input, including any `diff` line
+    section: Range<usize>,
+
+    /// From the `---` line for removed files, or the `+++` line for added
+    /// ones, without any timestamp
+    name: &'a str,
+
+    /// The file's contents, without the leading `-` or `+` or any trailing
+    /// newline
+    lines: Vec<&'a str>,
+
+    added: bool,
+}
+
+/// A removed and an added file that could be a rename
+struct Candidate<'a> {
+    similarity: usize,
+
+    /// Indices into the list of whole files
+    old_index: usize,
+    new_index: usize,
+
+    /// Diff between the two files
+    script: Vec<LineEdit<'a>>,
+}
+
+/// `--- a/x.txt\t2024-01-01 12:00:00` -> `a/x.txt`
+fn file_name<'a>(line: &'a str, prefix: &str) -> Option<&'a str> {
+    let name = line.strip_prefix(prefix)?;
+    return Some(file_timestamp::strip(name));
+}
+
+/// If a whole file is added or removed starting at `start`, parse it
+fn parse_whole_file<'a>(lines: &[&'a str], start: usize) -> Option<WholeFile<'a>> {
+    let mut index = start;
+    if lines[index].starts_with("diff ") {
+        index += 1;
+
+        // With `git diff --no-renames`
+        while index < lines.len()
+            && ["new file mode ", "deleted file mode ", "index "]
+                .iter()
+                .any(|prefix| lines[index].starts_with(prefix))
+        {
+            index += 1;
+        }
+    }
+
+    let old_name = file_name(lines.get(index)?, "--- ")?;
+    let new_name = file_name(lines.get(index + 1)?, "+++ ")?;
+    let header = HunkHeader::parse(lines.get(index + 2)?)?;
+    let (added, count) = match (header.old_count, header.new_count) {
+        (0, count) if header.old_start == 0 && header.new_start == 1 => (true, count),
+        (count, 0) if header.old_start == 1 && header.new_start == 0 => (false, count),
+        _ => return None,
+    };
+
+    let body_start = index + 3;
+    let body = lines.get(body_start..body_start + count)?;
+    let marker = if added { '+' } else { '-' };
+    if !body.iter().all(|line| line.starts_with(marker)) {
+        return None;
+    }
+    if let Some(next_line) = lines.get(body_start + count) {
+        if next_line.starts_with('\\') {
+            // No newline at end of file. Comparing the last lines would take
+            // extra care, don't bother.
+            return None;
+        }
+    }
+
+    return Some(WholeFile {
+        section: start..body_start + count,
+        name: if added { new_name } else { old_name },
+        lines: body
+            .iter()
+            .map(|line| line[1..].strip_suffix('\n').unwrap_or(&line[1..]))
+            .collect(),
+        added,
+    });
+}
+
+/// Percentage of lines the files have in common, relative to the larger one
+fn similarity(script: &[LineEdit], old: &[&str], new: &[&str]) -> usize {
+    let common = script
+        .iter()
+        .filter(|edit| matches!(edit, LineEdit::Context(_)))
+        .count();
+    return 100 * common / old.len().max(new.len()).max(1);
+}
+
+/// Find files removed and added as a whole in a plain diff, like from `diff
+/// -ruN`, that have similar contents. Render those as renames with a diff of
+/// the contents, like `git diff -M` would.
+///
+/// Returns `None` if no renames were found.
+#[must_use]
+pub fn detect_renames(diff: &str) -> Option<String> {
+    let lines: Vec<&str> = diff.split_inclusive('\n').collect();
+
+    let mut whole_files: Vec<WholeFile> = Vec::new();
+    let mut index = 0;
+    while index < lines.len() {
+        match parse_whole_file(&lines, index) {
+            Some(whole_file) => {
+                index = whole_file.section.end;
+                whole_files.push(whole_file);
+            }
+            None => index += 1,
+        }
+    }
+
+    // Score all removed / added pairs that could possibly be similar enough
+    let mut candidates: Vec<Candidate> = Vec::new();
+    for (old_index, old) in whole_files.iter().enumerate().filter(|(_, f)| !f.added) {
+        for (new_index, new) in whole_files.iter().enumerate().filter(|(_, f)| f.added) {
+            let (shorter, longer) = if old.lines.len() < new.lines.len() {
+                (old.lines.len(), new.lines.len())
+            } else {
+                (new.lines.len(), old.lines.len())
+            };
+            if 100 * shorter < MIN_SIMILARITY_PERCENT * longer {
+                continue;
+            }
+            let script = match line_edits(&old.lines, &new.lines, MAX_LINES_PRODUCT) {
+                Some(script) => script,
+                None => continue,
+            };
+            let similarity = similarity(&script, &old.lines, &new.lines);
+            if similarity >= MIN_SIMILARITY_PERCENT {
+                candidates.push(Candidate {
+                    similarity,
+                    old_index,
+                    new_index,
+                    script,
+                });
+            }
+        }
+    }
+    if candidates.is_empty() {
+        return None;
+    }
+
+    // Best matches first, every file can only be part of one rename
+    candidates.sort_by_key(|candidate| Reverse(candidate.similarity));
+    let mut renames: Vec<Option<String>> = vec![None; whole_files.len()];
+    let mut used = vec![false; whole_files.len()];
+    for candidate in candidates {
+        if used[candidate.old_index] || used[candidate.new_index] {
+            continue;
+        }
+        used[candidate.old_index] = true;
+        used[candidate.new_index] = true;
+
+        let old_name = whole_files[candidate.old_index].name;
+        let new_name = whole_files[candidate.new_index].name;
+        renames[candidate.old_index] = Some(format!(
+            "similarity index {}%\nrename from {old_name}\nrename to {new_name}\n--- {old_name}\n+++ {new_name}\n{}\n",
+            candidate.similarity,
+            to_hunks(&candidate.script, CONTEXT_LINES).join("\n")
+        ));
+    }
+
+    // Put each rename where its removed file was, and leave out the added one
+    let mut result = String::with_capacity(diff.len());
+    let mut index = 0;
+    for ((whole_file, rename), is_used) in whole_files.iter().zip(renames).zip(used) {
+        if !is_used {
+            // Not part of any rename, keep as is
+            continue;
+        }
+
+        for line in &lines[index..whole_file.section.start] {
+            result.push_str(line);
+        }
+        if let Some(rename) = rename {
+            result.push_str(&rename);
+        }
+        index = whole_file.section.end;
+    }
+    for line in &lines[index..] {
+        result.push_str(line);
+    }
+
+    return Some(result);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_detect_renames() {
+        let diff = concat!(
+            "diff -ruN a/new.txt b/new.txt\n",
+            "--- a/new.txt\t1970-01-01 00:00:00.000000000 +0000\n",
+            "+++ b/new.txt\t2024-01-01 12:00:00.000000000 +0000\n",
+            "@@ -0,0 +1,9 @@\n",
+            "+one\n",
+            "+two\n",
+            "+three\n",
+            "+FOUR\n",
+            "+five\n",
+            "+six\n",
+            "+seven\n",
+            "+eight\n",
+            "+nine\n",
+            "diff -ruN a/old.txt b/old.txt\n",
+            "--- a/old.txt\t2024-01-01 12:00:00.000000000 +0000\n",
+            "+++ b/old.txt\t1970-01-01 00:00:00.000000000 +0000\n",
+            "@@ -1,9 +0,0 @@\n",
+            "-one\n",
+            "-two\n",
+            "-three\n",
+            "-four\n",
+            "-five\n",
+            "-six\n",
+            "-seven\n",
+            "-eight\n",
+            "-nine\n",
+            "diff -ruN a/same.txt b/same.txt\n",
+            "--- a/same.txt\t2024-01-01 12:00:00.000000000 +0000\n",
+            "+++ b/same.txt\t2024-01-01 12:00:00.000000000 +0000\n",
+            "@@ -1 +1 @@\n",
+            "-x\n",
+            "+y\n",
+        );
+        assert_eq!(
+            detect_renames(diff).unwrap(),
+            concat!(
+                "similarity index 88%\n",
+                "rename from a/old.txt\n",
+                "rename to b/new.txt\n",
+                "--- a/old.txt\n",
+                "+++ b/new.txt\n",
+                "@@ -1,7 +1,7 @@\n",
+                " one\n",
+                " two\n",
+                " three\n",
+                "-four\n",
+                "+FOUR\n",
+                " five\n",
+                " six\n",
+                " seven\n",
+                "diff -ruN a/same.txt b/same.txt\n",
+                "--- a/same.txt\t2024-01-01 12:00:00.000000000 +0000\n",
+                "+++ b/same.txt\t2024-01-01 12:00:00.000000000 +0000\n",
+                "@@ -1 +1 @@\n",
+                "-x\n",
+                "+y\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_unrelated_files() {
+        let diff = concat!(
+            "--- a/new.txt\n",
+            "+++ b/new.txt\n",
+            "@@ -0,0 +1,2 @@\n",
+            "+one\n",
+            "+two\n",
+            "--- a/old.txt\n",
+            "+++ b/old.txt\n",
+            "@@ -1,2 +0,0 @@\n",
+            "-three\n",
+            "-four\n",
+        );
+        assert_eq!(detect_renames(diff), None);
+    }
+}
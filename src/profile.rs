@@ -0,0 +1,182 @@
+use crate::csv;
+use crate::file_timestamp;
+use crate::hexdump;
+use crate::json;
+use crate::markdown;
+use crate::python;
+use crate::rust;
+use crate::token_collector::{
+    highlight_nonleading_tabs, highlight_trailing_whitespace, Style, StyledToken,
+};
+use crate::tokenizer;
+use crate::yaml;
+
+/// How to tokenize and post process a file's contents, depending on what kind
+/// of file it is
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Profile {
+    Default,
+    Rust,
+    Python,
+    Yaml,
+    Json,
+    Markdown,
+    Csv,
+    Tsv,
+
+    /// `xxd` and `hexdump -C` output, picked by contents rather than by file
+    /// name
+    Hexdump,
+}
+
+impl Profile {
+    /// Switch to byte level refinement if both sides are hex dumps
+    #[must_use]
+    pub fn for_contents(self, old_text: &str, new_text: &str) -> Profile {
+        if hexdump::is_hexdump(old_text) && hexdump::is_hexdump(new_text) {
+            return Profile::Hexdump;
+        }
+        return self;
+    }
+
+    /// True for source code, where identifiers and trailing comments can be
+    /// refined on their own
+    #[must_use]
+    pub fn is_code(self) -> bool {
+        return self == Profile::Default || self == Profile::Rust || self == Profile::Python;
+    }
+
+    /// True if changing the indentation of a line changes what it means. Then
+    /// indentation changes get highlighted even when ignoring whitespace.
+    #[must_use]
+    pub fn has_significant_indentation(self) -> bool {
+        return self == Profile::Python;
+    }
+
+    /// Pick a profile based on a file name from a `+++ ` or `--- ` line.
+    /// Timestamps after the file name are fine.
+    #[must_use]
+    pub fn for_filename(filename: &str) -> Profile {
+        let filename = file_timestamp::strip(filename);
+
+        if let Some((_, extension)) = filename.rsplit_once('.') {
+            let extension = extension.to_lowercase();
+            if extension == "rs" {
+                return Profile::Rust;
+            }
+            if extension == "py" || extension == "pyi" {
+                return Profile::Python;
+            }
+            if extension == "yaml" || extension == "yml" {
+                return Profile::Yaml;
+            }
+            if extension == "json" {
+                return Profile::Json;
+            }
+            if extension == "md" || extension == "markdown" {
+                return Profile::Markdown;
+            }
+            if extension == "csv" {
+                return Profile::Csv;
+            }
+            if extension == "tsv" || extension == "tab" {
+                return Profile::Tsv;
+            }
+        }
+
+        // `kubectl diff` compares YAML in extensionless files in directories
+        // like /tmp/LIVE-123456789 and /tmp/MERGED-123456789
+        if filename.contains("/LIVE-") || filename.contains("/MERGED-") {
+            return Profile::Yaml;
+        }
+
+        return Profile::Default;
+    }
+
+    #[must_use]
+    pub fn tokenize(self, input: &str) -> Vec<&str> {
+        return match self {
+            Profile::Default => tokenizer::tokenize(input),
+            Profile::Rust => rust::tokenize(input),
+            Profile::Python => python::tokenize(input),
+            Profile::Yaml => yaml::tokenize(input),
+            Profile::Json => json::tokenize(input),
+            Profile::Markdown => markdown::tokenize(input),
+            Profile::Csv => csv::tokenize(input, ','),
+            Profile::Tsv => csv::tokenize(input, '\t'),
+            Profile::Hexdump => hexdump::tokenize(input),
+        };
+    }
+
+    /// Flag trailing whitespace and non-leading TABs in added lines
+    pub fn highlight_whitespace_errors(self, tokens: &mut [StyledToken]) {
+        match self {
+            Profile::Markdown => markdown::highlight_trailing_whitespace(tokens),
+            _ => highlight_trailing_whitespace(tokens, Style::Error),
+        }
+        if self != Profile::Tsv {
+            // TABs are what separates the cells in TSV files
+            highlight_nonleading_tabs(tokens);
+        }
+    }
+
+    /// Adjust highlighting after refinement is done
+    pub fn post_process(self, old_tokens: &mut [StyledToken], new_tokens: &mut [StyledToken]) {
+        match self {
+            Profile::Default | Profile::Rust | Profile::Python => {}
+            Profile::Yaml => {
+                yaml::lowlight_churn(old_tokens);
+                yaml::lowlight_churn(new_tokens);
+            }
+            Profile::Json => json::lowlight_moved_lines(old_tokens, new_tokens),
+            Profile::Markdown => {}
+            Profile::Csv | Profile::Tsv => {}
+            Profile::Hexdump => {
+                hexdump::unhighlight_offsets(old_tokens);
+                hexdump::unhighlight_offsets(new_tokens);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_for_filename() {
+        assert_eq!(Profile::for_filename("b/src/main.rs"), Profile::Rust);
+        assert_eq!(Profile::for_filename("b/src/main.c"), Profile::Default);
+        assert_eq!(Profile::for_filename("b/setup.py"), Profile::Python);
+        assert_eq!(Profile::for_filename("b/deploy.yaml"), Profile::Yaml);
+        assert_eq!(Profile::for_filename("b/package.json"), Profile::Json);
+        assert_eq!(
+            Profile::for_filename("b/.github/workflows/ci.YML"),
+            Profile::Yaml
+        );
+        assert_eq!(
+            Profile::for_filename(
+                "/tmp/MERGED-1445302542/apps.v1.Deployment.default.nginx\t2023-01-01 12:00:00"
+            ),
+            Profile::Yaml
+        );
+        assert_eq!(Profile::for_filename("README"), Profile::Default);
+        assert_eq!(Profile::for_filename("b/README.md"), Profile::Markdown);
+        assert_eq!(Profile::for_filename("b/data/prices.CSV"), Profile::Csv);
+        assert_eq!(Profile::for_filename("b/export.tsv"), Profile::Tsv);
+    }
+
+    #[test]
+    fn test_for_contents() {
+        let old = "00000000: 4865 6c6c 6f0a  Hello.\n";
+        let new = "00000000: 4865 6c70 210a  Help!.\n";
+        assert_eq!(Profile::Markdown.for_contents(old, new), Profile::Hexdump);
+        assert_eq!(
+            Profile::Markdown.for_contents(old, "Hello\n"),
+            Profile::Markdown
+        );
+    }
+}
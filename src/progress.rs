@@ -0,0 +1,137 @@
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+/// Don't show any progress for inputs we're done with before this
+const SHOW_AFTER: Duration = Duration::from_secs(1);
+
+/// Don't update the progress indicator more often than this
+const UPDATE_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Wraps an input stream and shows how far we have gotten reading it on
+/// stderr.
+///
+/// Only used while paging, so that the progress indicator never ends up in the
+/// highlighted output.
+pub struct ProgressReader<'a> {
+    input: &'a mut dyn io::Read,
+
+    /// Input size in bytes, if known. Needed for computing an ETA.
+    total_bytes: Option<u64>,
+
+    bytes_read: u64,
+    lines_read: usize,
+    started: Instant,
+    last_shown: Option<Instant>,
+}
+
+impl<'a> ProgressReader<'a> {
+    pub fn new(input: &'a mut dyn io::Read, total_bytes: Option<u64>) -> ProgressReader<'a> {
+        return ProgressReader {
+            input,
+            total_bytes,
+            bytes_read: 0,
+            lines_read: 0,
+            started: Instant::now(),
+            last_shown: None,
+        };
+    }
+
+    fn show(&mut self) {
+        let now = Instant::now();
+        if now.duration_since(self.started) < SHOW_AFTER {
+            return;
+        }
+        if let Some(last_shown) = self.last_shown {
+            if now.duration_since(last_shown) < UPDATE_INTERVAL {
+                return;
+            }
+        }
+        self.last_shown = Some(now);
+
+        let text = format_progress(
+            self.lines_read,
+            self.bytes_read,
+            self.total_bytes,
+            now.duration_since(self.started),
+        );
+
+        // Failing to show progress is not worth crashing over
+        let _ = write!(io::stderr(), "\r{text}\x1b[K");
+    }
+}
+
+impl<'a> Drop for ProgressReader<'a> {
+    fn drop(&mut self) {
+        if self.last_shown.is_some() {
+            // Clear the progress line
+            let _ = write!(io::stderr(), "\r\x1b[K");
+        }
+    }
+}
+
+impl<'a> io::Read for ProgressReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read_count = self.input.read(buf)?;
+        self.bytes_read += read_count as u64;
+        self.lines_read += bytecount::count(&buf[..read_count], b'\n');
+        self.show();
+        return Ok(read_count);
+    }
+}
+
+/// Returns something like `"123456 lines, 12.3MB read, ETA 7s"`
+#[must_use]
+fn format_progress(
+    lines_read: usize,
+    bytes_read: u64,
+    total_bytes: Option<u64>,
+    elapsed: Duration,
+) -> String {
+    let megabytes = bytes_read as f64 / (1024.0 * 1024.0);
+    let mut text = format!("{lines_read} lines, {megabytes:.1}MB read");
+
+    if let Some(total_bytes) = total_bytes {
+        if bytes_read > 0 && total_bytes >= bytes_read {
+            let remaining = (total_bytes - bytes_read) as f64 / bytes_read as f64;
+            let eta_seconds = (elapsed.as_secs_f64() * remaining).ceil();
+            text.push_str(&format!(", ETA {eta_seconds:.0}s"));
+        }
+    }
+
+    return text;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_format_progress() {
+        assert_eq!(
+            format_progress(1234, 3 * 1024 * 1024 / 2, None, Duration::from_secs(2)),
+            "1234 lines, 1.5MB read"
+        );
+
+        // A quarter done after 2s means 6s left
+        assert_eq!(
+            format_progress(1234, 1000, Some(4000), Duration::from_secs(2)),
+            "1234 lines, 0.0MB read, ETA 6s"
+        );
+    }
+
+    #[test]
+    fn test_counting() {
+        let mut input = "a\nb\nc".as_bytes();
+        let mut reader = ProgressReader::new(&mut input, None);
+        let mut result = String::new();
+        reader.read_to_string(&mut result).unwrap();
+
+        assert_eq!(result, "a\nb\nc");
+        assert_eq!(reader.bytes_read, 5);
+        assert_eq!(reader.lines_read, 2);
+    }
+}
@@ -0,0 +1,185 @@
+use crate::tokenizer;
+use std::ops::Range;
+
+/// Tokenize Python so that indentation, decorators like `@app.route` and
+/// f-string replacement fields like `{name!r}` are single tokens.
+///
+/// Indentation is semantic in Python, so a changed indentation level gets
+/// highlighted as a whole rather than a space at a time.
+pub fn tokenize(input: &str) -> Vec<&str> {
+    let mut result: Vec<&str> = Vec::with_capacity(input.len());
+    for line in input.split_inclusive('\n') {
+        if let Some(without_newline) = line.strip_suffix('\n') {
+            tokenize_line(without_newline, &mut result);
+            result.push(&line[without_newline.len()..]);
+        } else {
+            tokenize_line(line, &mut result);
+        }
+    }
+
+    return result;
+}
+
+fn tokenize_line<'a>(line: &'a str, result: &mut Vec<&'a str>) {
+    let code = line.trim_start_matches([' ', '\t']);
+    let indentation = &line[..line.len() - code.len()];
+    if !indentation.is_empty() {
+        result.push(indentation);
+    }
+
+    let mut rest = code;
+    if let Some(length) = decorator_length(code) {
+        result.push(&code[..length]);
+        rest = &code[length..];
+    }
+
+    let mut start = 0;
+    for field in fstring_fields(rest) {
+        result.append(&mut tokenizer::tokenize(&rest[start..field.start]));
+        result.push(&rest[field.clone()]);
+        start = field.end;
+    }
+    result.append(&mut tokenizer::tokenize(&rest[start..]));
+}
+
+/// Length of a `@decorator` or `@dotted.decorator` at the start of `code`
+fn decorator_length(code: &str) -> Option<usize> {
+    let name = code.strip_prefix('@')?;
+    let length = name
+        .find(|c: char| c != '_' && c != '.' && !c.is_alphanumeric())
+        .unwrap_or(name.len());
+    if length == 0 {
+        return None;
+    }
+    return Some(1 + length);
+}
+
+/// Byte ranges of the `{...}` replacement fields in the f-strings of a line
+/// of code. Escaped `{{` braces are not fields.
+fn fstring_fields(code: &str) -> Vec<Range<usize>> {
+    let mut fields: Vec<Range<usize>> = Vec::new();
+
+    // The quote that ends the string we're in, whether that string is an
+    // f-string and whether it is raw
+    let mut string: Option<(&str, bool, bool)> = None;
+    let mut index = 0;
+    while let Some(c) = code[index..].chars().next() {
+        let rest = &code[index..];
+        match string {
+            None => {
+                if c == '#' {
+                    // Comment, no more strings on this line
+                    break;
+                }
+                if c == '"' || c == '\'' {
+                    let prefix_start = code[..index]
+                        .trim_end_matches(|c: char| c == '_' || c.is_alphanumeric())
+                        .len();
+                    let prefix = &code[prefix_start..index];
+                    let is_fstring = ["f", "rf", "fr"]
+                        .iter()
+                        .any(|candidate| prefix.eq_ignore_ascii_case(candidate));
+                    let is_raw = prefix.contains(['r', 'R']);
+
+                    let quote_length = if rest.starts_with(&c.to_string().repeat(3)) {
+                        3
+                    } else {
+                        1
+                    };
+                    string = Some((&rest[..quote_length], is_fstring, is_raw));
+                    index += quote_length;
+                    continue;
+                }
+            }
+            Some((quote, is_fstring, is_raw)) => {
+                if c == '\\' && !is_raw {
+                    // Skip the escaped character
+                    index += 1;
+                    index += code[index..].chars().next().map_or(0, char::len_utf8);
+                    continue;
+                }
+                if rest.starts_with(quote) {
+                    string = None;
+                    index += quote.len();
+                    continue;
+                }
+                if is_fstring && rest.starts_with("{{") {
+                    index += 2;
+                    continue;
+                }
+                if is_fstring && c == '{' {
+                    if let Some(length) = field_length(rest) {
+                        fields.push(index..index + length);
+                        index += length;
+                        continue;
+                    }
+                }
+            }
+        }
+        index += c.len_utf8();
+    }
+
+    return fields;
+}
+
+/// Length of the `{...}` field at the start of `text`, including nested
+/// fields like in `{value:{width}}`
+fn field_length(text: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (index, c) in text.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(index + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+    return None;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_tokenize_indentation_and_decorators() {
+        assert_eq!(
+            tokenize("    @app.route(x)\n\tpass\n"),
+            [
+                "    ",
+                "@app.route",
+                "(",
+                "x",
+                ")",
+                "\n",
+                "\t",
+                "pass",
+                "\n"
+            ]
+        );
+
+        // Matrix multiplication is not a decorator
+        assert_eq!(tokenize("a @ b"), ["a", " ", "@", " ", "b"]);
+    }
+
+    #[test]
+    fn test_tokenize_fstrings() {
+        assert_eq!(
+            tokenize("f\"{name!r} is {age:{width}}\""),
+            ["f", "\"", "{name!r}", " ", "is", " ", "{age:{width}}", "\""]
+        );
+
+        // Escaped braces, and braces in strings that aren't f-strings
+        assert_eq!(
+            tokenize("f'{{x}}' + '{y}'"),
+            ["f", "'", "{", "{", "x", "}", "}", "'", " ", "+", " ", "'", "{", "y", "}", "'"]
+        );
+    }
+}
@@ -0,0 +1,129 @@
+use std::borrow::Cow;
+
+/// Lines that can contain file names that git may have quoted. `---` and `+++`
+/// lines are unquoted only once we know they are file headers, since inside of
+/// hunks they are removed or added lines.
+const PATH_HEADER_PREFIXES: &[&str] = &[
+    "diff --git ",
+    "rename from ",
+    "rename to ",
+    "copy from ",
+    "copy to ",
+    "Binary files ",
+];
+
+/// Unquote the file names in a `diff --git`, `rename from`, `Binary files` or
+/// similar line. Returns `None` for other lines, and for lines without any
+/// quoted file names.
+#[must_use]
+pub fn unquote_header(line: &str) -> Option<String> {
+    if !PATH_HEADER_PREFIXES
+        .iter()
+        .any(|prefix| line.starts_with(prefix))
+    {
+        return None;
+    }
+
+    return match unquote(line) {
+        Cow::Borrowed(_) => None,
+        Cow::Owned(unquoted) => Some(unquoted),
+    };
+}
+
+/// Git quotes file names with spaces, quotes or non-ASCII characters in them,
+/// like `"a/sp ace\303\244.txt"`. This function replaces all such quoted names
+/// in `text` with what they stand for, `a/sp aceä.txt` in this case.
+///
+/// Escaped bytes that don't make valid UTF-8 become replacement characters.
+#[must_use]
+pub fn unquote(text: &str) -> Cow<'_, str> {
+    if !text.contains('"') {
+        return Cow::Borrowed(text);
+    }
+
+    let mut unquoted: Vec<u8> = Vec::with_capacity(text.len());
+    let mut bytes = text.bytes().peekable();
+    while let Some(byte) = bytes.next() {
+        if byte != b'"' {
+            unquoted.push(byte);
+            continue;
+        }
+
+        // Inside a quoted name
+        while let Some(byte) = bytes.next() {
+            if byte == b'"' {
+                break;
+            }
+            if byte != b'\\' {
+                unquoted.push(byte);
+                continue;
+            }
+
+            let escaped = match bytes.next() {
+                Some(escaped) => escaped,
+                None => break,
+            };
+            let unescaped = match escaped {
+                b'a' => 0x07,
+                b'b' => 0x08,
+                b't' => b'\t',
+                b'n' => b'\n',
+                b'v' => 0x0b,
+                b'f' => 0x0c,
+                b'r' => b'\r',
+                b'0'..=b'7' => {
+                    // Up to three octal digits, one byte
+                    let mut value = u32::from(escaped - b'0');
+                    for _ in 0..2 {
+                        match bytes.peek() {
+                            Some(digit @ b'0'..=b'7') => {
+                                value = value * 8 + u32::from(digit - b'0');
+                                let _ = bytes.next();
+                            }
+                            _ => break,
+                        }
+                    }
+                    value as u8
+                }
+                // Quotes and backslashes
+                other => other,
+            };
+            unquoted.push(unescaped);
+        }
+    }
+
+    return Cow::Owned(String::from_utf8_lossy(&unquoted).into_owned());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_unquote() {
+        assert_eq!(unquote("a/plain.txt"), "a/plain.txt");
+        assert_eq!(unquote(r#""a/sp ace\303\244.txt""#), "a/sp aceä.txt");
+        assert_eq!(
+            unquote(r#""a/\"quoted\" \\ tab\t.txt"	2023-01-01"#),
+            "a/\"quoted\" \\ tab\t.txt\t2023-01-01"
+        );
+        assert_eq!(unquote(r#""a/bad\377""#), "a/bad\u{fffd}");
+    }
+
+    #[test]
+    fn test_unquote_header() {
+        assert_eq!(
+            unquote_header(r#"diff --git "a/x y.txt" "b/x y.txt""#),
+            Some("diff --git a/x y.txt b/x y.txt".to_string())
+        );
+        assert_eq!(
+            unquote_header(r#"rename to "\346\227\245.txt""#),
+            Some("rename to 日.txt".to_string())
+        );
+        assert_eq!(unquote_header("rename to x.txt"), None);
+        assert_eq!(unquote_header(r#" context with "\303\244""#), None);
+    }
+}
@@ -0,0 +1,61 @@
+use regex::Regex;
+
+/// `git range-diff` indents the diff between two versions of a commit by this
+pub const INDENT: &str = "    ";
+
+lazy_static! {
+    /// `1:  0123abc ! 1:  4567def Subject`, or with `-:  -------` for a commit
+    /// that is only on one side
+    static ref PAIR_HEADER: Regex =
+        Regex::new(r"^(\d+|-): +([0-9a-f]+|-+) [=!<>] (\d+|-): +([0-9a-f]+|-+)( |$)").unwrap();
+}
+
+/// True for the lines `git range-diff` puts before each pair of commits
+#[must_use]
+pub fn is_pair_header(line: &str) -> bool {
+    return PAIR_HEADER.is_match(line);
+}
+
+/// A changed line in the diff between two versions of a commit, without the
+/// indentation. Commit message lines, like trailers, are diffed like any other
+/// line.
+#[must_use]
+pub fn changed_line(line: &str) -> Option<&str> {
+    let inner = line.strip_prefix(INDENT)?;
+    if inner.starts_with('-') || inner.starts_with('+') {
+        return Some(inner);
+    }
+    return None;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_is_pair_header() {
+        assert!(is_pair_header(
+            "1:  0123abc ! 1:  4567def Fix the frobnicator"
+        ));
+        assert!(is_pair_header("12:  0123abc = 12:  4567def Fix"));
+        assert!(is_pair_header("-:  ------- > 3:  4567def New commit"));
+        assert!(is_pair_header("2:  0123abc < -:  ------- Dropped commit"));
+        assert!(!is_pair_header("1: Fix"));
+        assert!(!is_pair_header("commit 0123abc"));
+    }
+
+    #[test]
+    fn test_changed_line() {
+        assert_eq!(
+            changed_line("    -    Reviewed-by: A <a@example.com>"),
+            Some("-    Reviewed-by: A <a@example.com>")
+        );
+        assert_eq!(changed_line("    ++added"), Some("++added"));
+        assert_eq!(changed_line("         Context"), None);
+        assert_eq!(changed_line("    @@ Metadata"), None);
+        assert_eq!(changed_line("-not indented"), None);
+    }
+}
@@ -1,58 +1,138 @@
-use crate::constants::*;
-use crate::line_collector::NO_EOF_NEWLINE_MARKER_HOLDER;
+use crate::comments::trailing_comment_mask;
+use crate::config::{
+    Config, Identifiers, NoisyLines, SpacesBetweenHighlights, TrailingComments, UnpairedLines,
+    Whitespace, WhitespaceErrors,
+};
+use crate::describe;
+use crate::eof_marker;
+use crate::hunk::Hunk;
+use crate::palette::Palette;
+use crate::profile::Profile;
+use crate::token_cache::TokenCache;
 use crate::token_collector::*;
-use crate::tokenizer;
+use crate::tokenizer::{classify, split_identifiers};
 use diffus::{
     edit::{self, collection},
     Diffable,
 };
+use regex::Regex;
 
 /// Like format!(), but faster for our special case
-fn format_simple_line(old_new: &str, plus_minus: char, contents: &str) -> String {
-    let mut line = String::with_capacity(old_new.len() + 1 + contents.len() + NORMAL.len());
+fn format_simple_line(old_new: &str, plus_minus: char, contents: &str, normal: &str) -> String {
+    let mut line = String::with_capacity(old_new.len() + 1 + contents.len() + normal.len());
     line.push_str(old_new);
     line.push(plus_minus);
     line.push_str(contents);
-    line.push_str(NORMAL);
+    line.push_str(normal);
     return line;
 }
 
+/// Which side of a change gets a highlighted `⏎`, to help visualize what
+/// actually happens in "No newline at end of file" diffs
+#[derive(Debug, PartialEq, Eq)]
+enum NewlineMarker {
+    /// The old text ended in a newline that the new text lost
+    Old,
+
+    /// The new text gained a newline, or added text has none
+    New,
+}
+
+/// `None` for an empty text, otherwise whether it ends in a newline
+#[must_use]
+fn ends_in_newline(text: &str) -> Option<bool> {
+    if text.is_empty() {
+        return None;
+    }
+    return Some(text.ends_with('\n'));
+}
+
+/// Arguments are from [`ends_in_newline`] for the old and the new text
+#[must_use]
+fn newline_marker(old: Option<bool>, new: Option<bool>) -> Option<NewlineMarker> {
+    return match (old, new) {
+        // Adds only, point out that the file now ends without a newline
+        (None, Some(false)) => Some(NewlineMarker::New),
+
+        // Removes or adds only, no newline was gained or lost
+        (None, _) | (_, None) => None,
+
+        (Some(true), Some(false)) => Some(NewlineMarker::Old),
+        (Some(false), Some(true)) => Some(NewlineMarker::New),
+        _ => None,
+    };
+}
+
+/// Like [`ends_in_newline`], but for a hunk's old and new lines
+#[must_use]
+fn hunk_ends_in_newline(hunk: &Hunk) -> (Option<bool>, Option<bool>) {
+    let old = (!hunk.old_lines.is_empty()).then_some(!hunk.old_missing_eof_newline);
+    let new = (!hunk.new_lines.is_empty()).then_some(!hunk.new_missing_eof_newline);
+    return (old, new);
+}
+
 /// Format old and new lines in OLD and NEW colors.
 ///
 /// No intra-line refinement.
 #[must_use]
-fn format_simple(old_text: &str, new_text: &str) -> Vec<String> {
-    let mut lines: Vec<String> = Vec::new();
-
-    for old_line in old_text.lines() {
-        // Use a specialized line formatter since this code is in a hot path
-        lines.push(format_simple_line(OLD, '-', old_line));
+fn format_simple(hunk: &Hunk, palette: &Palette) -> Vec<String> {
+    let mut lines: Vec<String> =
+        Vec::with_capacity(hunk.old_lines.len() + hunk.new_lines.len() + 2);
+    let (old_ends_in_newline, new_ends_in_newline) = hunk_ends_in_newline(hunk);
+    let marker = newline_marker(old_ends_in_newline, new_ends_in_newline);
+
+    let old_line_count = hunk.old_lines.len();
+    for (line_number, old_line) in hunk.old_line_texts().enumerate() {
+        let old_line: String =
+            if marker == Some(NewlineMarker::Old) && line_number == old_line_count - 1 {
+                // The newline at the end of this line went away
+                format!(
+                    "{}-{old_line}{}⏎{}",
+                    palette.old, palette.inverse_video, palette.normal
+                )
+            } else {
+                // Use a specialized line formatter since this code is in a hot path
+                format_simple_line(&palette.old, '-', &old_line, &palette.normal)
+            };
+        lines.push(old_line);
     }
-    if (!old_text.is_empty()) && !old_text.ends_with('\n') {
-        let no_eof_newline_marker_guard = NO_EOF_NEWLINE_MARKER_HOLDER.lock().unwrap();
-        let no_eof_newline_marker = no_eof_newline_marker_guard.as_ref().unwrap();
+    if old_ends_in_newline == Some(false) {
+        let no_eof_newline_marker = eof_marker::get();
         lines.push(format!(
-            "{NO_EOF_NEWLINE_COLOR}{no_eof_newline_marker}{NORMAL}"
+            "{}{no_eof_newline_marker}{}",
+            palette.no_eof_newline, palette.normal
         ));
     }
 
-    let announce_lost_newline = !new_text.is_empty() && !new_text.ends_with('\n');
-    for (line_number, add_line) in new_text.lines().enumerate() {
+    let new_line_count = hunk.new_lines.len();
+    for (line_number, add_line) in hunk.new_line_texts().enumerate() {
         let new_line: String =
-            if announce_lost_newline && line_number == new_text.lines().count() - 1 {
-                // Add a red highlighted newline symbol at the end
-                format!("{NEW}+{add_line}{OLD}{INVERSE_VIDEO}⏎{NORMAL}")
+            if marker == Some(NewlineMarker::New) && line_number == new_line_count - 1 {
+                if old_ends_in_newline.is_none() {
+                    // Added text without a newline, add a red highlighted
+                    // newline symbol at the end
+                    format!(
+                        "{}+{add_line}{}{}⏎{}",
+                        palette.new, palette.old, palette.inverse_video, palette.normal
+                    )
+                } else {
+                    // The newline at the end of this line is new
+                    format!(
+                        "{}+{add_line}{}⏎{}",
+                        palette.new, palette.inverse_video, palette.normal
+                    )
+                }
             } else {
                 // Use a specialized line formatter since this code is in a hot path
-                format_simple_line(NEW, '+', add_line)
+                format_simple_line(&palette.new, '+', &add_line, &palette.normal)
             };
         lines.push(new_line);
     }
-    if (!new_text.is_empty()) && !new_text.ends_with('\n') {
-        let no_eof_newline_marker_guard = NO_EOF_NEWLINE_MARKER_HOLDER.lock().unwrap();
-        let no_eof_newline_marker = no_eof_newline_marker_guard.as_ref().unwrap();
+    if new_ends_in_newline == Some(false) {
+        let no_eof_newline_marker = eof_marker::get();
         lines.push(format!(
-            "{NO_EOF_NEWLINE_COLOR}{no_eof_newline_marker}{NORMAL}"
+            "{}{no_eof_newline_marker}{}",
+            palette.no_eof_newline, palette.normal
         ));
     }
 
@@ -71,117 +151,501 @@ fn too_large_to_refine(old_text: &str, new_text: &str) -> bool {
     return complexity > 13_000u64 * 13_000u64;
 }
 
-/// Returns a vector of ANSI highlighted lines
+/// Returns the name of the first `no-refine` rule matching either text
+#[must_use]
+fn find_no_refine_rule<'a>(config: &'a Config, old_text: &str, new_text: &str) -> Option<&'a str> {
+    return config
+        .no_refine
+        .iter()
+        .find(|rule| rule.regex.is_match(old_text) || rule.regex.is_match(new_text))
+        .map(|rule| rule.name.as_str());
+}
+
+/// Like [`format_simple`], but as text for screen readers with `--describe`
 #[must_use]
-pub fn format(old_text: &str, new_text: &str) -> Vec<String> {
-    if old_text.is_empty() || new_text.is_empty() {
-        return format_simple(old_text, new_text);
+fn format_unrefined(hunk: &Hunk, config: &Config) -> Vec<String> {
+    if config.describe {
+        return describe::describe_simple(hunk);
     }
+    return format_simple(hunk, &config.palette);
+}
 
-    if too_large_to_refine(old_text, new_text) {
-        return format_simple(old_text, new_text);
+/// Returns a vector of ANSI highlighted lines, the old ones first
+#[must_use]
+pub fn format(
+    hunk: &Hunk,
+    profile: Profile,
+    word_regex: Option<&Regex>,
+    refine: bool,
+    config: &Config,
+) -> Vec<String> {
+    if hunk.old_lines.is_empty() || hunk.new_lines.is_empty() {
+        return format_unrefined(hunk, config);
     }
 
-    let (old_tokens, new_tokens, old_highlights, new_unhighlighted) =
-        to_highlighted_tokens(old_text, new_text);
+    // Tokens can span lines, so refining is done on the texts as a whole
+    let old_text = hunk.old_text();
+    let new_text = hunk.new_text();
+    let (old_text, new_text) = (old_text.as_str(), new_text.as_str());
+    if let Some(rule_name) = find_no_refine_rule(config, old_text, new_text) {
+        let mut lines = format_unrefined(hunk, config);
+
+        // Put the marker at the end of the first line rather than on a line of
+        // its own. Adding lines would break `git add -p`.
+        lines[0].push_str(&format!(
+            " {}not refined (matched rule {rule_name}){}",
+            config.palette.faint, config.palette.normal
+        ));
+        return lines;
+    }
+
+    if !refine || too_large_to_refine(old_text, new_text) {
+        return format_unrefined(hunk, config);
+    }
+
+    let mut old_tokens: Vec<StyledToken> = Vec::new();
+    let mut new_tokens: Vec<StyledToken> = Vec::new();
+    let mut old_highlights = false;
+    let mut new_unhighlighted = false;
+    let mut old_unpaired: Vec<bool> = Vec::new();
+    let mut new_unpaired: Vec<bool> = Vec::new();
+    for (old_unit, new_unit) in split_units(old_text, new_text) {
+        let unit = to_highlighted_tokens(old_unit, new_unit, profile, word_regex, config);
+        old_tokens.extend(unit.old_tokens);
+        new_tokens.extend(unit.new_tokens);
+        old_highlights |= unit.old_highlights;
+        new_unhighlighted |= unit.new_unhighlighted;
+        old_unpaired.extend(unit.old_unpaired);
+        new_unpaired.extend(unit.new_unpaired);
+    }
+
+    if config.describe {
+        return to_lines(
+            &describe::describe_rows("removed: ", &old_tokens),
+            &describe::describe_rows("added: ", &new_tokens),
+            &config.palette,
+        );
+    }
 
     let highlighted_old_text;
     let highlighted_new_text;
     if old_highlights || new_unhighlighted || count_lines(&old_tokens) != count_lines(&new_tokens) {
-        highlighted_old_text = render(&LINE_STYLE_OLD, old_tokens);
-        highlighted_new_text = render(&LINE_STYLE_NEW, new_tokens);
+        if config.unpaired_lines == UnpairedLines::Shaded {
+            highlighted_old_text = render_rows(
+                row_style(&old_unpaired, &LINE_STYLE_OLD, &LINE_STYLE_OLD_UNPAIRED),
+                &config.palette,
+                &config.theme,
+                old_tokens,
+            );
+            highlighted_new_text = render_rows(
+                row_style(&new_unpaired, &LINE_STYLE_NEW, &LINE_STYLE_NEW_UNPAIRED),
+                &config.palette,
+                &config.theme,
+                new_tokens,
+            );
+        } else {
+            highlighted_old_text =
+                render(&LINE_STYLE_OLD, &config.palette, &config.theme, old_tokens);
+            highlighted_new_text =
+                render(&LINE_STYLE_NEW, &config.palette, &config.theme, new_tokens);
+        }
     } else {
-        highlighted_old_text = render(&LINE_STYLE_OLD_FAINT, old_tokens);
-        highlighted_new_text = render(&LINE_STYLE_ADDS_ONLY, new_tokens);
+        highlighted_old_text = render(
+            &LINE_STYLE_OLD_FAINT,
+            &config.palette,
+            &config.theme,
+            old_tokens,
+        );
+        highlighted_new_text = render(
+            &LINE_STYLE_ADDS_ONLY,
+            &config.palette,
+            &config.theme,
+            new_tokens,
+        );
     }
 
-    return to_lines(&highlighted_old_text, &highlighted_new_text);
+    return to_lines(
+        &highlighted_old_text,
+        &highlighted_new_text,
+        &config.palette,
+    );
 }
 
-/// Returns two vectors for old and new sections. The first bool is true if
-/// there were any highlights found in the old text. The second bool is true if
-/// any highlights were removed for readability in the new text.
-pub fn to_highlighted_tokens(
-    old_text: &str,
-    new_text: &str,
-) -> (Vec<StyledToken>, Vec<StyledToken>, bool, bool) {
-    // Find diffs between adds and removals
-    let mut old_tokens = Vec::new();
-    let mut new_tokens = Vec::new();
+/// Pick the line style of each row by whether it has a counterpart
+fn row_style<'a>(
+    unpaired: &'a [bool],
+    paired_style: &'a LineStyle<'a>,
+    unpaired_style: &'a LineStyle<'a>,
+) -> impl Fn(usize) -> &'a LineStyle<'a> {
+    return move |row| {
+        if unpaired.get(row) == Some(&true) {
+            return unpaired_style;
+        }
+        return paired_style;
+    };
+}
 
-    // Tokenize adds and removes before diffing them
-    let mut tokenized_old = tokenizer::tokenize(old_text);
-    let mut tokenized_new = tokenizer::tokenize(new_text);
+/// Split `text` after each run of blank lines
+fn paragraphs(text: &str) -> Vec<&str> {
+    let mut paragraphs: Vec<&str> = Vec::new();
+    let mut start = 0;
+    let mut end = 0;
+    let mut previous_was_blank = false;
+    for line in text.split_inclusive('\n') {
+        let is_blank = line.trim().is_empty();
+        if previous_was_blank && !is_blank {
+            paragraphs.push(&text[start..end]);
+            start = end;
+        }
+        end += line.len();
+        previous_was_blank = is_blank;
+    }
+    paragraphs.push(&text[start..]);
 
-    // Help visualize what actually happens in "No newline at end of file" diffs
-    if old_text.ends_with('\n') && !new_text.ends_with('\n') {
-        tokenized_old.insert(tokenized_old.len() - 1, "⏎");
-    } else if new_text.ends_with('\n') && !old_text.ends_with('\n') {
-        tokenized_new.insert(tokenized_new.len() - 1, "⏎");
+    return paragraphs;
+}
+
+/// Split a block of changes into units to refine separately.
+///
+/// Context lines already end blocks. But when both the old and the new text
+/// have the same number of blank line separated paragraphs, those are most
+/// likely unrelated edits, and refining them one by one keeps the highlights
+/// of one edit from bleeding into the next.
+#[must_use]
+fn split_units<'a>(old_text: &'a str, new_text: &'a str) -> Vec<(&'a str, &'a str)> {
+    let old_paragraphs = paragraphs(old_text);
+    let new_paragraphs = paragraphs(new_text);
+    if old_paragraphs.len() != new_paragraphs.len() {
+        return vec![(old_text, new_text)];
     }
 
-    let diff = tokenized_old.diff(&tokenized_new);
+    return old_paragraphs.into_iter().zip(new_paragraphs).collect();
+}
+
+#[must_use]
+fn to_styled_tokens(tokens: &[&str], styles: Vec<Style>) -> Vec<StyledToken> {
+    return tokens
+        .iter()
+        .zip(styles)
+        .zip(classify(tokens))
+        .map(|((token, style), class)| {
+            let mut styled_token = StyledToken::new(token.to_string(), style);
+            styled_token.set_class(class);
+            return styled_token;
+        })
+        .collect();
+}
+
+/// Whitespace other than linefeeds. The tokenizer puts whitespace characters
+/// in tokens of their own.
+fn is_blank(token: &str) -> bool {
+    return token != "\n" && token.chars().all(char::is_whitespace);
+}
+
+/// How to compare tokens when diffing
+#[derive(Copy, Clone)]
+struct Comparison {
+    whitespace: Whitespace,
+
+    /// Compare indentation exactly, whatever `whitespace` says
+    significant_indentation: bool,
+}
+
+/// Picks which tokens to compare when diffing, based on how whitespace should
+/// be treated.
+///
+/// Returns the indices of the tokens to compare, and the tokens to compare in
+/// the same order. Tokens not taking part in the comparison will never be
+/// highlighted.
+#[must_use]
+fn comparison_keys<'a>(tokens: &[&'a str], comparison: Comparison) -> (Vec<usize>, Vec<&'a str>) {
+    let Comparison {
+        whitespace,
+        significant_indentation,
+    } = comparison;
+    let mut indices: Vec<usize> = Vec::with_capacity(tokens.len());
+    let mut keys: Vec<&str> = Vec::with_capacity(tokens.len());
+
+    let mut index = 0;
+    while index < tokens.len() {
+        let token = tokens[index];
+        let is_indentation = significant_indentation && (index == 0 || tokens[index - 1] == "\n");
+        if whitespace == Whitespace::Exact || is_indentation || !is_blank(token) {
+            indices.push(index);
+            keys.push(token);
+            index += 1;
+            continue;
+        }
+
+        let run_start = index;
+        while index < tokens.len() && is_blank(tokens[index]) {
+            index += 1;
+        }
+
+        let at_end_of_line = index == tokens.len() || tokens[index] == "\n";
+        if whitespace == Whitespace::IgnoreChange && !at_end_of_line {
+            // Any amount of whitespace compares equal to any other amount
+            indices.push(run_start);
+            keys.push(" ");
+        }
+    }
+
+    return (indices, keys);
+}
+
+/// Positions of the code tokens, or of the trailing comment tokens, plus the
+/// linefeeds so that lines stay apart
+#[must_use]
+fn part_positions(tokens: &[&str], comment_mask: &[bool], comment: bool) -> Vec<usize> {
+    return (0..tokens.len())
+        .filter(|index| comment_mask[*index] == comment || tokens[*index] == "\n")
+        .collect();
+}
+
+/// One step of getting from the old items to the new ones, see
+/// [`diff_edits`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Edit {
+    /// The next old and new items are the same
+    Same,
+
+    /// The next old item was removed
+    Removed,
+
+    /// The next new item was added
+    Added,
+}
+
+/// Diff `old` against `new`, tokens or lines, into the steps for getting from
+/// one to the other.
+///
+/// All diffing goes through here.
+#[must_use]
+pub fn diff_edits(old: &[&str], new: &[&str]) -> Vec<Edit> {
+    // Diffable is implemented for Vec, but not for slices
+    let old = old.to_vec();
+    let new = new.to_vec();
+    return match old.diff(&new) {
+        edit::Edit::Copy(_) => vec![Edit::Same; old.len()],
+        edit::Edit::Change(diff) => diff
+            .into_iter()
+            .map(|edit| match edit {
+                collection::Edit::Copy(_) => Edit::Same,
+                collection::Edit::Remove(_) => Edit::Removed,
+                collection::Edit::Insert(_) => Edit::Added,
+                collection::Edit::Change(_) => {
+                    unimplemented!("Edit/Change/Change not implemented, help!")
+                }
+            })
+            .collect(),
+    };
+}
+
+/// Diff the old tokens at `old_positions` against the new tokens at
+/// `new_positions`, and highlight the differences.
+///
+/// Returns true if any old token got highlighted.
+#[must_use]
+fn highlight_differences(
+    tokenized_old: &[&str],
+    old_positions: &[usize],
+    tokenized_new: &[&str],
+    new_positions: &[usize],
+    comparison: Comparison,
+    old_styles: &mut [Style],
+    new_styles: &mut [Style],
+) -> bool {
+    let old_part: Vec<&str> = old_positions
+        .iter()
+        .map(|index| tokenized_old[*index])
+        .collect();
+    let new_part: Vec<&str> = new_positions
+        .iter()
+        .map(|index| tokenized_new[*index])
+        .collect();
+    let (old_indices, old_keys) = comparison_keys(&old_part, comparison);
+    let (new_indices, new_keys) = comparison_keys(&new_part, comparison);
     let mut old_highlights = false;
-    match diff {
-        edit::Edit::Copy(tokens) => {
-            for &token in tokens {
-                // FIXME: "Copy" means that old and new are the same, why was
-                // format_split() called on this non-difference?
-                //
-                // Get here using "git show 686f3d7ae | cargo run" with git 2.35.1
-                old_tokens.push(StyledToken::new(token.to_string(), Style::Plain));
-                new_tokens.push(StyledToken::new(token.to_string(), Style::Plain));
+    let mut old_position = 0;
+    let mut new_position = 0;
+    for edit in diff_edits(&old_keys, &new_keys) {
+        match edit {
+            Edit::Same => {
+                old_position += 1;
+                new_position += 1;
+            }
+            Edit::Added => {
+                new_styles[new_positions[new_indices[new_position]]] = Style::Highlighted;
+                new_position += 1;
+            }
+            Edit::Removed => {
+                old_styles[old_positions[old_indices[old_position]]] = Style::Highlighted;
+                old_position += 1;
+                old_highlights = true;
             }
         }
-        edit::Edit::Change(diff) => {
-            diff.into_iter()
-                .map(|edit| {
-                    match edit {
-                        collection::Edit::Copy(token) => {
-                            old_tokens.push(StyledToken::new(token.to_string(), Style::Plain));
-                            new_tokens.push(StyledToken::new(token.to_string(), Style::Plain));
-                        }
-                        collection::Edit::Insert(token) => {
-                            new_tokens
-                                .push(StyledToken::new(token.to_string(), Style::Highlighted));
-                        }
-                        collection::Edit::Remove(token) => {
-                            old_tokens
-                                .push(StyledToken::new(token.to_string(), Style::Highlighted));
-                            old_highlights = true;
-                        }
-                        collection::Edit::Change(_) => {
-                            unimplemented!("Edit/Change/Change not implemented, help!")
-                        }
-                    };
-                })
-                .for_each(drop);
+    }
+
+    return old_highlights;
+}
+
+/// Refined old and new sections
+pub struct HighlightedTokens {
+    pub old_tokens: Vec<StyledToken>,
+    pub new_tokens: Vec<StyledToken>,
+
+    /// True if there were any highlights found in the old text
+    pub old_highlights: bool,
+
+    /// True if any highlights were removed for readability in the new text
+    pub new_unhighlighted: bool,
+
+    /// For each old and new line, true if it has no counterpart on the other
+    /// side. Always false when both sides have the same number of lines,
+    /// those get paired up one by one.
+    pub old_unpaired: Vec<bool>,
+    pub new_unpaired: Vec<bool>,
+}
+
+pub fn to_highlighted_tokens(
+    old_text: &str,
+    new_text: &str,
+    profile: Profile,
+    word_regex: Option<&Regex>,
+    config: &Config,
+) -> HighlightedTokens {
+    let profile = profile.for_contents(old_text, new_text);
+
+    // Tokenize adds and removes before diffing them
+    let mut token_cache = TokenCache::new(profile, word_regex);
+    let mut tokenized_old = token_cache.tokenize(old_text);
+    let mut tokenized_new = token_cache.tokenize(new_text);
+
+    if config.identifiers == Identifiers::Segments && profile.is_code() {
+        // Only source code has camelCase and snake_case identifiers
+        tokenized_old = split_identifiers(&tokenized_old);
+        tokenized_new = split_identifiers(&tokenized_new);
+    }
+
+    match newline_marker(ends_in_newline(old_text), ends_in_newline(new_text)) {
+        Some(NewlineMarker::Old) => tokenized_old.insert(tokenized_old.len() - 1, "⏎"),
+        Some(NewlineMarker::New) if new_text.ends_with('\n') => {
+            tokenized_new.insert(tokenized_new.len() - 1, "⏎");
         }
+        _ => {}
+    }
+
+    // Find diffs between adds and removals
+    let comparison = Comparison {
+        whitespace: config.whitespace,
+        significant_indentation: profile.has_significant_indentation(),
+    };
+    let mut old_styles = vec![Style::Plain; tokenized_old.len()];
+    let mut new_styles = vec![Style::Plain; tokenized_new.len()];
+    let old_highlights =
+        if config.trailing_comments == TrailingComments::Separately && profile.is_code() {
+            // Code and trailing comments are diffed separately, so that changes
+            // in one can't make highlights smear into the other
+            let old_mask = trailing_comment_mask(&tokenized_old);
+            let new_mask = trailing_comment_mask(&tokenized_new);
+            let code_highlights = highlight_differences(
+                &tokenized_old,
+                &part_positions(&tokenized_old, &old_mask, false),
+                &tokenized_new,
+                &part_positions(&tokenized_new, &new_mask, false),
+                comparison,
+                &mut old_styles,
+                &mut new_styles,
+            );
+            let comment_highlights = highlight_differences(
+                &tokenized_old,
+                &part_positions(&tokenized_old, &old_mask, true),
+                &tokenized_new,
+                &part_positions(&tokenized_new, &new_mask, true),
+                comparison,
+                &mut old_styles,
+                &mut new_styles,
+            );
+            code_highlights || comment_highlights
+        } else {
+            let old_positions: Vec<usize> = (0..tokenized_old.len()).collect();
+            let new_positions: Vec<usize> = (0..tokenized_new.len()).collect();
+            highlight_differences(
+                &tokenized_old,
+                &old_positions,
+                &tokenized_new,
+                &new_positions,
+                comparison,
+                &mut old_styles,
+                &mut new_styles,
+            )
+        };
+
+    let mut old_tokens = to_styled_tokens(&tokenized_old, old_styles);
+    let mut new_tokens = to_styled_tokens(&tokenized_new, new_styles);
+
+    let (old_unpaired, new_unpaired) = if count_lines(&old_tokens) == count_lines(&new_tokens) {
+        (
+            vec![false; count_lines(&old_tokens)],
+            vec![false; count_lines(&new_tokens)],
+        )
+    } else {
+        (unpaired_rows(&old_tokens), unpaired_rows(&new_tokens))
+    };
+
+    pair_highlighted_brackets(&mut old_tokens);
+    pair_highlighted_brackets(&mut new_tokens);
+    if config.spaces_between_highlights == SpacesBetweenHighlights::Highlight {
+        bridge_consecutive_highlighted_tokens(&mut old_tokens);
+        bridge_consecutive_highlighted_tokens(&mut new_tokens);
     }
 
-    bridge_consecutive_highlighted_tokens(&mut old_tokens);
-    unhighlight_noisy_rows(&mut old_tokens);
+    // Character highlights would cut through whole identifiers, YAML keys and
+    // CSV cells, which are meant to be highlighted as a whole
+    let narrow_characters =
+        profile == Profile::Default && config.identifiers == Identifiers::Segments;
+    let new_unhighlighted = match adapt_granularity(
+        &mut old_tokens,
+        &mut new_tokens,
+        config.noisy_lines,
+        narrow_characters,
+    ) {
+        Some(new_unhighlighted) => new_unhighlighted,
+        None if config.noisy_lines == NoisyLines::Keep => false,
+        None => {
+            // Lines can't be paired, judge each row on its own
+            unhighlight_noisy_rows(&mut old_tokens);
+            unhighlight_noisy_rows(&mut new_tokens)
+        }
+    };
+    if config.whitespace_errors != WhitespaceErrors::Off {
+        profile.highlight_whitespace_errors(&mut new_tokens);
+    }
 
-    bridge_consecutive_highlighted_tokens(&mut new_tokens);
-    let new_unhighlighted = unhighlight_noisy_rows(&mut new_tokens);
-    highlight_trailing_whitespace(&mut new_tokens);
-    highlight_nonleading_tabs(&mut new_tokens);
+    profile.post_process(&mut old_tokens, &mut new_tokens);
 
-    return (old_tokens, new_tokens, old_highlights, new_unhighlighted);
+    return HighlightedTokens {
+        old_tokens,
+        new_tokens,
+        old_highlights,
+        new_unhighlighted,
+        old_unpaired,
+        new_unpaired,
+    };
 }
 
 #[must_use]
-fn to_lines(old: &str, new: &str) -> Vec<String> {
+fn to_lines(old: &str, new: &str, palette: &Palette) -> Vec<String> {
     let mut lines: Vec<String> = Vec::new();
 
     for highlighted_old_line in old.lines() {
         lines.push(highlighted_old_line.to_string());
     }
     if (!old.is_empty()) && !old.ends_with('\n') {
-        let no_eof_newline_marker_guard = NO_EOF_NEWLINE_MARKER_HOLDER.lock().unwrap();
-        let no_eof_newline_marker = no_eof_newline_marker_guard.as_ref().unwrap();
+        let no_eof_newline_marker = eof_marker::get();
         lines.push(format!(
-            "{NO_EOF_NEWLINE_COLOR}{no_eof_newline_marker}{NORMAL}"
+            "{}{no_eof_newline_marker}{}",
+            palette.no_eof_newline, palette.normal
         ));
     }
 
@@ -189,10 +653,10 @@ fn to_lines(old: &str, new: &str) -> Vec<String> {
         lines.push(highlighted_new_line.to_string());
     }
     if (!new.is_empty()) && !new.ends_with('\n') {
-        let no_eof_newline_marker_guard = NO_EOF_NEWLINE_MARKER_HOLDER.lock().unwrap();
-        let no_eof_newline_marker = no_eof_newline_marker_guard.as_ref().unwrap();
+        let no_eof_newline_marker = eof_marker::get();
         lines.push(format!(
-            "{NO_EOF_NEWLINE_COLOR}{no_eof_newline_marker}{NORMAL}"
+            "{}{no_eof_newline_marker}{}",
+            palette.no_eof_newline, palette.normal
         ));
     }
 
@@ -202,6 +666,7 @@ fn to_lines(old: &str, new: &str) -> Vec<String> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::constants::*;
 
     #[cfg(test)]
     use pretty_assertions::assert_eq;
@@ -209,15 +674,18 @@ mod tests {
     #[test]
     fn test_simple_format_adds_and_removes() {
         let empty: Vec<String> = Vec::new();
-        assert_eq!(format_simple("", ""), empty);
+        assert_eq!(
+            format_simple(&Hunk::from_texts("", ""), &Palette::default()),
+            empty
+        );
 
         // Test adds-only
         assert_eq!(
-            format_simple("", "a\n"),
+            format_simple(&Hunk::from_texts("", "a\n"), &Palette::default()),
             ["".to_string() + NEW + "+a" + NORMAL]
         );
         assert_eq!(
-            format_simple("", "a\nb\n"),
+            format_simple(&Hunk::from_texts("", "a\nb\n"), &Palette::default()),
             [
                 "".to_string() + NEW + "+a" + NORMAL,
                 "".to_string() + NEW + "+b" + NORMAL,
@@ -226,11 +694,11 @@ mod tests {
 
         // Test removes-only
         assert_eq!(
-            format_simple("a\n", ""),
+            format_simple(&Hunk::from_texts("a\n", ""), &Palette::default()),
             ["".to_string() + OLD + "-a" + NORMAL]
         );
         assert_eq!(
-            format_simple("a\nb\n", ""),
+            format_simple(&Hunk::from_texts("a\nb\n", ""), &Palette::default()),
             [
                 "".to_string() + OLD + "-a" + NORMAL,
                 "".to_string() + OLD + "-b" + NORMAL,
@@ -238,14 +706,50 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_simple_format_newline_markers() {
+        let eof = format!("{NO_EOF_NEWLINE_COLOR}{}{NORMAL}", eof_marker::get());
+
+        // Lost newline, marked on the old side
+        assert_eq!(
+            format_simple(&Hunk::from_texts("a\n", "a"), &Palette::default()),
+            [
+                format!("{OLD}-a{INVERSE_VIDEO}⏎{NORMAL}"),
+                format!("{NEW}+a{NORMAL}"),
+                eof.clone(),
+            ]
+        );
+
+        // Gained newline, marked on the new side
+        assert_eq!(
+            format_simple(&Hunk::from_texts("a", "a\n"), &Palette::default()),
+            [
+                format!("{OLD}-a{NORMAL}"),
+                eof,
+                format!("{NEW}+a{INVERSE_VIDEO}⏎{NORMAL}"),
+            ]
+        );
+
+        // Both sides agree, nothing to mark
+        assert_eq!(
+            format_simple(&Hunk::from_texts("a", "b"), &Palette::default())[0],
+            format!("{OLD}-a{NORMAL}")
+        );
+    }
+
     #[test]
     fn test_quote_change() {
         // FIXME: Get this from somewhere else?
-        const NOT_INVERSE_VIDEO: &str = "\x1b[27m";
 
         let result = format(
-            "<unchanged text between quotes>\n",
-            "[unchanged text between quotes]\n",
+            &Hunk::from_texts(
+                "<unchanged text between quotes>\n",
+                "[unchanged text between quotes]\n",
+            ),
+            Profile::Default,
+            None,
+            true,
+            &Config::default(),
         );
         assert_eq!(
             result,
@@ -260,12 +764,258 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_character_granularity() {
+        let segments = Config {
+            identifiers: Identifiers::Segments,
+            ..Config::default()
+        };
+        let typo_fix = |profile: Profile, config: &Config| {
+            return format(
+                &Hunk::from_texts("let x = recieve(y);\n", "let x = receive(y);\n"),
+                profile,
+                None,
+                true,
+                config,
+            );
+        };
+
+        assert_eq!(
+            typo_fix(Profile::Default, &segments),
+            [
+                format!("{OLD}-let x = rec{INVERSE_VIDEO}ie{NOT_INVERSE_VIDEO}ve(y);{NORMAL}"),
+                format!("{NEW}+let x = rec{INVERSE_VIDEO}ei{NOT_INVERSE_VIDEO}ve(y);{NORMAL}"),
+            ]
+        );
+
+        // Whole identifiers, and other profiles, get whole tokens highlighted
+        let whole_tokens = [
+            format!("{OLD}-let x = {INVERSE_VIDEO}recieve{NOT_INVERSE_VIDEO}(y);{NORMAL}"),
+            format!("{NEW}+let x = {INVERSE_VIDEO}receive{NOT_INVERSE_VIDEO}(y);{NORMAL}"),
+        ];
+        assert_eq!(typo_fix(Profile::Default, &Config::default()), whole_tokens);
+        assert_eq!(typo_fix(Profile::Rust, &segments), whole_tokens);
+    }
+
+    #[test]
+    fn test_paragraphs() {
+        assert_eq!(paragraphs("a\n"), ["a\n"]);
+        assert_eq!(paragraphs("a\n\n \nb\nc"), ["a\n\n \n", "b\nc"]);
+        assert_eq!(paragraphs("\na\n\n"), ["\n", "a\n\n"]);
+    }
+
+    #[test]
+    fn test_split_units() {
+        assert_eq!(
+            split_units("a\n\nb\n", "A\n\nB\n"),
+            [("a\n\n", "A\n\n"), ("b\n", "B\n")]
+        );
+
+        // Different paragraph counts, don't guess which ones belong together
+        assert_eq!(split_units("a\n\nb\n", "A\nB\n"), [("a\n\nb\n", "A\nB\n")]);
+    }
+
+    #[test]
+    fn test_no_refine_rule() {
+        let config = Config::from_toml("[no-refine]\nhash = \"^hash: \"").unwrap();
+        let result = format(
+            &Hunk::from_texts("hash: abc\n", "hash: abd\n"),
+            Profile::Default,
+            None,
+            true,
+            &config,
+        );
+        assert_eq!(
+            result,
+            [
+                format!("{OLD}-hash: abc{NORMAL} {FAINT}not refined (matched rule hash){NORMAL}"),
+                format!("{NEW}+hash: abd{NORMAL}"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_comparison_keys() {
+        let tokens = ["a", " ", " ", "b", " ", "\n", "\t", "c"];
+        assert_eq!(
+            comparison_keys(
+                &tokens,
+                Comparison {
+                    whitespace: Whitespace::Exact,
+                    significant_indentation: false,
+                },
+            ),
+            (
+                vec![0, 1, 2, 3, 4, 5, 6, 7],
+                vec!["a", " ", " ", "b", " ", "\n", "\t", "c"]
+            )
+        );
+        assert_eq!(
+            comparison_keys(
+                &tokens,
+                Comparison {
+                    whitespace: Whitespace::IgnoreChange,
+                    significant_indentation: false,
+                },
+            ),
+            (vec![0, 1, 3, 5, 6, 7], vec!["a", " ", "b", "\n", " ", "c"])
+        );
+        assert_eq!(
+            comparison_keys(
+                &tokens,
+                Comparison {
+                    whitespace: Whitespace::IgnoreAll,
+                    significant_indentation: false,
+                },
+            ),
+            (vec![0, 3, 5, 7], vec!["a", "b", "\n", "c"])
+        );
+
+        // Indentation, but nothing else, is compared with significant indentation
+        assert_eq!(
+            comparison_keys(
+                &tokens,
+                Comparison {
+                    whitespace: Whitespace::IgnoreAll,
+                    significant_indentation: true,
+                },
+            ),
+            (vec![0, 3, 5, 6, 7], vec!["a", "b", "\n", "\t", "c"])
+        );
+    }
+
+    #[test]
+    fn test_ignore_whitespace() {
+        let config = Config {
+            whitespace: Whitespace::IgnoreAll,
+            ..Config::default()
+        };
+        let result = format(
+            &Hunk::from_texts("if (x) { y(); }\n", "if (x) {  y( ); }\n"),
+            Profile::Default,
+            None,
+            true,
+            &config,
+        );
+
+        // No highlights, and the new line is shown as is
+        assert_eq!(
+            result,
+            [
+                format!("{FAINT}{OLD}-if (x) {{ y(); }}{NORMAL}"),
+                format!("{FAINT}{NEW}+{NORMAL}if (x) {{  y( ); }}"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_whitespace_errors_off() {
+        let config = Config {
+            whitespace_errors: WhitespaceErrors::Off,
+            ..Config::default()
+        };
+        let result = format(
+            &Hunk::from_texts("a\n", "a  \n"),
+            Profile::Default,
+            None,
+            true,
+            &config,
+        );
+
+        // Markdown line breaks are not errors
+        assert_eq!(
+            result,
+            [
+                format!("{FAINT}{OLD}-a{NORMAL}"),
+                format!("{FAINT}{NEW}+{NORMAL}a{INVERSE_VIDEO}{NEW}  {NORMAL}"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unpaired_lines_shaded() {
+        let config = Config {
+            unpaired_lines: UnpairedLines::Shaded,
+            ..Config::default()
+        };
+        let result = format(
+            &Hunk::from_texts("a = 1\n", "a = 2\nb()\n"),
+            Profile::Default,
+            None,
+            true,
+            &config,
+        );
+        assert_eq!(
+            result,
+            [
+                format!("{OLD}-a = {INVERSE_VIDEO}1{NORMAL}"),
+                format!("{NEW}+a = {INVERSE_VIDEO}2{NORMAL}"),
+                format!("{NEW_UNPAIRED}+b(){NORMAL}"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_trailing_comments_separately() {
+        let old = "return a + b + c  # sum\n";
+        let new = "return total  # was a + b + c\n";
+
+        // Together, the old code lines up with the new comment
+        let result = format(
+            &Hunk::from_texts(old, new),
+            Profile::Default,
+            None,
+            true,
+            &Config::default(),
+        );
+        assert_eq!(
+            result,
+            [
+                format!("{OLD}-return a + b + c{INVERSE_VIDEO}  # sum{NORMAL}"),
+                format!(
+                    "{NEW}+return {INVERSE_VIDEO}total  # was {NOT_INVERSE_VIDEO}a + b + c{NORMAL}"
+                ),
+            ]
+        );
+
+        let config = Config {
+            trailing_comments: TrailingComments::Separately,
+            ..Config::default()
+        };
+        let result = format(
+            &Hunk::from_texts(old, new),
+            Profile::Default,
+            None,
+            true,
+            &config,
+        );
+        assert_eq!(
+            result,
+            [
+                format!("{OLD}-return {INVERSE_VIDEO}a + b + c{NOT_INVERSE_VIDEO}  # {INVERSE_VIDEO}sum{NORMAL}"),
+                format!("{NEW}+return {INVERSE_VIDEO}total{NOT_INVERSE_VIDEO}  # {INVERSE_VIDEO}was a + b + c{NORMAL}"),
+            ]
+        );
+    }
+
     #[test]
     fn test_almost_empty_changes() {
-        let result = format("x\n", "");
+        let result = format(
+            &Hunk::from_texts("x\n", ""),
+            Profile::Default,
+            None,
+            true,
+            &Config::default(),
+        );
         assert_eq!(result, [format!("{OLD}-x{NORMAL}"),]);
 
-        let result = format("", "x\n");
+        let result = format(
+            &Hunk::from_texts("", "x\n"),
+            Profile::Default,
+            None,
+            true,
+            &Config::default(),
+        );
         assert_eq!(result, [format!("{NEW}+x{NORMAL}"),]);
     }
 }
@@ -0,0 +1,158 @@
+use crate::palette::Palette;
+use regex::Regex;
+
+/// How `git apply --reject` ends the `diff` line of a `.rej` file. Those files
+/// have no `---` and `+++` lines, so the `diff` line is all that names the
+/// file.
+const REJECTED_HUNKS_SUFFIX: &str = "\t(rejected hunks)";
+
+lazy_static! {
+    /// `patch` and `git apply` lines saying something didn't apply
+    static ref FAILURE: Regex = Regex::new(concat!(
+        r"^(Hunk #\d+ FAILED at \d+.*",
+        r"|\d+ out of \d+ hunks? (?:FAILED|ignored)",
+        r"|Rejected hunk #\d+\.",
+        r"|error: patch failed: .*",
+        r"|error: .*: patch does not apply",
+        r"|error: .*: does not exist in index",
+        r"|error: .*: already exists in working directory)",
+        // From `patch`, where the hunks that didn't apply went
+        r"(?: -- saving rejects to file (?P<rej_file>.*))?$"
+    ))
+    .unwrap();
+
+    /// `patch` and `git apply` lines saying something did apply
+    static ref SUCCESS: Regex = Regex::new(concat!(
+        r"^(Hunk #\d+ succeeded at \d+.*",
+        r"|Hunk #\d+ applied cleanly\.",
+        r"|Applied patch .* cleanly\.",
+        r"|Checking patch .*\.\.\.)$"
+    ))
+    .unwrap();
+
+    /// `patch` and `git apply` lines naming the file that comes next
+    static ref FILE: Regex = Regex::new(
+        r"^(patching file .*|Applying patch .* with \d+ rejects?\.\.\.)$"
+    )
+    .unwrap();
+}
+
+/// The new file name of a `diff a/x.rs b/x.rs<TAB>(rejected hunks)` line, or
+/// `None` if this isn't one
+#[must_use]
+pub fn rejected_file_name(line: &str) -> Option<&str> {
+    let names = line
+        .strip_prefix("diff ")?
+        .strip_suffix(REJECTED_HUNKS_SUFFIX)?;
+    let new_name_start = names.rfind(" b/")? + 1;
+    return Some(&names[new_name_start..]);
+}
+
+/// Faint like other `diff` lines, but with the `(rejected hunks)` note
+/// standing out
+#[must_use]
+pub fn format_rejected_hunks_header(line: &str, palette: &Palette) -> Option<String> {
+    let _ = rejected_file_name(line)?;
+    let names = line.strip_suffix(REJECTED_HUNKS_SUFFIX)?;
+    return Some(format!(
+        "{}{names}{}\t{}{}(rejected hunks){}",
+        palette.faint, palette.normal, palette.bold, palette.old, palette.normal
+    ));
+}
+
+/// Color the outcome lines of `patch` and `git apply`, with failures in bold
+/// red and successes faint. Returns `None` if this isn't one of those.
+#[must_use]
+pub fn format_status(line: &str, palette: &Palette) -> Option<String> {
+    if let Some(captures) = FAILURE.captures(line) {
+        let failure = captures.get(1).unwrap().as_str();
+        let mut styled = format!("{}{}{failure}{}", palette.bold, palette.old, palette.normal);
+        if let Some(rej_file) = captures.name("rej_file") {
+            styled.push_str(&format!(
+                " -- saving rejects to file {}{}{}",
+                palette.bold,
+                rej_file.as_str(),
+                palette.normal
+            ));
+        }
+        return Some(styled);
+    }
+
+    if SUCCESS.is_match(line) {
+        return Some(format!("{}{line}{}", palette.faint, palette.normal));
+    }
+
+    if FILE.is_match(line) {
+        return Some(format!("{}{line}{}", palette.bold, palette.normal));
+    }
+
+    return None;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_rejected_file_name() {
+        assert_eq!(
+            rejected_file_name("diff a/src/x.rs b/src/x.rs\t(rejected hunks)"),
+            Some("b/src/x.rs")
+        );
+        assert_eq!(
+            rejected_file_name("diff a/sp ace.rs b/sp ace.rs\t(rejected hunks)"),
+            Some("b/sp ace.rs")
+        );
+        assert_eq!(rejected_file_name("diff --git a/x.rs b/x.rs"), None);
+    }
+
+    #[test]
+    fn test_format_status() {
+        let palette = Palette::default();
+        let failed = |text: &str| {
+            return format!("{}{}{text}{}", palette.bold, palette.old, palette.normal);
+        };
+
+        assert_eq!(
+            format_status("Hunk #2 FAILED at 33.", &palette),
+            Some(failed("Hunk #2 FAILED at 33."))
+        );
+        assert_eq!(
+            format_status(
+                "1 out of 2 hunks FAILED -- saving rejects to file src/x.c.rej",
+                &palette
+            ),
+            Some(format!(
+                "{} -- saving rejects to file {}src/x.c.rej{}",
+                failed("1 out of 2 hunks FAILED"),
+                palette.bold,
+                palette.normal
+            ))
+        );
+        assert_eq!(
+            format_status("error: patch failed: src/x.c:33", &palette),
+            Some(failed("error: patch failed: src/x.c:33"))
+        );
+        assert_eq!(
+            format_status("Hunk #1 succeeded at 12 (offset 2 lines).", &palette),
+            Some(format!(
+                "{}Hunk #1 succeeded at 12 (offset 2 lines).{}",
+                palette.faint, palette.normal
+            ))
+        );
+        assert_eq!(
+            format_status("patching file src/x.c", &palette),
+            Some(format!(
+                "{}patching file src/x.c{}",
+                palette.bold, palette.normal
+            ))
+        );
+
+        // The searched for lines that follow are file contents
+        assert_eq!(format_status("error: while searching for:", &palette), None);
+        assert_eq!(format_status("Hunk #1 is great", &palette), None);
+    }
+}
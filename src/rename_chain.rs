@@ -0,0 +1,73 @@
+/// Keeps track of files being renamed multiple times over a `git log -p
+/// --follow` session, so that we can show the whole chain of names rather
+/// than just the current rename.
+///
+/// `git log` goes newest-first, so renames usually get added at the start of a
+/// chain. With `git log --reverse` they get added at the end.
+pub struct RenameChains {
+    /// Each chain is a list of file names, oldest first
+    chains: Vec<Vec<String>>,
+}
+
+impl RenameChains {
+    pub fn new() -> RenameChains {
+        return RenameChains { chains: vec![] };
+    }
+
+    /// Record a rename, and return the chain it is part of
+    pub fn record(&mut self, from: &str, to: &str) -> &[String] {
+        for index in 0..self.chains.len() {
+            let chain = &mut self.chains[index];
+            if chain.first().map(String::as_str) == Some(to) {
+                // Newest-first: this is an earlier name for the same file
+                chain.insert(0, from.to_string());
+                return &self.chains[index];
+            }
+
+            if chain.last().map(String::as_str) == Some(from) {
+                // Oldest-first: this is a later name for the same file
+                chain.push(to.to_string());
+                return &self.chains[index];
+            }
+        }
+
+        self.chains.push(vec![from.to_string(), to.to_string()]);
+        return self.chains.last().unwrap();
+    }
+}
+
+/// Returns something like `"a.txt → b.txt → c.txt"`
+#[must_use]
+pub fn describe(chain: &[String]) -> String {
+    return chain.join(" → ");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_newest_first() {
+        let mut chains = RenameChains::new();
+        assert_eq!(describe(chains.record("b", "c")), "b → c");
+        assert_eq!(describe(chains.record("a", "b")), "a → b → c");
+    }
+
+    #[test]
+    fn test_oldest_first() {
+        let mut chains = RenameChains::new();
+        assert_eq!(describe(chains.record("a", "b")), "a → b");
+        assert_eq!(describe(chains.record("b", "c")), "a → b → c");
+    }
+
+    #[test]
+    fn test_unrelated_renames() {
+        let mut chains = RenameChains::new();
+        assert_eq!(describe(chains.record("b", "c")), "b → c");
+        assert_eq!(describe(chains.record("x", "y")), "x → y");
+        assert_eq!(describe(chains.record("a", "b")), "a → b → c");
+    }
+}
@@ -0,0 +1,53 @@
+use std::collections::HashSet;
+
+/// Keeps track of which files each commit of a `git log -p` session changes,
+/// so that files the previous commit also changed can be told apart.
+pub struct RepeatedFiles {
+    /// Files changed by the previous commit
+    previous: HashSet<String>,
+
+    /// Files changed by the current commit so far
+    current: HashSet<String>,
+}
+
+impl RepeatedFiles {
+    pub fn new() -> RepeatedFiles {
+        return RepeatedFiles {
+            previous: HashSet::new(),
+            current: HashSet::new(),
+        };
+    }
+
+    /// Call for each `commit` line
+    pub fn next_commit(&mut self) {
+        self.previous = std::mem::take(&mut self.current);
+    }
+
+    /// Record that the current commit changes `path`, and return true if the
+    /// previous commit changed it as well
+    pub fn record(&mut self, path: &str) -> bool {
+        let _ = self.current.insert(path.to_string());
+        return self.previous.contains(path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record() {
+        let mut files = RepeatedFiles::new();
+        assert!(!files.record("a.txt"));
+        assert!(!files.record("b.txt"));
+
+        files.next_commit();
+        assert!(files.record("a.txt"));
+        assert!(!files.record("c.txt"));
+
+        // Only the previous commit counts
+        files.next_commit();
+        assert!(!files.record("b.txt"));
+        assert!(files.record("c.txt"));
+    }
+}
@@ -0,0 +1,87 @@
+use crate::display_width::display_width;
+use std::process::{Command, Stdio};
+
+/// Section banners start with this. The line collector makes them bold.
+pub const BANNER_PREFIX: &str = "━━━ ";
+
+/// With a known terminal width, the banner line extends all the way across
+fn banner(title: &str, width: Option<usize>) -> Vec<u8> {
+    let mut banner = format!("{BANNER_PREFIX}{title} ");
+    let banner_width = display_width(&banner);
+    if let Some(width) = width {
+        banner.push_str(&"━".repeat(width.saturating_sub(banner_width)));
+    }
+    return format!("{}\n", banner.trim_end()).into_bytes();
+}
+
+/// Run a git command and return its output. Exit code 1 means "differences
+/// found" for `git diff`, so that's fine.
+pub fn git(args: &[&str]) -> Result<Vec<u8>, String> {
+    let output = Command::new("git")
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|error| format!("Running git failed: {error}"))?;
+
+    match output.status.code() {
+        Some(0) | Some(1) => return Ok(output.stdout),
+        _ => return Err(format!("git {} failed: {}", args.join(" "), output.status)),
+    }
+}
+
+/// Collect unstaged changes, staged changes and untracked files into one diff,
+/// with a banner before each non-empty section. Used by `riff diff --all`.
+///
+/// `args` are extra `git diff` options, like `-U5`. Untracked files are shown
+/// in full, so they don't get those.
+pub fn collect_all_changes(args: &[String], width: Option<usize>) -> Result<Vec<u8>, String> {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let mut collected: Vec<u8> = Vec::new();
+
+    let mut unstaged_args = vec!["diff"];
+    unstaged_args.extend(&args);
+    let unstaged = git(&unstaged_args)?;
+    if !unstaged.is_empty() {
+        collected.extend(banner("Unstaged changes", width));
+        collected.extend(unstaged);
+    }
+
+    let mut staged_args = vec!["diff", "--staged"];
+    staged_args.extend(&args);
+    let staged = git(&staged_args)?;
+    if !staged.is_empty() {
+        collected.extend(banner("Staged changes", width));
+        collected.extend(staged);
+    }
+
+    let untracked = git(&["ls-files", "--others", "--exclude-standard", "-z"])?;
+    let untracked = String::from_utf8_lossy(&untracked);
+    let mut untracked_banner_shown = false;
+    for path in untracked.split('\0').filter(|path| !path.is_empty()) {
+        if !untracked_banner_shown {
+            collected.extend(banner("Untracked files", width));
+            untracked_banner_shown = true;
+        }
+        collected.extend(git(&["diff", "--no-index", "--", "/dev/null", path])?);
+    }
+
+    return Ok(collected);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_banner() {
+        assert_eq!(banner("Staged", None), "━━━ Staged\n".as_bytes());
+        assert_eq!(banner("Staged", Some(14)), "━━━ Staged ━━━\n".as_bytes());
+
+        // Too narrow for any padding
+        assert_eq!(banner("Staged", Some(5)), "━━━ Staged\n".as_bytes());
+    }
+}
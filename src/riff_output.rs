@@ -0,0 +1,154 @@
+/// Escape code sequences that riff writes, but that git and other diff
+/// highlighters don't.
+const RIFF_SIGNATURES: [&[u8]; 3] = [
+    // Highlighted changed parts: inverse video first, then the color
+    b"\x1b[7m\x1b[31m",
+    b"\x1b[7m\x1b[32m",
+    // Added or removed newline at end of file
+    "\x1b[7m⏎".as_bytes(),
+];
+
+/// Faint lines start like this, which is nothing git does
+const RIFF_LINE_PREFIXES: [&[u8]; 2] = [b"\x1b[2m\x1b[31m-", b"\x1b[2m\x1b[32m+"];
+
+/// Ends inverse video, like riff does between highlighted parts. diff-highlight
+/// does the same, but git and diff-highlight end their lines with `ESC[m`
+/// where riff uses `ESC[0m`.
+const NOT_INVERSE_VIDEO: &[u8] = b"\x1b[27m";
+const RIFF_LINE_SUFFIX: &[u8] = b"\x1b[0m";
+const GIT_LINE_SUFFIX: &[u8] = b"\x1b[m";
+
+/// How many lines need to look like riff output before we believe the input as
+/// a whole is riff output. One line could just be a coincidence, or made up.
+const EVIDENCE_LINES: usize = 2;
+
+fn contains(line: &[u8], part: &[u8]) -> bool {
+    return line.windows(part.len()).any(|window| window == part);
+}
+
+/// True if this line looks like it came out of riff, with its escape codes
+/// still in place. See [`Detector`] for deciding about the whole input.
+#[must_use]
+fn is_riff_output(line: &[u8]) -> bool {
+    // Riff styles its lines from the very start, and git ends all its colored
+    // lines with ESC[m. This way, diffs of files containing riff output, like
+    // riff's own test data, aren't mistaken for riff output.
+    if line.first() != Some(&b'\x1b') || line.ends_with(GIT_LINE_SUFFIX) {
+        return false;
+    }
+
+    if RIFF_LINE_PREFIXES
+        .iter()
+        .any(|prefix| line.starts_with(prefix))
+    {
+        return true;
+    }
+
+    if line.ends_with(RIFF_LINE_SUFFIX) && contains(line, NOT_INVERSE_VIDEO) {
+        return true;
+    }
+
+    return RIFF_SIGNATURES
+        .iter()
+        .any(|signature| contains(line, signature));
+}
+
+/// True for diff lines riff would never have written: uncolored added and
+/// removed lines, and git colored ones
+fn is_not_riff_output(line: &[u8]) -> bool {
+    if line.starts_with(b"+") || line.starts_with(b"-") {
+        return true;
+    }
+
+    let text_start = crate::ansi::parts(&String::from_utf8_lossy(line)).find_map(Result::ok);
+    return matches!(text_start, Some('+' | '-')) && line.ends_with(GIT_LINE_SUFFIX);
+}
+
+/// Decides whether the input is riff's own output, for passing it through
+/// rather than highlighting it twice.
+///
+/// Takes [`EVIDENCE_LINES`] riff looking lines to decide, and any line riff
+/// wouldn't have written rules it out for the rest of the input.
+#[derive(Default)]
+pub struct Detector {
+    riff_lines: usize,
+    ruled_out: bool,
+}
+
+impl Detector {
+    /// True once enough input lines, including this one, have looked like riff
+    /// output
+    pub fn is_riff_output(&mut self, line: &[u8]) -> bool {
+        if self.ruled_out {
+            return false;
+        }
+        if is_not_riff_output(line) {
+            self.ruled_out = true;
+            return false;
+        }
+        if is_riff_output(line) {
+            self.riff_lines += 1;
+        }
+        return self.riff_lines >= EVIDENCE_LINES;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_riff_output() {
+        // Riff output
+        assert!(is_riff_output(
+            b"\x1b[32m+Hello, my \x1b[7m\x1b[32mfirst \x1b[0mname is Johan"
+        ));
+        assert!(is_riff_output(b"\x1b[2m\x1b[31m-Hello\x1b[0m"));
+        assert!(is_riff_output(
+            b"\x1b[32m+Some \x1b[7m**\x1b[27mtext\x1b[0m"
+        ));
+        assert!(is_riff_output("\x1b[31m-apor\x1b[7m⏎\x1b[0m".as_bytes()));
+
+        // Git output
+        assert!(!is_riff_output(b"\x1b[31m-Hello\x1b[m"));
+        assert!(!is_riff_output(b"\x1b[1mdiff --git a/x b/x\x1b[m"));
+
+        // Git output through diff-highlight
+        assert!(!is_riff_output(
+            b"\x1b[32m+Hello \x1b[7mfirst\x1b[27m name\x1b[m"
+        ));
+
+        // Diffs of riff output, without and with git colors
+        assert!(!is_riff_output(b"+\x1b[2m\x1b[31m-Hello\x1b[0m"));
+        assert!(!is_riff_output(
+            b"\x1b[32m+\x1b[m\x1b[32m\x1b[2m\x1b[31m-Hello\x1b[0m\x1b[m"
+        ));
+
+        // Not colored at all
+        assert!(!is_riff_output(b"+Hello"));
+    }
+
+    #[test]
+    fn test_detector() {
+        let riff_line: &[u8] = b"\x1b[2m\x1b[31m-Hello\x1b[0m";
+
+        let mut detector = Detector::default();
+        assert!(!detector.is_riff_output(b"\x1b[1m--- a/x\x1b[0m"));
+        assert!(!detector.is_riff_output(riff_line));
+        assert!(!detector.is_riff_output(b" context"));
+        assert!(detector.is_riff_output(riff_line));
+
+        // Git colored lines rule it out for good
+        let mut detector = Detector::default();
+        assert!(!detector.is_riff_output(riff_line));
+        assert!(!detector.is_riff_output(b"\x1b[32m+Hello\x1b[m"));
+        assert!(!detector.is_riff_output(riff_line));
+        assert!(!detector.is_riff_output(riff_line));
+
+        // So do uncolored ones
+        let mut detector = Detector::default();
+        assert!(!detector.is_riff_output(b"-Hello"));
+        assert!(!detector.is_riff_output(riff_line));
+        assert!(!detector.is_riff_output(riff_line));
+    }
+}
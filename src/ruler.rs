@@ -0,0 +1,85 @@
+use crate::ansi::parts;
+use crate::display_width::char_width;
+use crate::palette::Palette;
+use std::borrow::Cow;
+
+/// Mark where a highlighted added line crosses any of the `--ruler` columns,
+/// by changing the background of the first character past each of them.
+///
+/// Columns are counted without the leading `+`, so `--ruler 80` marks the
+/// 81st character of the line's contents. Lines that don't reach a ruler
+/// column are returned unchanged.
+#[must_use]
+pub fn mark_columns<'a>(line: &'a str, columns: &[usize], palette: &Palette) -> Cow<'a, str> {
+    if columns.is_empty() {
+        return Cow::Borrowed(line);
+    }
+
+    let mut marked = String::with_capacity(line.len() + 16);
+    let mut any_marks = false;
+
+    // Display column, where the `+` is in column zero
+    let mut column = 0;
+    for part in parts(line) {
+        let c = match part {
+            Err(escape) => {
+                marked.push_str(escape);
+                continue;
+            }
+            Ok(c) => c,
+        };
+
+        let c_width = char_width(c, column);
+        let is_marked = columns
+            .iter()
+            .any(|ruler| (column..column + c_width).contains(&(ruler + 1)));
+        if is_marked {
+            marked.push_str(&palette.ruler_background);
+            marked.push(c);
+            marked.push_str(&palette.default_background);
+            any_marks = true;
+        } else {
+            marked.push(c);
+        }
+        column += c_width;
+    }
+
+    if !any_marks {
+        return Cow::Borrowed(line);
+    }
+    return Cow::Owned(marked);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::*;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_mark_columns() {
+        let palette = Palette::default();
+        assert_eq!(mark_columns("+1234", &[4], &palette), "+1234");
+        assert_eq!(
+            mark_columns(&format!("{NEW}+12345{NORMAL}"), &[4], &palette),
+            format!("{NEW}+1234{RULER_BACKGROUND}5{DEFAULT_BACKGROUND}{NORMAL}")
+        );
+        assert_eq!(
+            mark_columns("+123456", &[2, 4], &palette),
+            format!("+12{RULER_BACKGROUND}3{DEFAULT_BACKGROUND}4{RULER_BACKGROUND}5{DEFAULT_BACKGROUND}6")
+        );
+    }
+
+    #[test]
+    fn test_wide_characters() {
+        let palette = Palette::default();
+
+        // The second 本 covers columns 3 and 4
+        assert_eq!(
+            mark_columns("+日本語", &[3], &palette),
+            format!("+日{RULER_BACKGROUND}本{DEFAULT_BACKGROUND}語")
+        );
+    }
+}
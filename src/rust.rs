@@ -0,0 +1,119 @@
+use crate::tokenizer;
+
+/// Operators that Rust programmers read as one symbol, longest first so that
+/// `<<=` wins over `<<`
+const OPERATORS: &[&str] = &[
+    "..=", "...", "<<=", ">>=", "::", "->", "=>", "==", "!=", "<=", ">=", "&&", "||", "+=", "-=",
+    "*=", "/=", "%=", "^=", "&=", "|=", "<<", ">>", "..",
+];
+
+fn is_word(token: &str) -> bool {
+    return token
+        .chars()
+        .next()
+        .is_some_and(|c| c == '_' || c.is_alphanumeric());
+}
+
+/// How many of the default tokens starting at `tokens[0]` make up one Rust
+/// token
+fn token_count(tokens: &[&str]) -> usize {
+    let first = tokens[0];
+    let second = tokens.get(1).copied().unwrap_or_default();
+    let third = tokens.get(2).copied();
+
+    // The default tokenizer makes each operator character a token of its own
+    let operator = OPERATORS.iter().find(|operator| {
+        return operator.len() <= tokens.len()
+            && operator
+                .chars()
+                .zip(tokens)
+                .all(|(c, token)| token.len() == 1 && token.starts_with(c));
+    });
+    if let Some(operator) = operator {
+        return operator.len();
+    }
+
+    // Lifetimes like 'a and 'static, but not char literals like 'a'
+    if first == "'" && is_word(second) && third != Some("'") {
+        return 2;
+    }
+
+    // Raw identifiers like r#type, but not raw strings like r#"text"#
+    if first == "r" && second == "#" && third.is_some_and(is_word) {
+        return 3;
+    }
+
+    return 1;
+}
+
+/// Tokenize Rust so that operators like `::`, `+=` and `..=`, lifetimes like
+/// `'a` and raw identifiers like `r#type` are single tokens.
+///
+/// This way a changed operator is highlighted as a whole, like `+=` becoming
+/// `-=`, and so is a changed lifetime.
+pub fn tokenize(input: &str) -> Vec<&str> {
+    let tokens = tokenizer::tokenize(input);
+    let mut result: Vec<&str> = Vec::with_capacity(tokens.len());
+
+    // The default tokens cover the input back to back, so merged tokens are
+    // slices of the input
+    let mut byte_index = 0;
+    let mut token_index = 0;
+    while token_index < tokens.len() {
+        let count = token_count(&tokens[token_index..]);
+        let length: usize = tokens[token_index..token_index + count]
+            .iter()
+            .map(|token| token.len())
+            .sum();
+        result.push(&input[byte_index..byte_index + length]);
+        byte_index += length;
+        token_index += count;
+    }
+
+    return result;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(
+            tokenize("std::io::Result<()>"),
+            ["std", "::", "io", "::", "Result", "<", "(", ")", ">"]
+        );
+        assert_eq!(
+            tokenize("fn f() -> u8 { x => 1 }"),
+            [
+                "fn", " ", "f", "(", ")", " ", "->", " ", "u8", " ", "{", " ", "x", " ", "=>", " ",
+                "1", " ", "}"
+            ]
+        );
+        assert_eq!(tokenize("a = >b"), ["a", " ", "=", " ", ">", "b"]);
+    }
+
+    #[test]
+    fn test_tokenize_operators() {
+        assert_eq!(tokenize("x+=1"), ["x", "+=", "1"]);
+        assert_eq!(
+            tokenize("a==b&&c!=d"),
+            ["a", "==", "b", "&&", "c", "!=", "d"]
+        );
+        assert_eq!(tokenize("x<<=2"), ["x", "<<=", "2"]);
+        assert_eq!(tokenize("0..=n"), ["0", "..=", "n"]);
+        assert_eq!(tokenize("0..n"), ["0", "..", "n"]);
+        assert_eq!(tokenize("a||b"), ["a", "||", "b"]);
+    }
+
+    #[test]
+    fn test_tokenize_lifetimes_and_raw_identifiers() {
+        assert_eq!(tokenize("&'static str"), ["&", "'static", " ", "str"]);
+        assert_eq!(tokenize("'a'"), ["'", "a", "'"]);
+        assert_eq!(tokenize("r#type"), ["r#type"]);
+        assert_eq!(tokenize("r#\"x\"#"), ["r", "#", "\"", "x", "\"", "#"]);
+    }
+}
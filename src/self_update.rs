@@ -0,0 +1,241 @@
+use sha2::{Digest, Sha256};
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+const RELEASES_URL: &str = "https://github.com/walles/riff/releases";
+
+/// Run `curl` on `url` with `extra_args`, following redirects. Returns what
+/// it printed.
+fn run_curl(url: &str, extra_args: &[&str]) -> Result<Vec<u8>, String> {
+    let output = Command::new("curl")
+        .args(["--fail", "--silent", "--show-error", "--location"].iter())
+        .args(extra_args.iter())
+        .arg(url)
+        .output()
+        .map_err(|error| format!("Running curl failed, is it installed? {error}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Downloading {url} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    return Ok(output.stdout);
+}
+
+/// Download a URL using `curl`
+fn curl(url: &str) -> Result<Vec<u8>, String> {
+    return run_curl(url, &[]);
+}
+
+/// GitHub redirects `releases/latest` to `releases/tag/<version>`
+fn latest_version() -> Result<String, String> {
+    let output = run_curl(
+        &format!("{RELEASES_URL}/latest"),
+        &["--output", "/dev/null", "--write-out", "%{url_effective}"],
+    )?;
+    let url = String::from_utf8_lossy(&output).to_string();
+    return url
+        .rsplit_once("/tag/")
+        .map(|(_, version)| version.to_string())
+        .ok_or(format!("Latest release not found: {url}"));
+}
+
+/// Version numbers of `2.25.1`, or of a build after it like
+/// `2.25.1-3-g0123abc`. `None` if there are no version numbers.
+fn parse_version(version: &str) -> Option<Vec<u64>> {
+    let release = version.split('-').next()?;
+    return release
+        .split('.')
+        .map(|number| number.parse::<u64>().ok())
+        .collect();
+}
+
+/// True if `latest` is a strictly newer version than `current`. Anything we
+/// can't make sense of is an error, rather than a reason to update.
+fn is_newer(latest: &str, current: &str) -> Result<bool, String> {
+    let latest_numbers =
+        parse_version(latest).ok_or(format!("Can't parse latest version: {latest}"))?;
+    let current_numbers =
+        parse_version(current).ok_or(format!("Can't parse current version: {current}"))?;
+    return Ok(latest_numbers > current_numbers);
+}
+
+/// Release binary names, as created by `release.sh`
+fn asset_name(version: &str, os: &str, arch: &str) -> Result<String, String> {
+    return match (os, arch) {
+        ("macos", _) => Ok(format!("riff-{version}-universal-macos")),
+        ("linux", "x86_64") => Ok(format!("riff-{version}-x86_64-linux")),
+        ("windows", "x86_64") => Ok(format!("riff-{version}-x86_64-windows.exe")),
+        _ => Err(format!(
+            "No release binaries for {os} on {arch}, please install riff some other way"
+        )),
+    };
+}
+
+/// Find the checksum for `name` in `sha256sum` style output
+fn find_checksum<'a>(checksums: &'a str, name: &str) -> Option<&'a str> {
+    for line in checksums.lines() {
+        if let Some((checksum, file_name)) = line.split_once(char::is_whitespace) {
+            // A leading "*" means the file was checksummed in binary mode
+            if file_name.trim_start().trim_start_matches('*') == name {
+                return Some(checksum);
+            }
+        }
+    }
+    return None;
+}
+
+/// Replace `executable` with `contents`, keeping the old one around until the
+/// new one is in place.
+///
+/// Windows can't remove running executables, so there the old one stays
+/// behind as `riff.old` until the next update removes it.
+fn replace_executable(executable: &Path, contents: &[u8]) -> Result<(), String> {
+    let directory = executable
+        .parent()
+        .ok_or("Can't find the directory of the riff executable")?;
+
+    // Left over from the previous update, and not running any more
+    let old_file = executable.with_extension("old");
+    if old_file.exists() {
+        fs::remove_file(&old_file)
+            .map_err(|error| format!("{}: {error}", old_file.to_string_lossy()))?;
+    }
+
+    // Same directory for an atomic rename into place
+    let new_file = tempfile::NamedTempFile::new_in(directory)
+        .map_err(|error| format!("{}: {error}", directory.to_string_lossy()))?;
+    fs::write(new_file.path(), contents).map_err(|error| error.to_string())?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(new_file.path(), fs::Permissions::from_mode(0o755))
+            .map_err(|error| error.to_string())?;
+    }
+
+    // Windows can't replace running executables, but it can rename them
+    fs::rename(executable, &old_file).map_err(|error| error.to_string())?;
+    if let Err(error) = new_file.persist(executable) {
+        let _ = fs::rename(&old_file, executable);
+        return Err(error.to_string());
+    }
+    let _ = fs::remove_file(&old_file);
+
+    return Ok(());
+}
+
+/// Replace the running executable with the latest release from GitHub, if
+/// that is newer than `current_version`.
+///
+/// The download is checked against the release's `SHA256SUMS`. That catches
+/// broken downloads, but it comes from the same release as the binary, so it
+/// doesn't protect against a tampered with release. For that we rely on HTTPS
+/// and on GitHub.
+///
+/// Returns a message to show the user.
+pub fn self_update(current_version: &str) -> Result<String, String> {
+    let version = latest_version()?;
+    if !is_newer(&version, current_version)? {
+        return Ok(format!(
+            "riff {current_version} is up to date, the latest release is {version}"
+        ));
+    }
+
+    let name = asset_name(&version, env::consts::OS, env::consts::ARCH)?;
+    let checksums = curl(&format!("{RELEASES_URL}/download/{version}/SHA256SUMS"))?;
+    let checksums = String::from_utf8_lossy(&checksums);
+    let expected = find_checksum(&checksums, &name)
+        .ok_or(format!("No checksum for {name} in release {version}"))?;
+
+    let contents = curl(&format!("{RELEASES_URL}/download/{version}/{name}"))?;
+    let actual = format!("{:x}", Sha256::digest(&contents));
+    if !actual.eq_ignore_ascii_case(expected) {
+        return Err(format!(
+            "Checksum mismatch for {name}, expected {expected} but got {actual}"
+        ));
+    }
+
+    let executable = env::current_exe().map_err(|error| error.to_string())?;
+    replace_executable(&executable, &contents)?;
+
+    return Ok(format!(
+        "Updated {} from riff {current_version} to {version}",
+        executable.to_string_lossy()
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_asset_name() {
+        assert_eq!(
+            asset_name("2.25.1", "linux", "x86_64"),
+            Ok("riff-2.25.1-x86_64-linux".to_string())
+        );
+        assert_eq!(
+            asset_name("2.25.1", "macos", "aarch64"),
+            Ok("riff-2.25.1-universal-macos".to_string())
+        );
+        assert!(asset_name("2.25.1", "linux", "riscv64").is_err());
+    }
+
+    #[test]
+    fn test_is_newer() {
+        assert_eq!(is_newer("2.25.1", "2.25.0"), Ok(true));
+        assert_eq!(is_newer("2.25.1", "2.3.9"), Ok(true));
+        assert_eq!(is_newer("2.25.1", "2.25"), Ok(true));
+        assert_eq!(is_newer("2.25.1", "2.25.1"), Ok(false));
+        assert_eq!(is_newer("2.25.1", "2.25.1-3-g0123abc"), Ok(false));
+        assert_eq!(is_newer("2.25.1", "2.25.1-dirty"), Ok(false));
+
+        // No downgrades
+        assert_eq!(is_newer("2.25.0", "2.25.1"), Ok(false));
+
+        assert!(is_newer("2.25.1", "0123abc").is_err());
+        assert!(is_newer("<html>", "2.25.1").is_err());
+    }
+
+    #[test]
+    fn test_find_checksum() {
+        let checksums = "0123abcd  riff-2.25.1-x86_64-linux\nfedc *riff-2.25.1-universal-macos\n";
+        assert_eq!(
+            find_checksum(checksums, "riff-2.25.1-x86_64-linux"),
+            Some("0123abcd")
+        );
+        assert_eq!(
+            find_checksum(checksums, "riff-2.25.1-universal-macos"),
+            Some("fedc")
+        );
+        assert_eq!(
+            find_checksum(checksums, "riff-2.25.1-x86_64-windows.exe"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_replace_executable() {
+        let directory = tempfile::tempdir().unwrap();
+        let executable = directory.path().join("riff");
+        fs::write(&executable, "old").unwrap();
+
+        replace_executable(&executable, b"new").unwrap();
+
+        assert_eq!(fs::read_to_string(&executable).unwrap(), "new");
+        assert_eq!(fs::read_dir(directory.path()).unwrap().count(), 1);
+
+        // Left over from an earlier update that couldn't remove it
+        fs::write(directory.path().join("riff.old"), "older").unwrap();
+        replace_executable(&executable, b"newer").unwrap();
+
+        assert_eq!(fs::read_to_string(&executable).unwrap(), "newer");
+        assert_eq!(fs::read_dir(directory.path()).unwrap().count(), 1);
+    }
+}
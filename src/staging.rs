@@ -0,0 +1,462 @@
+use crate::review;
+use std::io::{self, BufRead, Write};
+use std::ops::Range;
+use std::process::{Command, Stdio};
+
+/// Context lines shown around each part of a split hunk
+const SPLIT_CONTEXT: usize = 3;
+
+const HELP: &str = "\
+y - stage this hunk
+n - do not stage this hunk
+s - split this hunk into smaller hunks
+q - quit, staging the hunks selected so far
+? - print help
+";
+
+/// One file of a `git diff`
+struct FilePatch {
+    /// The `diff`, `index`, `---` and `+++` lines
+    header: String,
+
+    hunks: Vec<Hunk>,
+}
+
+struct Hunk {
+    old_start: usize,
+    new_start: usize,
+
+    /// Whatever comes after the closing `@@`, usually a function name
+    heading: String,
+
+    /// Lines after the `@@` line, with their newlines
+    lines: Vec<String>,
+}
+
+/// Parse a `@@ -1,2 +3,4 @@ heading` line, without the newline, into its
+/// start lines and its heading
+fn parse_hunk_header(line: &str) -> Option<(usize, usize, &str)> {
+    let rest = line.strip_prefix("@@ -")?;
+    let (ranges, heading) = rest.split_once(" @@")?;
+    let (old_range, new_range) = ranges.split_once(" +")?;
+    let start = |range: &str| range.split(',').next()?.parse::<usize>().ok();
+    return Some((start(old_range)?, start(new_range)?, heading));
+}
+
+fn is_old_line(line: &str) -> bool {
+    return line.starts_with('-') || line.starts_with(' ') || line == "\n";
+}
+
+fn is_new_line(line: &str) -> bool {
+    return line.starts_with('+') || line.starts_with(' ') || line == "\n";
+}
+
+fn is_change(line: &str) -> bool {
+    return line.starts_with('-') || line.starts_with('+');
+}
+
+/// Old and new line counts, for a `@@` line
+fn line_counts(lines: &[String]) -> (usize, usize) {
+    let old_count = lines.iter().filter(|line| is_old_line(line)).count();
+    let new_count = lines.iter().filter(|line| is_new_line(line)).count();
+    return (old_count, new_count);
+}
+
+impl Hunk {
+    /// Runs of changed lines, with any `\ No newline` lines following them.
+    /// These are what a hunk gets split into.
+    fn change_groups(&self) -> Vec<Range<usize>> {
+        let mut groups: Vec<Range<usize>> = Vec::new();
+        let mut group_start: Option<usize> = None;
+        for (index, line) in self.lines.iter().enumerate() {
+            let continues_group = is_change(line) || line.starts_with('\\');
+            match (group_start, continues_group) {
+                (None, true) if is_change(line) => group_start = Some(index),
+                (Some(start), false) => {
+                    groups.push(start..index);
+                    group_start = None;
+                }
+                _ => {}
+            }
+        }
+        if let Some(start) = group_start {
+            groups.push(start..self.lines.len());
+        }
+        return groups;
+    }
+
+    /// The `@@` line and the lines in `range`, as a hunk of its own
+    fn render(&self, range: Range<usize>) -> String {
+        let (old_before, new_before) = line_counts(&self.lines[..range.start]);
+        let (old_count, new_count) = line_counts(&self.lines[range.clone()]);
+        let mut rendered = format!(
+            "@@ -{},{old_count} +{},{new_count} @@{}\n",
+            self.old_start + old_before,
+            self.new_start + new_before,
+            self.heading
+        );
+        for line in &self.lines[range] {
+            rendered.push_str(line);
+        }
+        return rendered;
+    }
+
+    /// The lines to stage, with the changes in groups that weren't selected
+    /// undone: Their removed lines become context lines, and their added
+    /// lines are left out.
+    fn staged_lines(&self, groups: &[Range<usize>], selected: &[bool]) -> Vec<String> {
+        let mut lines: Vec<String> = Vec::with_capacity(self.lines.len());
+        let mut dropped_previous = false;
+        for (index, line) in self.lines.iter().enumerate() {
+            let group = groups.iter().position(|group| group.contains(&index));
+            let is_selected = group.is_none_or(|group| selected[group]);
+
+            if line.starts_with('\\') {
+                // "\ No newline at end of file" belongs to the line before it
+                if !dropped_previous {
+                    lines.push(line.clone());
+                }
+                continue;
+            }
+
+            dropped_previous = false;
+            if is_selected {
+                lines.push(line.clone());
+            } else if let Some(removed) = line.strip_prefix('-') {
+                lines.push(format!(" {removed}"));
+            } else if line.starts_with('+') {
+                dropped_previous = true;
+            } else {
+                lines.push(line.clone());
+            }
+        }
+        return lines;
+    }
+}
+
+/// Split `git diff` output into files and hunks. Files without hunks, like
+/// binary files, are left out.
+fn parse_diff(diff: &str) -> Vec<FilePatch> {
+    let mut files: Vec<FilePatch> = Vec::new();
+    let mut in_header = false;
+    for line in diff.split_inclusive('\n') {
+        if line.starts_with("diff ") {
+            files.push(FilePatch {
+                header: String::new(),
+                hunks: Vec::new(),
+            });
+            in_header = true;
+        }
+        let file = match files.last_mut() {
+            Some(file) => file,
+            None => continue,
+        };
+
+        if let Some((old_start, new_start, heading)) =
+            parse_hunk_header(line.trim_end_matches('\n'))
+        {
+            file.hunks.push(Hunk {
+                old_start,
+                new_start,
+                heading: heading.to_string(),
+                lines: Vec::new(),
+            });
+            in_header = false;
+            continue;
+        }
+
+        if in_header {
+            file.header.push_str(line);
+        } else if let Some(hunk) = file.hunks.last_mut() {
+            hunk.lines.push(line.to_string());
+        }
+    }
+
+    files.retain(|file| !file.hunks.is_empty());
+    return files;
+}
+
+/// A patch with the selected change groups of every hunk, for `git apply
+/// --cached`. `selections` has one `Vec<bool>` per hunk, with one entry per
+/// change group.
+fn staged_patch(files: &[FilePatch], selections: &[Vec<Vec<bool>>]) -> String {
+    let mut patch = String::new();
+    for (file, file_selections) in files.iter().zip(selections) {
+        if !file_selections.iter().flatten().any(|selected| *selected) {
+            continue;
+        }
+        patch.push_str(&file.header);
+
+        // How far the new line numbers have moved from the old ones, because
+        // of the hunks staged so far
+        let mut offset: isize = 0;
+        for (hunk, selected) in file.hunks.iter().zip(file_selections) {
+            if !selected.iter().any(|selected| *selected) {
+                continue;
+            }
+
+            let lines = hunk.staged_lines(&hunk.change_groups(), selected);
+            let (old_count, new_count) = line_counts(&lines);
+            let new_start = (hunk.old_start as isize + offset).max(0);
+            patch.push_str(&format!(
+                "@@ -{},{old_count} +{new_start},{new_count} @@{}\n",
+                hunk.old_start, hunk.heading
+            ));
+            for line in lines {
+                patch.push_str(&line);
+            }
+            offset += new_count as isize - old_count as isize;
+        }
+    }
+    return patch;
+}
+
+enum Answer {
+    Yes,
+    No,
+    Split,
+    Quit,
+    Help,
+}
+
+/// Prompt until we get an answer. End of input means quit.
+fn ask(input: &mut dyn BufRead, output: &mut dyn Write) -> io::Result<Answer> {
+    loop {
+        write!(output, "Stage this hunk [y,n,s,q,?]? ")?;
+        output.flush()?;
+
+        let mut answer = String::new();
+        if input.read_line(&mut answer)? == 0 {
+            writeln!(output)?;
+            return Ok(Answer::Quit);
+        }
+        match answer.trim().to_lowercase().as_str() {
+            "y" => return Ok(Answer::Yes),
+            "n" => return Ok(Answer::No),
+            "s" => return Ok(Answer::Split),
+            "q" => return Ok(Answer::Quit),
+            "?" => return Ok(Answer::Help),
+            _ => {}
+        }
+    }
+}
+
+/// Which lines of a hunk to show for the change groups in `unit`, with up to
+/// `SPLIT_CONTEXT` lines of context that aren't part of other groups
+fn unit_lines(hunk: &Hunk, groups: &[Range<usize>], unit: &Range<usize>) -> Range<usize> {
+    let start = if unit.start == 0 {
+        0
+    } else {
+        let first = &groups[unit.start];
+        first
+            .start
+            .saturating_sub(SPLIT_CONTEXT)
+            .max(groups[unit.start - 1].end)
+    };
+    let end = if unit.end == groups.len() {
+        hunk.lines.len()
+    } else {
+        let last = &groups[unit.end - 1];
+        (last.end + SPLIT_CONTEXT).min(groups[unit.end].start)
+    };
+    return start..end;
+}
+
+/// Ask about each hunk of `diff`, and return the patch to stage.
+///
+/// `highlight(text, with_header)` highlights a diff, with or without its file
+/// header lines.
+fn select_hunks(
+    diff: &str,
+    highlight: &dyn Fn(&str, bool) -> String,
+    input: &mut dyn BufRead,
+    output: &mut dyn Write,
+) -> io::Result<String> {
+    let files = parse_diff(diff);
+    let mut selections: Vec<Vec<Vec<bool>>> = files
+        .iter()
+        .map(|file| {
+            return file
+                .hunks
+                .iter()
+                .map(|hunk| vec![false; hunk.change_groups().len()])
+                .collect();
+        })
+        .collect();
+
+    'files: for (file_index, file) in files.iter().enumerate() {
+        let mut header_shown = false;
+        for (hunk_index, hunk) in file.hunks.iter().enumerate() {
+            let groups = hunk.change_groups();
+
+            // Start with the whole hunk, splitting replaces it with one unit
+            // per change group
+            let mut units: Vec<Range<usize>> = std::iter::once(0..groups.len()).collect();
+            let mut unit_index = 0;
+            while unit_index < units.len() {
+                let unit = units[unit_index].clone();
+                let text = format!(
+                    "{}{}",
+                    file.header,
+                    hunk.render(unit_lines(hunk, &groups, &unit))
+                );
+                write!(output, "{}", highlight(&text, !header_shown))?;
+                header_shown = true;
+
+                match ask(input, output)? {
+                    Answer::Yes => {
+                        for group in unit {
+                            selections[file_index][hunk_index][group] = true;
+                        }
+                    }
+                    Answer::No => {}
+                    Answer::Split if unit.len() > 1 => {
+                        let parts: Vec<Range<usize>> = unit.map(|group| group..group + 1).collect();
+                        writeln!(output, "Split into {} hunks.", parts.len())?;
+                        let _ = units.splice(unit_index..=unit_index, parts);
+                        continue;
+                    }
+                    Answer::Split => {
+                        writeln!(output, "Sorry, cannot split this hunk.")?;
+                        continue;
+                    }
+                    Answer::Quit => break 'files,
+                    Answer::Help => {
+                        write!(output, "{HELP}")?;
+                        continue;
+                    }
+                }
+                unit_index += 1;
+            }
+        }
+    }
+
+    return Ok(staged_patch(&files, &selections));
+}
+
+/// `git apply --cached` the patch
+fn apply_to_index(patch: &str) -> Result<(), String> {
+    let mut git = Command::new("git")
+        .args(["apply", "--cached", "-"])
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|error| format!("Running git apply failed: {error}"))?;
+
+    let mut stdin = git.stdin.take().unwrap();
+    stdin
+        .write_all(patch.as_bytes())
+        .map_err(|error| format!("Writing to git apply failed: {error}"))?;
+    drop(stdin);
+
+    let status = git
+        .wait()
+        .map_err(|error| format!("Waiting for git apply failed: {error}"))?;
+    if !status.success() {
+        return Err(format!("git apply --cached failed: {status}"));
+    }
+    return Ok(());
+}
+
+/// `riff add`: Show each hunk of `git diff <args>` highlighted, ask which ones
+/// to stage, and stage those. Like `git add -p`.
+pub fn add_interactively(
+    args: &[String],
+    highlight: &dyn Fn(&str, bool) -> String,
+) -> Result<(), String> {
+    let mut diff_args = vec!["diff", "--no-color", "--no-ext-diff"];
+    diff_args.extend(args.iter().map(String::as_str));
+    let diff = review::git(&diff_args)?;
+    let diff = String::from_utf8_lossy(&diff);
+    if diff.is_empty() {
+        println!("No changes.");
+        return Ok(());
+    }
+
+    let patch = select_hunks(&diff, highlight, &mut io::stdin().lock(), &mut io::stdout())
+        .map_err(|error| format!("Asking about hunks failed: {error}"))?;
+    if patch.is_empty() {
+        return Ok(());
+    }
+    return apply_to_index(&patch);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    const DIFF: &str = concat!(
+        "diff --git a/x.txt b/x.txt\n",
+        "index 1234567..89abcde 100644\n",
+        "--- a/x.txt\n",
+        "+++ b/x.txt\n",
+        "@@ -1,5 +1,5 @@ heading\n",
+        "-one\n",
+        "+ONE\n",
+        " two\n",
+        " three\n",
+        "-four\n",
+        "+FOUR\n",
+        " five\n",
+        "@@ -10 +10,2 @@\n",
+        " ten\n",
+        "+eleven\n",
+    );
+
+    const HEADER: &str = concat!(
+        "diff --git a/x.txt b/x.txt\n",
+        "index 1234567..89abcde 100644\n",
+        "--- a/x.txt\n",
+        "+++ b/x.txt\n",
+    );
+
+    fn select(answers: &str) -> String {
+        let highlight = |text: &str, _| text.to_string();
+        let mut output: Vec<u8> = Vec::new();
+        return select_hunks(DIFF, &highlight, &mut answers.as_bytes(), &mut output).unwrap();
+    }
+
+    #[test]
+    fn test_select_whole_hunks() {
+        assert_eq!(select("y\ny\n"), DIFF.replace("@@ -10 ", "@@ -10,1 "));
+        assert_eq!(select("n\nn\n"), "");
+        assert_eq!(select("q\n"), "");
+
+        // Skipping the first hunk moves the second one back up
+        assert_eq!(
+            select("n\ny\n"),
+            format!("{HEADER}@@ -10,1 +10,2 @@\n ten\n+eleven\n")
+        );
+    }
+
+    #[test]
+    fn test_select_split_hunk() {
+        // Split the first hunk, and stage only its second change
+        assert_eq!(
+            select("s\nn\ny\nn\n"),
+            format!("{HEADER}@@ -1,5 +1,5 @@ heading\n one\n two\n three\n-four\n+FOUR\n five\n")
+        );
+
+        // Stage only the first change, then the second hunk
+        assert_eq!(
+            select("s\ny\nn\ny\n"),
+            format!(
+                "{HEADER}@@ -1,5 +1,5 @@ heading\n-one\n+ONE\n two\n three\n four\n five\n@@ -10,1 +10,2 @@\n ten\n+eleven\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_render_split_part() {
+        let files = parse_diff(DIFF);
+        let hunk = &files[0].hunks[0];
+        let groups = hunk.change_groups();
+        assert_eq!(groups, [0..2, 4..6]);
+        assert_eq!(
+            hunk.render(unit_lines(hunk, &groups, &(1..2))),
+            "@@ -2,4 +2,4 @@ heading\n two\n three\n-four\n+FOUR\n five\n"
+        );
+    }
+}
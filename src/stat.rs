@@ -0,0 +1,206 @@
+use crate::palette::Palette;
+use regex::Regex;
+
+lazy_static! {
+    /// ` 3 files changed, 10 insertions(+), 2 deletions(-)`, from `git diff
+    /// --shortstat` and at the end of `git diff --stat`
+    static ref SHORTSTAT: Regex = Regex::new(
+        r"^ \d+ files? changed(?P<insertions>, \d+ insertions?\(\+\))?(?P<deletions>, \d+ deletions?\(-\))?$"
+    )
+    .unwrap();
+}
+
+/// Width of the bar after the percentage in `similarity index` lines
+const SIMILARITY_BAR_WIDTH: usize = 10;
+
+/// One line of `git diff --numstat` output
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NumstatRow {
+    added: String,
+    removed: String,
+    path: String,
+}
+
+/// Parse `12<TAB>3<TAB>src/main.rs` into a row. Binary files have `-` for
+/// their counts.
+#[must_use]
+pub fn parse_numstat(line: &str) -> Option<NumstatRow> {
+    let mut columns = line.splitn(3, '\t');
+    let added = columns.next()?;
+    let removed = columns.next()?;
+    let path = columns.next()?;
+
+    let is_count = |count: &str| {
+        count == "-" || (!count.is_empty() && count.chars().all(|c| c.is_ascii_digit()))
+    };
+    if !is_count(added) || !is_count(removed) || path.is_empty() {
+        return None;
+    }
+
+    return Some(NumstatRow {
+        added: added.to_string(),
+        removed: removed.to_string(),
+        path: path.to_string(),
+    });
+}
+
+/// Format consecutive numstat rows into a table with aligned, colored count
+/// columns. Returns one line per row.
+#[must_use]
+pub fn format_numstat(rows: &[NumstatRow], palette: &Palette) -> Vec<String> {
+    let added_width = rows.iter().map(|row| row.added.len()).max().unwrap_or(0);
+    let removed_width = rows.iter().map(|row| row.removed.len()).max().unwrap_or(0);
+
+    return rows
+        .iter()
+        .map(|row| {
+            format!(
+                "{}{:>added_width$}{}  {}{:>removed_width$}{}  {}",
+                palette.new,
+                row.added,
+                palette.normal,
+                palette.old,
+                row.removed,
+                palette.normal,
+                row.path,
+            )
+        })
+        .collect();
+}
+
+/// Color the counts of a `git diff --shortstat` line, or return `None` if
+/// this isn't one.
+#[must_use]
+pub fn format_shortstat(line: &str, palette: &Palette) -> Option<String> {
+    let captures = SHORTSTAT.captures(line)?;
+
+    let mut styled = String::with_capacity(line.len() + 20);
+    let mut end_of_previous = 0;
+    for (name, color) in [("insertions", &palette.new), ("deletions", &palette.old)].iter() {
+        if let Some(found) = captures.name(name) {
+            // Leave the leading ", " unstyled
+            let start = found.start() + 2;
+            styled.push_str(&line[end_of_previous..start]);
+            styled.push_str(color);
+            styled.push_str(&line[start..found.end()]);
+            styled.push_str(&palette.normal);
+            end_of_previous = found.end();
+        }
+    }
+    styled.push_str(&line[end_of_previous..]);
+
+    return Some(styled);
+}
+
+/// Show the percentage of a `similarity index 92%` line as a bar, colored by
+/// how similar the files are. Returns `None` if this isn't one.
+#[must_use]
+pub fn format_similarity(line: &str, palette: &Palette) -> Option<String> {
+    let percentage = line.strip_prefix("similarity index ")?;
+    let percent: usize = percentage.strip_suffix('%')?.parse().ok()?;
+    if percent > 100 {
+        return None;
+    }
+
+    // From the most similar to the least similar
+    let color = match percent {
+        91..=100 => &palette.similarity[0],
+        50..=90 => &palette.similarity[1],
+        _ => &palette.similarity[2],
+    };
+    let filled = (percent * SIMILARITY_BAR_WIDTH + 50) / 100;
+
+    return Some(format!(
+        "{}similarity index {}{color}{percentage} {}{}{}{}{}",
+        palette.faint,
+        palette.normal,
+        "█".repeat(filled),
+        palette.normal,
+        palette.faint,
+        "░".repeat(SIMILARITY_BAR_WIDTH - filled),
+        palette.normal,
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::*;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_parse_numstat() {
+        assert_eq!(
+            parse_numstat("12\t3\tsrc/main.rs"),
+            Some(NumstatRow {
+                added: "12".to_string(),
+                removed: "3".to_string(),
+                path: "src/main.rs".to_string(),
+            })
+        );
+        assert!(parse_numstat("-\t-\tscreenshot.png").is_some());
+        assert!(parse_numstat("12\t3").is_none());
+        assert!(parse_numstat("12\tx\tpath").is_none());
+        assert!(parse_numstat("+\tadded with a tab").is_none());
+    }
+
+    #[test]
+    fn test_format_numstat() {
+        let rows = [
+            parse_numstat("120\t3\tsrc/main.rs").unwrap(),
+            parse_numstat("-\t-\tscreenshot.png").unwrap(),
+        ];
+        assert_eq!(
+            format_numstat(&rows, &Palette::default()),
+            [
+                format!("{NEW}120{NORMAL}  {OLD}3{NORMAL}  src/main.rs"),
+                format!("{NEW}  -{NORMAL}  {OLD}-{NORMAL}  screenshot.png"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_format_shortstat() {
+        let palette = Palette::default();
+        assert_eq!(
+            format_shortstat(
+                " 3 files changed, 10 insertions(+), 1 deletion(-)",
+                &palette
+            ),
+            Some(format!(
+                " 3 files changed, {NEW}10 insertions(+){NORMAL}, {OLD}1 deletion(-){NORMAL}"
+            ))
+        );
+        assert_eq!(
+            format_shortstat(" 1 file changed, 1 deletion(-)", &palette),
+            Some(format!(" 1 file changed, {OLD}1 deletion(-){NORMAL}"))
+        );
+        assert_eq!(format_shortstat("3 files changed", &palette), None);
+    }
+
+    #[test]
+    fn test_format_similarity() {
+        let palette = Palette::default();
+        assert_eq!(
+            format_similarity("similarity index 92%", &palette),
+            Some(format!(
+                "{FAINT}similarity index {NORMAL}{GREEN}92% █████████{NORMAL}{FAINT}░{NORMAL}"
+            ))
+        );
+        assert_eq!(
+            format_similarity("similarity index 50%", &palette),
+            Some(format!(
+                "{FAINT}similarity index {NORMAL}{YELLOW}50% █████{NORMAL}{FAINT}░░░░░{NORMAL}"
+            ))
+        );
+        assert_eq!(
+            format_similarity("similarity index 3%", &palette),
+            Some(format!(
+                "{FAINT}similarity index {NORMAL}{RED}3% {NORMAL}{FAINT}░░░░░░░░░░{NORMAL}"
+            ))
+        );
+        assert_eq!(format_similarity("similarity index x%", &palette), None);
+    }
+}
@@ -0,0 +1,125 @@
+use std::fs::File;
+use std::io::{self, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+/// Writes the highlighted output both to the pager and to the `--output` file.
+///
+/// Quitting the pager early doesn't stop the file from getting the whole diff.
+/// And if writing the file fails, we say so once and keep paging.
+pub struct Tee<W: Write> {
+    output: Option<W>,
+    file: Option<File>,
+    path: PathBuf,
+}
+
+impl<W: Write> Tee<W> {
+    /// Exits with an error message if the file can't be created
+    pub fn new(output: W, path: &Path) -> Tee<W> {
+        let file = match File::create(path) {
+            Ok(file) => file,
+            Err(error) => {
+                eprintln!("ERROR: Creating {}: {error}", path.display());
+                std::process::exit(1);
+            }
+        };
+
+        return Tee {
+            output: Some(output),
+            file: Some(file),
+            path: path.to_path_buf(),
+        };
+    }
+}
+
+impl<W: Write> Write for Tee<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_all(buf)?;
+        return Ok(buf.len());
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        if let Some(file) = &mut self.file {
+            if let Err(error) = file.write_all(buf) {
+                eprintln!("WARNING: Writing {}: {error}", self.path.display());
+                self.file = None;
+            }
+        }
+
+        if let Some(output) = &mut self.output {
+            if let Err(error) = output.write_all(buf) {
+                if error.kind() != ErrorKind::BrokenPipe || self.file.is_none() {
+                    return Err(error);
+                }
+
+                // The pager is gone, but the file still wants the rest
+                self.output = None;
+            }
+        }
+
+        if self.output.is_none() && self.file.is_none() {
+            return Err(io::Error::from(ErrorKind::BrokenPipe));
+        }
+        return Ok(());
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if let Some(file) = &mut self.file {
+            file.flush()?;
+        }
+        if let Some(output) = &mut self.output {
+            if let Err(error) = output.flush() {
+                if error.kind() != ErrorKind::BrokenPipe || self.file.is_none() {
+                    return Err(error);
+                }
+                self.output = None;
+            }
+        }
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    /// Like a pager that has been quit
+    struct Closed;
+
+    impl Write for Closed {
+        fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+            return Err(io::Error::from(ErrorKind::BrokenPipe));
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            return Ok(());
+        }
+    }
+
+    #[test]
+    fn test_tee() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let mut output: Vec<u8> = Vec::new();
+        let mut tee = Tee::new(&mut output, file.path());
+        tee.write_all(b"hello\n").unwrap();
+        tee.flush().unwrap();
+        drop(tee);
+
+        assert_eq!(output, b"hello\n");
+        assert_eq!(fs::read_to_string(file.path()).unwrap(), "hello\n");
+    }
+
+    #[test]
+    fn test_file_outlives_pager() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let mut tee = Tee::new(Closed, file.path());
+        tee.write_all(b"a\n").unwrap();
+        tee.write_all(b"b\n").unwrap();
+        drop(tee);
+
+        assert_eq!(fs::read_to_string(file.path()).unwrap(), "a\nb\n");
+    }
+}
@@ -0,0 +1,42 @@
+use crate::file_timestamp;
+/// OSC 2 sequence setting the terminal window title to show which file we're
+/// at, from a `---` or `+++` file name like `b/src/main.rs`. Terminal
+/// multiplexers like tmux show this in their window lists.
+#[must_use]
+pub fn title_sequence(file_name: &str) -> String {
+    // Leave out timestamps, and the "b/" prefix git adds
+    let path = file_timestamp::strip(file_name);
+    let path = path
+        .strip_prefix("a/")
+        .or_else(|| path.strip_prefix("b/"))
+        .unwrap_or(path);
+
+    // A BEL or ESC in the file name would end the sequence early
+    let path: String = path.chars().filter(|c| !c.is_control()).collect();
+
+    return format!("\x1b]2;riff: {path}\x07");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_title_sequence() {
+        assert_eq!(
+            title_sequence("b/src/main.rs"),
+            "\x1b]2;riff: src/main.rs\x07"
+        );
+        assert_eq!(
+            title_sequence("x.txt\t2021-01-01 12:00:00"),
+            "\x1b]2;riff: x.txt\x07"
+        );
+        assert_eq!(
+            title_sequence("b/evil\x07\x1b[2Jname"),
+            "\x1b]2;riff: evil[2Jname\x07"
+        );
+    }
+}
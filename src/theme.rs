@@ -0,0 +1,119 @@
+use crate::ansi::{AnsiStyle, Weight};
+use crate::token_style::{TokenContext, TokenStyleRule, TokenStyler};
+use crate::tokenizer::TokenClass;
+use std::collections::HashMap;
+
+/// Themes that come with riff, by name. The token classes not listed are
+/// styled normally.
+const BUILT_IN_THEMES: &[(&str, &[(TokenClass, Weight)])] = &[
+    ("plain", &[]),
+    (
+        "faint-punctuation",
+        &[(TokenClass::Punctuation, Weight::Faint)],
+    ),
+    (
+        "contrast",
+        &[
+            (TokenClass::Identifier, Weight::Bold),
+            (TokenClass::Punctuation, Weight::Faint),
+            (TokenClass::String, Weight::Faint),
+        ],
+    ),
+];
+
+/// How to style unchanged tokens depending on their class. Changed tokens are
+/// always highlighted the same way, so that changes stand out.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Theme {
+    class_weights: HashMap<TokenClass, Weight>,
+
+    /// From `[theme.rules]`, these go before the class weights
+    rules: Vec<TokenStyleRule>,
+}
+
+impl Theme {
+    /// The theme that comes with riff by this name, if any
+    #[must_use]
+    pub fn built_in(name: &str) -> Option<Theme> {
+        return Self::built_in_themes()
+            .find(|(candidate, _)| *candidate == name)
+            .map(|(_, theme)| theme);
+    }
+
+    /// All themes that come with riff, by name
+    pub fn built_in_themes() -> impl Iterator<Item = (&'static str, Theme)> {
+        return BUILT_IN_THEMES.iter().map(|(name, class_weights)| {
+            let theme = Theme {
+                class_weights: class_weights.iter().copied().collect(),
+                rules: Vec::new(),
+            };
+            return (*name, theme);
+        });
+    }
+
+    pub fn set_weight(&mut self, class: TokenClass, weight: Weight) {
+        self.class_weights.insert(class, weight);
+    }
+
+    pub fn add_rule(&mut self, rule: TokenStyleRule) {
+        self.rules.push(rule);
+    }
+
+    /// True if no token gets any special styling
+    #[must_use]
+    pub fn is_plain(&self) -> bool {
+        return self.class_weights.is_empty() && self.rules.is_empty();
+    }
+
+    /// Adjust the style of an unchanged token based on the rules and its class
+    #[must_use]
+    pub fn style(&self, style: AnsiStyle, token: &str, class: TokenClass) -> AnsiStyle {
+        if !self.rules.is_empty() {
+            let context = TokenContext { class };
+            let style_override = self
+                .rules
+                .iter()
+                .find_map(|rule| rule.style(token, &context));
+            if let Some(style_override) = style_override {
+                return AnsiStyle {
+                    weight: style_override.weight,
+                    ..style
+                };
+            }
+        }
+
+        return match self.class_weights.get(&class) {
+            Some(weight) => AnsiStyle {
+                weight: *weight,
+                ..style
+            },
+            None => style,
+        };
+    }
+}
+
+/// Parse a weight from the config file
+#[must_use]
+pub fn parse_weight(name: &str) -> Option<Weight> {
+    return match name {
+        "normal" => Some(Weight::Normal),
+        "bold" => Some(Weight::Bold),
+        "faint" => Some(Weight::Faint),
+        _ => None,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_built_in() {
+        assert_eq!(Theme::built_in("plain"), Some(Theme::default()));
+        assert!(!Theme::built_in("contrast").unwrap().is_plain());
+        assert_eq!(Theme::built_in("monkey"), None);
+    }
+}
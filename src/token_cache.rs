@@ -0,0 +1,107 @@
+use crate::profile::Profile;
+use crate::tokenizer;
+use regex::Regex;
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// Tokenizes text line by line, tokenizing each distinct line only once.
+///
+/// Diffs of generated code repeat lines like `});` and license headers
+/// thousands of times. Use one cache per refinement, for both the old and the
+/// new text.
+pub struct TokenCache<'a> {
+    profile: Profile,
+
+    /// From the `[word-regex]` config section, tokenizes instead of the
+    /// profile
+    word_regex: Option<&'a Regex>,
+
+    /// Tokens of every distinct line seen so far, back to back
+    tokens: Vec<&'a str>,
+
+    /// Where in `tokens` the tokens of each line are
+    lines: HashMap<&'a str, Range<usize>>,
+}
+
+impl<'a> TokenCache<'a> {
+    #[must_use]
+    pub fn new(profile: Profile, word_regex: Option<&'a Regex>) -> TokenCache<'a> {
+        return TokenCache {
+            profile,
+            word_regex,
+            tokens: Vec::new(),
+            lines: HashMap::new(),
+        };
+    }
+
+    /// Same result as [`Profile::tokenize`] or [`tokenizer::tokenize_words`],
+    /// all of those tokenize lines independently of each other
+    #[must_use]
+    pub fn tokenize(&mut self, input: &'a str) -> Vec<&'a str> {
+        let mut result: Vec<&'a str> = Vec::with_capacity(input.len());
+        for line in input.split_inclusive('\n') {
+            let range = match self.lines.get(line) {
+                Some(range) => range.clone(),
+                None => {
+                    let start = self.tokens.len();
+                    match self.word_regex {
+                        Some(word_regex) => {
+                            self.tokens
+                                .extend(tokenizer::tokenize_words(line, word_regex));
+                        }
+                        None => self.tokens.extend(self.profile.tokenize(line)),
+                    }
+                    let range = start..self.tokens.len();
+                    self.lines.insert(line, range.clone());
+                    range
+                }
+            };
+            result.extend_from_slice(&self.tokens[range]);
+        }
+
+        return result;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_same_as_uncached() {
+        let text = "  });\n  x: \"a b\"\n**bold**\n  });\n  });";
+        for profile in [
+            Profile::Default,
+            Profile::Rust,
+            Profile::Python,
+            Profile::Yaml,
+            Profile::Json,
+            Profile::Markdown,
+        ]
+        .iter()
+        {
+            let mut cache = TokenCache::new(*profile, None);
+            assert_eq!(cache.tokenize(text), profile.tokenize(text));
+            assert_eq!(cache.tokenize("  });\n"), profile.tokenize("  });\n"));
+        }
+
+        let word_regex = Regex::new(r"\S+").unwrap();
+        let mut cache = TokenCache::new(Profile::Rust, Some(&word_regex));
+        assert_eq!(
+            cache.tokenize(text),
+            tokenizer::tokenize_words(text, &word_regex)
+        );
+    }
+
+    #[test]
+    fn test_tokenizes_repeated_lines_once() {
+        let mut cache = TokenCache::new(Profile::Default, None);
+        let _ = cache.tokenize("});\n});\n});\n");
+        let _ = cache.tokenize("});\nx\n");
+        assert_eq!(cache.lines.len(), 2);
+        assert_eq!(cache.tokens, ["}", ")", ";", "\n", "x", "\n"]);
+    }
+}
@@ -1,15 +1,27 @@
 use crate::ansi::AnsiStyle;
+use crate::ansi::Color::DarkGreen;
+use crate::ansi::Color::DarkRed;
 use crate::ansi::Color::Default;
 use crate::ansi::Color::Green;
 use crate::ansi::Color::Red;
 use crate::ansi::Weight;
 use crate::ansi::ANSI_STYLE_NORMAL;
+use crate::config::NoisyLines;
+use crate::file_timestamp;
+use crate::hunk::CR_MARKER;
+use crate::palette::Palette;
+use crate::theme::Theme;
+use crate::tokenizer::TokenClass;
+use std::ops::Range;
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Style {
     Plain,
     Highlighted,
     Error,
+
+    /// Like `Error`, but for problems in lines that didn't change
+    Warning,
     Lowlighted,
 }
 
@@ -17,6 +29,7 @@ pub enum Style {
 pub struct StyledToken {
     token: String,
     style: Style,
+    class: TokenClass,
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -48,6 +61,28 @@ pub const LINE_STYLE_OLD: LineStyle = {
     }
 };
 
+/// Removed lines without a counterpart, with `unpaired-lines = "shaded"`
+pub const LINE_STYLE_OLD_UNPAIRED: LineStyle = {
+    LineStyle {
+        prefix: "-",
+        prefix_style: AnsiStyle {
+            inverse: false,
+            weight: Weight::Normal,
+            color: DarkRed,
+        },
+        plain_style: AnsiStyle {
+            inverse: false,
+            weight: Weight::Normal,
+            color: DarkRed,
+        },
+        highlighted_style: AnsiStyle {
+            inverse: true,
+            weight: Weight::Normal,
+            color: Red,
+        },
+    }
+};
+
 pub const LINE_STYLE_OLD_FAINT: LineStyle = {
     LineStyle {
         prefix: "-",
@@ -90,6 +125,28 @@ pub const LINE_STYLE_NEW: LineStyle = {
     }
 };
 
+/// Added lines without a counterpart, with `unpaired-lines = "shaded"`
+pub const LINE_STYLE_NEW_UNPAIRED: LineStyle = {
+    LineStyle {
+        prefix: "+",
+        prefix_style: AnsiStyle {
+            inverse: false,
+            weight: Weight::Normal,
+            color: DarkGreen,
+        },
+        plain_style: AnsiStyle {
+            inverse: false,
+            weight: Weight::Normal,
+            color: DarkGreen,
+        },
+        highlighted_style: AnsiStyle {
+            inverse: true,
+            weight: Weight::Normal,
+            color: Green,
+        },
+    }
+};
+
 pub const LINE_STYLE_ADDS_ONLY: LineStyle = {
     LineStyle {
         prefix: "+",
@@ -111,6 +168,15 @@ pub const LINE_STYLE_ADDS_ONLY: LineStyle = {
     }
 };
 
+pub const LINE_STYLE_CONTEXT: LineStyle = {
+    LineStyle {
+        prefix: " ",
+        prefix_style: ANSI_STYLE_NORMAL,
+        plain_style: ANSI_STYLE_NORMAL,
+        highlighted_style: ANSI_STYLE_NORMAL,
+    }
+};
+
 pub const LINE_STYLE_OLD_FILENAME: LineStyle = {
     LineStyle {
         prefix: "--- ",
@@ -153,9 +219,74 @@ pub const LINE_STYLE_NEW_FILENAME: LineStyle = {
     }
 };
 
+pub const LINE_STYLE_COPY_FROM: LineStyle = {
+    LineStyle {
+        prefix: "copy from ",
+        prefix_style: AnsiStyle {
+            inverse: false,
+            weight: Weight::Faint,
+            color: Default,
+        },
+        plain_style: AnsiStyle {
+            inverse: false,
+            weight: Weight::Faint,
+            color: Default,
+        },
+        highlighted_style: AnsiStyle {
+            inverse: true,
+            weight: Weight::Normal,
+            color: Red,
+        },
+    }
+};
+
+pub const LINE_STYLE_COPY_TO: LineStyle = {
+    LineStyle {
+        prefix: "copy to ",
+        prefix_style: AnsiStyle {
+            inverse: false,
+            weight: Weight::Bold,
+            color: Default,
+        },
+        plain_style: AnsiStyle {
+            inverse: false,
+            weight: Weight::Bold,
+            color: Default,
+        },
+        highlighted_style: AnsiStyle {
+            inverse: true,
+            weight: Weight::Bold,
+            color: Green,
+        },
+    }
+};
+
 impl StyledToken {
     pub fn new(token: String, style: Style) -> StyledToken {
-        return StyledToken { token, style };
+        let class = TokenClass::of(&token);
+        return StyledToken {
+            token,
+            style,
+            class,
+        };
+    }
+
+    pub fn token(&self) -> &str {
+        return &self.token;
+    }
+
+    pub fn style(&self) -> Style {
+        return self.style;
+    }
+
+    pub fn set_style(&mut self, style: Style) {
+        self.style = style;
+    }
+
+    /// Override the class guessed by [`StyledToken::new`], for tokens that
+    /// need context to be classified
+    pub fn set_class(&mut self, class: TokenClass) {
+        self.class = class;
     }
 
     pub fn is_whitespace(&self) -> bool {
@@ -171,27 +302,37 @@ impl StyledToken {
     }
 }
 
-#[must_use]
-fn render_row(line_style: &LineStyle, row: &[StyledToken]) -> String {
-    let mut rendered = String::new();
-
+fn render_row(
+    line_style: &LineStyle,
+    palette: &Palette,
+    theme: &Theme,
+    row: &[StyledToken],
+    rendered: &mut String,
+) {
     let mut current_style = ANSI_STYLE_NORMAL;
 
     // Render prefix
-    rendered.push_str(&line_style.prefix_style.from(&current_style));
+    line_style
+        .prefix_style
+        .push_from(&current_style, palette, rendered);
     current_style = line_style.prefix_style;
     rendered.push_str(line_style.prefix);
 
     // Render tokens
     for token in row {
         let new_style = match token.style {
-            Style::Plain => line_style.plain_style,
+            Style::Plain => theme.style(line_style.plain_style, &token.token, token.class),
             Style::Highlighted => line_style.highlighted_style,
             Style::Error => AnsiStyle {
                 inverse: true,
                 weight: Weight::Normal,
                 color: Red,
             },
+            Style::Warning => AnsiStyle {
+                inverse: true,
+                weight: Weight::Faint,
+                color: Default,
+            },
             Style::Lowlighted => AnsiStyle {
                 inverse: false,
                 weight: Weight::Faint,
@@ -199,36 +340,74 @@ fn render_row(line_style: &LineStyle, row: &[StyledToken]) -> String {
             },
         };
 
-        rendered.push_str(&new_style.from(&current_style));
+        new_style.push_from(&current_style, palette, rendered);
         current_style = new_style;
         rendered.push_str(&token.token);
     }
 
     // Reset formatting at the end of the line
-    rendered.push_str(&ANSI_STYLE_NORMAL.from(&current_style));
-
-    return rendered;
+    ANSI_STYLE_NORMAL.push_from(&current_style, palette, rendered);
 }
 
 /// Render all the tokens into a (most of the time multiline) string
 #[must_use]
-pub fn render(line_style: &LineStyle, tokens: Vec<StyledToken>) -> String {
-    let mut rendered = String::new();
+pub fn render(
+    line_style: &LineStyle,
+    palette: &Palette,
+    theme: &Theme,
+    tokens: Vec<StyledToken>,
+) -> String {
+    return render_rows(|_| line_style, palette, theme, tokens);
+}
+
+/// Like [`render`], but with the line style of each row picked by its index
+#[must_use]
+pub fn render_rows<'a, F>(
+    row_style: F,
+    palette: &Palette,
+    theme: &Theme,
+    tokens: Vec<StyledToken>,
+) -> String
+where
+    F: Fn(usize) -> &'a LineStyle<'a>,
+{
+    // Twice the text leaves room for the escape codes, so that we rarely have
+    // to grow the buffer.
+    //
+    // Allocation counts for a 91k lines `git log -p`, with output byte for
+    // byte the same:
+    //
+    // String per row and per style change => 938k allocations, 0.19s
+    // One buffer for all rows              => 803k allocations, 0.19s
+    let text_length: usize = tokens.iter().map(|token| token.token.len()).sum();
+    let mut rendered = String::with_capacity(2 * text_length);
 
     let mut current_row_start = 0;
+    let mut row_index = 0;
     for (i, token) in tokens.iter().enumerate() {
         if token.token == "\n" {
-            let rendered_row = &render_row(line_style, &tokens[current_row_start..i]);
-            rendered.push_str(rendered_row);
+            render_row(
+                row_style(row_index),
+                palette,
+                theme,
+                &tokens[current_row_start..i],
+                &mut rendered,
+            );
             rendered.push('\n');
             current_row_start = i + 1;
+            row_index += 1;
             continue;
         }
     }
 
     if current_row_start < tokens.len() {
-        let rendered_row = &render_row(line_style, &tokens[current_row_start..]);
-        rendered.push_str(rendered_row);
+        render_row(
+            row_style(row_index),
+            palette,
+            theme,
+            &tokens[current_row_start..],
+            &mut rendered,
+        );
     }
 
     return rendered;
@@ -236,6 +415,10 @@ pub fn render(line_style: &LineStyle, tokens: Vec<StyledToken>) -> String {
 
 /// Unhighlight rows that have too much highlighting.
 ///
+/// This counts tokens rather than columns on purpose. Measured in columns, a
+/// single renamed identifier is most of its line and would lose its
+/// highlighting.
+///
 /// Returns true if something was unhighlighted, false otherwise.
 pub fn unhighlight_noisy_rows(tokens: &mut [StyledToken]) -> bool {
     fn maybe_unhighlight_row(row: &mut [StyledToken], highlighted_tokens_count: usize) -> bool {
@@ -283,7 +466,238 @@ pub fn unhighlight_noisy_rows(tokens: &mut [StyledToken]) -> bool {
     return changed;
 }
 
-pub fn highlight_trailing_whitespace(tokens: &mut [StyledToken]) {
+/// Paired lines at least this similar, in percent of unhighlighted tokens,
+/// get their highlights narrowed down to the changed characters
+const CHARACTER_GRANULARITY_MIN_SIMILARITY: usize = 80;
+
+/// Paired lines less similar than this are shown without highlights, since
+/// highlighting most of a line doesn't help reading it
+const TOKEN_GRANULARITY_MIN_SIMILARITY: usize = 30;
+
+/// Index ranges of the rows of `tokens`, without their linefeeds
+fn row_ranges(tokens: &[StyledToken]) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut row_start = 0;
+    for (index, token) in tokens.iter().enumerate() {
+        if token.token == "\n" {
+            ranges.push(row_start..index);
+            row_start = index + 1;
+        }
+    }
+    ranges.push(row_start..tokens.len());
+    return ranges;
+}
+
+/// For each row, whether all of it was highlighted as added or removed, so
+/// that it has no counterpart on the other side. Call this before any
+/// highlights are removed for readability.
+///
+/// Whitespace only rows count as unpaired if a linefeed next to them is
+/// highlighted, since the diff can pick either of those as the added one.
+#[must_use]
+pub fn unpaired_rows(tokens: &[StyledToken]) -> Vec<bool> {
+    let is_highlighted_linefeed = |index: usize| return tokens[index].style == Style::Highlighted;
+
+    let mut rows = row_ranges(tokens);
+    if rows.last().is_some_and(|row| row.is_empty()) {
+        // Nothing after the last linefeed, that's not a row
+        let _ = rows.pop();
+    }
+
+    return rows
+        .into_iter()
+        .map(|row| {
+            let mut text_tokens = tokens[row.clone()]
+                .iter()
+                .filter(|token| !token.is_whitespace())
+                .peekable();
+            if text_tokens.peek().is_some() {
+                return text_tokens.all(|token| token.style == Style::Highlighted);
+            }
+
+            let before = row
+                .start
+                .checked_sub(1)
+                .is_some_and(is_highlighted_linefeed);
+            let after = row.end < tokens.len() && is_highlighted_linefeed(row.end);
+            return before || after;
+        })
+        .collect();
+}
+
+/// Index ranges of consecutive highlighted tokens
+fn highlighted_runs(row: &[StyledToken]) -> Vec<Range<usize>> {
+    let mut runs: Vec<Range<usize>> = Vec::new();
+    for (index, token) in row.iter().enumerate() {
+        if token.style != Style::Highlighted {
+            continue;
+        }
+        match runs.last_mut() {
+            Some(run) if run.end == index => run.end += 1,
+            _ => runs.push(index..index + 1),
+        }
+    }
+    return runs;
+}
+
+/// Split a token into an unchanged prefix, a highlighted middle and an
+/// unchanged suffix, all of the token's class. Empty parts are left out.
+fn split_token(
+    token: &StyledToken,
+    prefix_length: usize,
+    suffix_length: usize,
+) -> Vec<StyledToken> {
+    let chars: Vec<char> = token.token.chars().collect();
+    let middle_end = chars.len() - suffix_length;
+
+    let mut tokens = Vec::with_capacity(3);
+    for (range, style) in [
+        (0..prefix_length, Style::Plain),
+        (prefix_length..middle_end, Style::Highlighted),
+        (middle_end..chars.len(), Style::Plain),
+    ] {
+        if range.is_empty() {
+            continue;
+        }
+        let mut part = StyledToken::new(chars[range].iter().collect(), style);
+        part.set_class(token.class);
+        tokens.push(part);
+    }
+    return tokens;
+}
+
+/// For a line where a single word changed, like a typo fix, only highlight the
+/// characters that changed within the word.
+///
+/// Operators, punctuation and multi token changes are left alone, those are
+/// as small as they get already.
+///
+/// Returns the new rows.
+fn narrow_to_changed_characters(
+    old_row: &[StyledToken],
+    new_row: &[StyledToken],
+) -> Option<(Vec<StyledToken>, Vec<StyledToken>)> {
+    let old_runs = highlighted_runs(old_row);
+    let new_runs = highlighted_runs(new_row);
+    if old_runs.len() != 1 || new_runs.len() != 1 {
+        return None;
+    }
+    let (old_run, new_run) = (old_runs[0].clone(), new_runs[0].clone());
+    if old_run.len() != 1 || new_run.len() != 1 {
+        return None;
+    }
+    let (old_token, new_token) = (&old_row[old_run.start], &new_row[new_run.start]);
+    let is_word = |token: &StyledToken| return token.token.chars().all(char::is_alphanumeric);
+    if !is_word(old_token) || !is_word(new_token) {
+        return None;
+    }
+
+    let old_chars: Vec<char> = old_token.token.chars().collect();
+    let new_chars: Vec<char> = new_token.token.chars().collect();
+
+    let prefix_length = old_chars
+        .iter()
+        .zip(new_chars.iter())
+        .take_while(|(old, new)| old == new)
+        .count();
+    let max_suffix_length = old_chars.len().min(new_chars.len()) - prefix_length;
+    let suffix_length = old_chars
+        .iter()
+        .rev()
+        .zip(new_chars.iter().rev())
+        .take(max_suffix_length)
+        .take_while(|(old, new)| old == new)
+        .count();
+    if prefix_length + suffix_length == 0 {
+        // Nothing in common, tokens are as good as it gets
+        return None;
+    }
+
+    let splice = |row: &[StyledToken], index: usize| {
+        let mut spliced = row[..index].to_vec();
+        spliced.extend(split_token(&row[index], prefix_length, suffix_length));
+        spliced.extend_from_slice(&row[index + 1..]);
+        return spliced;
+    };
+    return Some((
+        splice(old_row, old_run.start),
+        splice(new_row, new_run.start),
+    ));
+}
+
+/// Pick highlighting granularity line by line, when the old and new texts
+/// have the same number of lines so that line N of the old text can be
+/// compared to line N of the new text.
+///
+/// With `narrow_characters`, lines that are almost the same get their
+/// highlights narrowed down to the changed characters. Lines that differ a
+/// lot get no highlights at all unless `noisy_lines` says to keep them, and
+/// other lines keep their token highlights.
+///
+/// Returns `None` if the lines can't be paired. Otherwise returns true if
+/// something was unhighlighted in the new text.
+pub fn adapt_granularity(
+    old_tokens: &mut Vec<StyledToken>,
+    new_tokens: &mut Vec<StyledToken>,
+    noisy_lines: NoisyLines,
+    narrow_characters: bool,
+) -> Option<bool> {
+    let old_rows = row_ranges(old_tokens);
+    let new_rows = row_ranges(new_tokens);
+    if old_rows.len() != new_rows.len() {
+        return None;
+    }
+
+    let mut adapted_old: Vec<StyledToken> = Vec::with_capacity(old_tokens.len());
+    let mut adapted_new: Vec<StyledToken> = Vec::with_capacity(new_tokens.len());
+    let mut new_unhighlighted = false;
+    for (index, (old_row, new_row)) in old_rows.into_iter().zip(new_rows).enumerate() {
+        if index > 0 {
+            // The linefeed before this row
+            adapted_old.push(old_tokens[old_row.start - 1].clone());
+            adapted_new.push(new_tokens[new_row.start - 1].clone());
+        }
+        let mut old_row = old_tokens[old_row].to_vec();
+        let mut new_row = new_tokens[new_row].to_vec();
+
+        let plain_count = old_row
+            .iter()
+            .chain(new_row.iter())
+            .filter(|token| token.style == Style::Plain)
+            .count();
+        let total_count = old_row.len() + new_row.len();
+        let similarity = (100 * plain_count).checked_div(total_count).unwrap_or(100);
+
+        if similarity < TOKEN_GRANULARITY_MIN_SIMILARITY && noisy_lines == NoisyLines::Unhighlight {
+            // Whole line
+            for token in old_row.iter_mut() {
+                token.style = Style::Plain;
+            }
+            for token in new_row.iter_mut() {
+                if token.style == Style::Highlighted {
+                    token.style = Style::Plain;
+                    new_unhighlighted = true;
+                }
+            }
+        } else if narrow_characters && similarity >= CHARACTER_GRANULARITY_MIN_SIMILARITY {
+            if let Some((narrowed_old, narrowed_new)) =
+                narrow_to_changed_characters(&old_row, &new_row)
+            {
+                old_row = narrowed_old;
+                new_row = narrowed_new;
+            }
+        }
+
+        adapted_old.extend(old_row);
+        adapted_new.extend(new_row);
+    }
+
+    *old_tokens = adapted_old;
+    *new_tokens = adapted_new;
+    return Some(new_unhighlighted);
+}
+
+pub fn highlight_trailing_whitespace(tokens: &mut [StyledToken], style: Style) {
     let mut in_trailer = true;
     for token in tokens.iter_mut().rev() {
         if token.token == "\n" {
@@ -291,8 +705,13 @@ pub fn highlight_trailing_whitespace(tokens: &mut [StyledToken]) {
             continue;
         }
 
+        if in_trailer && token.token == CR_MARKER {
+            // Whitespace before a CR LF line ending is still trailing
+            continue;
+        }
+
         if in_trailer && token.is_whitespace() {
-            token.style = Style::Error;
+            token.style = style;
             continue;
         }
 
@@ -318,6 +737,88 @@ pub fn highlight_nonleading_tabs(tokens: &mut [StyledToken]) {
     }
 }
 
+/// Returns the closing bracket for an opening bracket
+#[must_use]
+fn closing_bracket(token: &str) -> Option<&'static str> {
+    return match token {
+        "(" => Some(")"),
+        "[" => Some("]"),
+        "{" => Some("}"),
+        _ => None,
+    };
+}
+
+/// When wrapping something in brackets, like `f(x)` becoming `g(f(x))`, the
+/// diff can't tell which of the `))` closing brackets is the new one, and
+/// often picks the wrong one.
+///
+/// Here we move highlights between consecutive identical closing brackets, so
+/// that highlighted opening brackets get their matching closing brackets
+/// highlighted.
+pub fn pair_highlighted_brackets(tokens: &mut [StyledToken]) {
+    // Find the opening bracket for each closing bracket
+    let mut opening_indices: Vec<Option<usize>> = vec![None; tokens.len()];
+    let mut stack: Vec<usize> = Vec::new();
+    for (index, token) in tokens.iter().enumerate() {
+        if closing_bracket(&token.token).is_some() {
+            stack.push(index);
+            continue;
+        }
+
+        if let Some(&opening_index) = stack.last() {
+            if closing_bracket(&tokens[opening_index].token) == Some(token.token.as_str()) {
+                stack.pop();
+                opening_indices[index] = Some(opening_index);
+            }
+        }
+    }
+
+    let mut run_start = 0;
+    while run_start < tokens.len() {
+        let mut run_end = run_start + 1;
+        while run_end < tokens.len() && tokens[run_end].token == tokens[run_start].token {
+            run_end += 1;
+        }
+
+        let run = run_start..run_end;
+        run_start = run_end;
+        if run.len() < 2 || opening_indices[run.start].is_none() {
+            // Not a run of closing brackets
+            continue;
+        }
+        if !tokens[run.clone()]
+            .iter()
+            .all(|token| token.style == Style::Plain || token.style == Style::Highlighted)
+        {
+            continue;
+        }
+
+        let wanted: Vec<bool> = run
+            .clone()
+            .map(|index| {
+                opening_indices[index]
+                    .is_some_and(|opening| tokens[opening].style == Style::Highlighted)
+            })
+            .collect();
+        let highlighted_count = tokens[run.clone()]
+            .iter()
+            .filter(|token| token.style == Style::Highlighted)
+            .count();
+        if wanted.iter().filter(|wanted| **wanted).count() != highlighted_count {
+            // Not just a matter of moving highlights around, leave it be
+            continue;
+        }
+
+        for (index, wanted) in run.zip(wanted) {
+            tokens[index].style = if wanted {
+                Style::Highlighted
+            } else {
+                Style::Plain
+            };
+        }
+    }
+}
+
 /// Highlight single space between two highlighted tokens
 pub fn bridge_consecutive_highlighted_tokens(tokens: &mut [StyledToken]) {
     enum FoundState {
@@ -389,41 +890,22 @@ pub fn count_lines(tokens: &[StyledToken]) -> usize {
     return lines;
 }
 
-/// File timestamps are found after either a tab character or a double space
+/// File timestamps are found after either a tab character or a double space.
+/// Labels from `diff --label` have no timestamps, but can have tabs and double
+/// spaces, see [`file_timestamp::start`].
 pub fn lowlight_timestamp(row: &mut [StyledToken]) {
-    #[derive(PartialEq)]
-    enum State {
-        Initial,
-        FoundOneSpace,
-        InTimestamp,
-    }
+    let name: String = row.iter().map(|token| token.token.as_str()).collect();
+    let timestamp_start = match file_timestamp::start(&name) {
+        Some(timestamp_start) => timestamp_start,
+        None => return,
+    };
 
-    let mut state = State::Initial;
+    let mut token_start = 0;
     for token in row.iter_mut() {
-        match state {
-            State::Initial => {
-                if token.token == "\t" {
-                    state = State::InTimestamp;
-                } else if token.token == " " {
-                    state = State::FoundOneSpace;
-                }
-            }
-            State::FoundOneSpace => {
-                if token.token == " " {
-                    state = State::InTimestamp;
-                } else {
-                    state = State::Initial;
-                }
-            }
-            State::InTimestamp => {
-                // Intentionally left blank, no way out of this state
-            }
-        }
-
-        if state == State::InTimestamp {
+        if token_start >= timestamp_start {
             token.style = Style::Lowlighted;
-            continue;
         }
+        token_start += token.token.len();
     }
 }
 
@@ -445,13 +927,40 @@ pub fn unhighlight_git_prefix(row: &mut [StyledToken]) {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::constants::FAINT;
+    use crate::constants::INVERSE_VIDEO;
     use crate::constants::NEW;
     use crate::constants::NORMAL;
+    use crate::constants::NORMAL_INTENSITY;
+    use crate::constants::NOT_INVERSE_VIDEO;
     use crate::constants::OLD;
 
     #[cfg(test)]
     use pretty_assertions::assert_eq;
 
+    #[test]
+    fn test_unpaired_rows() {
+        let tokens = [
+            ("a", Style::Plain),
+            ("\n", Style::Plain),
+            ("  ", Style::Highlighted),
+            ("b", Style::Highlighted),
+            ("\n", Style::Highlighted),
+            ("\n", Style::Plain),
+            ("c", Style::Highlighted),
+            (" ", Style::Plain),
+            ("d", Style::Plain),
+            ("\n", Style::Plain),
+        ]
+        .iter()
+        .map(|(token, style)| StyledToken::new(token.to_string(), *style))
+        .collect::<Vec<_>>();
+
+        // The blank row is next to a highlighted linefeed
+        assert_eq!(unpaired_rows(&tokens), [false, true, true, false]);
+        assert_eq!(unpaired_rows(&[]), Vec::<bool>::new());
+    }
+
     #[test]
     fn test_highlight_nonleading_tabs() {
         let mut tokens = vec![
@@ -481,25 +990,45 @@ mod tests {
     fn test_basic() {
         let rendered = render(
             &LINE_STYLE_NEW,
+            &Palette::default(),
+            &Theme::default(),
             vec![
-                StyledToken {
-                    token: "hej".to_string(),
-                    style: Style::Plain,
-                },
-                StyledToken {
-                    token: "\n".to_string(),
-                    style: Style::Plain,
-                },
+                StyledToken::new("hej".to_string(), Style::Plain),
+                StyledToken::new("\n".to_string(), Style::Plain),
             ],
         );
         assert_eq!(rendered, format!("{NEW}+hej{NORMAL}\n"));
     }
 
+    #[test]
+    fn test_themed_classes() {
+        let mut theme = Theme::default();
+        theme.set_weight(TokenClass::Punctuation, Weight::Faint);
+
+        let rendered = render(
+            &LINE_STYLE_NEW,
+            &Palette::default(),
+            &theme,
+            vec![
+                StyledToken::new("f".to_string(), Style::Plain),
+                StyledToken::new("(".to_string(), Style::Plain),
+                StyledToken::new("x".to_string(), Style::Highlighted),
+                StyledToken::new(")".to_string(), Style::Plain),
+            ],
+        );
+
+        // Unchanged punctuation is faint, highlighted tokens are not affected
+        assert_eq!(
+            rendered,
+            format!("{NEW}+f{FAINT}({INVERSE_VIDEO}{NORMAL_INTENSITY}x{NOT_INVERSE_VIDEO}{FAINT}){NORMAL}")
+        );
+    }
+
     #[test]
     fn test_add_trailing_whitespace() {
         // Just a whitespace
         let mut row = [StyledToken::new(" ".to_string(), Style::Plain)];
-        highlight_trailing_whitespace(&mut row);
+        highlight_trailing_whitespace(&mut row, Style::Error);
         assert_eq!(row, [StyledToken::new(" ".to_string(), Style::Error)]);
 
         // Trailing whitespace
@@ -507,7 +1036,7 @@ mod tests {
             StyledToken::new("x".to_string(), Style::Plain),
             StyledToken::new(" ".to_string(), Style::Plain),
         ];
-        highlight_trailing_whitespace(&mut row);
+        highlight_trailing_whitespace(&mut row, Style::Error);
         assert_eq!(
             row,
             [
@@ -521,7 +1050,7 @@ mod tests {
             StyledToken::new(" ".to_string(), Style::Plain),
             StyledToken::new("x".to_string(), Style::Plain),
         ];
-        highlight_trailing_whitespace(&mut row);
+        highlight_trailing_whitespace(&mut row, Style::Error);
         assert_eq!(
             row,
             [
@@ -536,6 +1065,8 @@ mod tests {
         // It shouldn't be highlighted, just added ones should
         let actual = render(
             &LINE_STYLE_OLD,
+            &Palette::default(),
+            &Theme::default(),
             vec![StyledToken::new(" ".to_string(), Style::Plain)],
         );
 
@@ -599,6 +1130,8 @@ mod tests {
         // It shouldn't be highlighted, just added ones should
         let actual = render(
             &LINE_STYLE_OLD,
+            &Palette::default(),
+            &Theme::default(),
             vec![
                 StyledToken::new("x".to_string(), Style::Plain),
                 StyledToken::new("\t".to_string(), Style::Plain),
@@ -608,6 +1141,141 @@ mod tests {
         assert_eq!(actual, format!("{OLD}-x\t{NORMAL}"));
     }
 
+    #[test]
+    fn test_pair_highlighted_brackets() {
+        fn tokens(highlights: &[(&str, Style)]) -> Vec<StyledToken> {
+            return highlights
+                .iter()
+                .map(|(token, style)| StyledToken::new(token.to_string(), *style))
+                .collect();
+        }
+
+        // "f(x)" -> "g(f(x))", with the diff highlighting the inner ")"
+        let mut row = tokens(&[
+            ("g", Style::Highlighted),
+            ("(", Style::Highlighted),
+            ("f", Style::Plain),
+            ("(", Style::Plain),
+            ("x", Style::Plain),
+            (")", Style::Highlighted),
+            (")", Style::Plain),
+        ]);
+        pair_highlighted_brackets(&mut row);
+        assert_eq!(
+            row,
+            tokens(&[
+                ("g", Style::Highlighted),
+                ("(", Style::Highlighted),
+                ("f", Style::Plain),
+                ("(", Style::Plain),
+                ("x", Style::Plain),
+                (")", Style::Plain),
+                (")", Style::Highlighted),
+            ])
+        );
+
+        // Unbalanced highlights are left alone
+        let mut row = tokens(&[
+            ("(", Style::Plain),
+            ("(", Style::Plain),
+            (")", Style::Highlighted),
+            (")", Style::Plain),
+        ]);
+        let before = row.clone();
+        pair_highlighted_brackets(&mut row);
+        assert_eq!(row, before);
+    }
+
+    #[test]
+    fn test_adapt_granularity() {
+        fn tokens(highlights: &[(&str, Style)]) -> Vec<StyledToken> {
+            return highlights
+                .iter()
+                .map(|(token, style)| StyledToken::new(token.to_string(), *style))
+                .collect();
+        }
+        fn highlights(tokens: &[StyledToken]) -> Vec<(&str, Style)> {
+            return tokens
+                .iter()
+                .map(|token| (token.token(), token.style))
+                .collect();
+        }
+
+        // Almost the same, highlight only the changed characters
+        let plain = |token| (token, Style::Plain);
+        let mut old = tokens(&[
+            ("x", Style::Plain),
+            (" ", Style::Plain),
+            ("=", Style::Plain),
+            (" ", Style::Plain),
+            ("recieve", Style::Highlighted),
+            ("(", Style::Plain),
+            (")", Style::Plain),
+            (";", Style::Plain),
+            ("\n", Style::Plain),
+            ("a", Style::Highlighted),
+        ]);
+        let mut new = tokens(&[
+            ("x", Style::Plain),
+            (" ", Style::Plain),
+            ("=", Style::Plain),
+            (" ", Style::Plain),
+            ("receive", Style::Highlighted),
+            ("(", Style::Plain),
+            (")", Style::Plain),
+            (";", Style::Plain),
+            ("\n", Style::Plain),
+            ("b", Style::Highlighted),
+        ]);
+        let (mut kept_old, mut kept_new) = (old.clone(), new.clone());
+        assert_eq!(
+            adapt_granularity(&mut old, &mut new, NoisyLines::Unhighlight, true),
+            Some(true)
+        );
+        assert_eq!(
+            highlights(&old),
+            [
+                plain("x"),
+                plain(" "),
+                plain("="),
+                plain(" "),
+                plain("rec"),
+                ("ie", Style::Highlighted),
+                plain("ve"),
+                plain("("),
+                plain(")"),
+                plain(";"),
+                plain("\n"),
+                // Completely different, nothing highlighted
+                plain("a"),
+            ]
+        );
+        assert_eq!(
+            highlights(&new)[4..7].to_vec(),
+            [plain("rec"), ("ei", Style::Highlighted), plain("ve")]
+        );
+        assert_eq!(highlights(&new).last(), Some(&plain("b")));
+
+        // Unless asked to keep the highlights of completely different lines
+        assert_eq!(
+            adapt_granularity(&mut kept_old, &mut kept_new, NoisyLines::Keep, true),
+            Some(false)
+        );
+        assert_eq!(highlights(&kept_old)[5], ("ie", Style::Highlighted));
+        assert_eq!(
+            kept_old.last(),
+            Some(&StyledToken::new("a".to_string(), Style::Highlighted))
+        );
+
+        // Different numbers of lines can't be paired
+        let mut old = tokens(&[("a", Style::Plain)]);
+        let mut new = tokens(&[("a", Style::Plain), ("\n", Style::Highlighted)]);
+        assert_eq!(
+            adapt_granularity(&mut old, &mut new, NoisyLines::Unhighlight, true),
+            None
+        );
+    }
+
     #[test]
     fn test_highlight_space_between_words() {
         let mut row = [
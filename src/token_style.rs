@@ -0,0 +1,114 @@
+use crate::ansi::Weight;
+use crate::tokenizer::TokenClass;
+use regex::Regex;
+
+/// What we know about an unchanged token when styling it
+pub struct TokenContext {
+    pub class: TokenClass,
+}
+
+/// How to style a token instead of what its class says
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct StyleOverride {
+    pub weight: Weight,
+}
+
+/// Custom styling of unchanged tokens, on top of the theme's token class
+/// weights.
+///
+/// Themes ask their stylers in order, and the first one to return an override
+/// wins. The `[theme.rules]` regexes are the only stylers for now.
+pub trait TokenStyler {
+    fn style(&self, token: &str, context: &TokenContext) -> Option<StyleOverride>;
+}
+
+/// A `[theme.rules]` entry, styling whole tokens matching a regex, optionally
+/// only tokens of one class
+#[derive(Clone, Debug)]
+pub struct TokenStyleRule {
+    pub name: String,
+    regex: Regex,
+    class: Option<TokenClass>,
+    weight: Weight,
+}
+
+impl TokenStyleRule {
+    /// The regex has to match the whole token
+    pub fn new(
+        name: &str,
+        regex: &str,
+        class: Option<TokenClass>,
+        weight: Weight,
+    ) -> Result<TokenStyleRule, regex::Error> {
+        return Ok(TokenStyleRule {
+            name: name.to_string(),
+            regex: Regex::new(&format!("^(?:{regex})$"))?,
+            class,
+            weight,
+        });
+    }
+}
+
+impl PartialEq for TokenStyleRule {
+    fn eq(&self, other: &Self) -> bool {
+        return self.name == other.name
+            && self.regex.as_str() == other.regex.as_str()
+            && self.class == other.class
+            && self.weight == other.weight;
+    }
+}
+
+impl Eq for TokenStyleRule {}
+
+impl TokenStyler for TokenStyleRule {
+    fn style(&self, token: &str, context: &TokenContext) -> Option<StyleOverride> {
+        if self.class.is_some_and(|class| class != context.class) {
+            return None;
+        }
+        if !self.regex.is_match(token) {
+            return None;
+        }
+        return Some(StyleOverride {
+            weight: self.weight,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_token_style_rule() {
+        let rule = TokenStyleRule::new(
+            "todo",
+            "TODO|FIXME",
+            Some(TokenClass::Identifier),
+            Weight::Bold,
+        )
+        .unwrap();
+        let context = TokenContext {
+            class: TokenClass::Identifier,
+        };
+
+        assert_eq!(
+            rule.style("FIXME", &context),
+            Some(StyleOverride {
+                weight: Weight::Bold
+            })
+        );
+
+        // Only whole tokens match
+        assert_eq!(rule.style("TODOS", &context), None);
+        assert_eq!(rule.style("todo", &context), None);
+
+        // Only tokens of the rule's class match
+        let context = TokenContext {
+            class: TokenClass::String,
+        };
+        assert_eq!(rule.style("TODO", &context), None);
+    }
+}
@@ -1,3 +1,5 @@
+use regex::Regex;
+
 // FIXME: The doctest doesn't run on `cargo test`, why?
 /// Splits string into a vector of words. A word is any sequence of alphanumeric
 /// characters. Non-words get into the vector one and one.
@@ -42,6 +44,174 @@ pub fn tokenize(input: &str) -> Vec<&str> {
     return result;
 }
 
+/// Splits lines into the matches of `word_regex`, like git's
+/// `--word-diff-regex`. Text between matches gets into the vector one
+/// character at a time, and so do linefeeds.
+pub fn tokenize_words<'a>(input: &'a str, word_regex: &Regex) -> Vec<&'a str> {
+    let mut result: Vec<&str> = Vec::with_capacity(input.len());
+    fn push_characters<'a>(text: &'a str, result: &mut Vec<&'a str>) {
+        for (index, character) in text.char_indices() {
+            result.push(&text[index..index + character.len_utf8()]);
+        }
+    }
+
+    for line in input.split_inclusive('\n') {
+        let text = line.strip_suffix('\n').unwrap_or(line);
+        let mut end = 0;
+        for word in word_regex.find_iter(text) {
+            if word.as_str().is_empty() {
+                continue;
+            }
+            push_characters(&text[end..word.start()], &mut result);
+            result.push(word.as_str());
+            end = word.end();
+        }
+        push_characters(&line[end..], &mut result);
+    }
+
+    return result;
+}
+
+/// Split identifier tokens into their camelCase and snake_case segments, so
+/// that refining `getUserName` -> `getUserEmail` highlights only `Email`.
+///
+/// Underscores stay with the segment before them, so `user_name` becomes
+/// `user_` and `name`. Other tokens are passed through.
+#[must_use]
+pub fn split_identifiers<'a>(tokens: &[&'a str]) -> Vec<&'a str> {
+    let mut result: Vec<&str> = Vec::with_capacity(tokens.len());
+    for token in tokens {
+        if TokenClass::of(token) == TokenClass::Identifier {
+            push_identifier_segments(token, &mut result);
+        } else {
+            result.push(token);
+        }
+    }
+    return result;
+}
+
+fn push_identifier_segments<'a>(identifier: &'a str, result: &mut Vec<&'a str>) {
+    let chars: Vec<(usize, char)> = identifier.char_indices().collect();
+    let mut segment_start = 0;
+    for (i, (index, c)) in chars.iter().enumerate().skip(1) {
+        let previous = chars[i - 1].1;
+        let next = chars.get(i + 1).map(|(_, next)| *next);
+
+        let after_underscores = previous == '_' && *c != '_';
+
+        // "userName" and "HTTPServer"
+        let camel_hump = c.is_uppercase()
+            && (previous.is_lowercase()
+                || previous.is_ascii_digit()
+                || (previous.is_uppercase() && next.is_some_and(char::is_lowercase)));
+
+        if after_underscores || camel_hump {
+            result.push(&identifier[segment_start..*index]);
+            segment_start = *index;
+        }
+    }
+    result.push(&identifier[segment_start..]);
+}
+
+/// What kind of text a token contains. Used for styling tokens differently
+/// depending on what they are, see the `[theme]` config section.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum TokenClass {
+    Identifier,
+    Number,
+    Punctuation,
+    Whitespace,
+
+    /// Anything inside quotes, including the quotes
+    String,
+}
+
+impl TokenClass {
+    pub const ALL: [TokenClass; 5] = [
+        TokenClass::Identifier,
+        TokenClass::Number,
+        TokenClass::Punctuation,
+        TokenClass::Whitespace,
+        TokenClass::String,
+    ];
+
+    /// Config file name of this class
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        return match self {
+            TokenClass::Identifier => "identifier",
+            TokenClass::Number => "number",
+            TokenClass::Punctuation => "punctuation",
+            TokenClass::Whitespace => "whitespace",
+            TokenClass::String => "string",
+        };
+    }
+
+    /// Classify a single token without looking at its surroundings. This will
+    /// never return [`TokenClass::String`], use [`classify`] for that.
+    #[must_use]
+    pub fn of(token: &str) -> TokenClass {
+        let first_char = match token.chars().next() {
+            Some(first_char) => first_char,
+            None => return TokenClass::Whitespace,
+        };
+
+        if first_char.is_whitespace() {
+            return TokenClass::Whitespace;
+        }
+        if first_char.is_ascii_digit() {
+            // Includes things like 0xC0deCafe and 12px
+            return TokenClass::Number;
+        }
+        if first_char == '_' || first_char.is_alphanumeric() {
+            return TokenClass::Identifier;
+        }
+        return TokenClass::Punctuation;
+    }
+}
+
+/// Classify the tokens returned by [`tokenize`].
+///
+/// Tokens between quotes on the same line are classified as strings.
+/// Backslash escaped quotes don't end strings.
+#[must_use]
+pub fn classify(tokens: &[&str]) -> Vec<TokenClass> {
+    let mut classes = Vec::with_capacity(tokens.len());
+    let mut open_quote: Option<&str> = None;
+    let mut escaped = false;
+    for token in tokens {
+        if *token == "\n" {
+            // Unterminated strings end at the end of the line
+            open_quote = None;
+            escaped = false;
+            classes.push(TokenClass::Whitespace);
+            continue;
+        }
+
+        if let Some(quote) = open_quote {
+            classes.push(TokenClass::String);
+            if escaped {
+                escaped = false;
+            } else if *token == "\\" {
+                escaped = true;
+            } else if *token == quote {
+                open_quote = None;
+            }
+            continue;
+        }
+
+        if *token == "\"" || *token == "'" || *token == "`" {
+            open_quote = Some(token);
+            classes.push(TokenClass::String);
+            continue;
+        }
+
+        classes.push(TokenClass::of(token));
+    }
+
+    return classes;
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -49,6 +219,34 @@ mod tests {
     #[cfg(test)]
     use pretty_assertions::assert_eq;
 
+    #[test]
+    fn test_split_identifiers() {
+        assert_eq!(
+            split_identifiers(&["getUserName", "(", "user_id", ")"]),
+            ["get", "User", "Name", "(", "user_", "id", ")"]
+        );
+        assert_eq!(
+            split_identifiers(&["HTTPServer", " ", "__init__", " ", "utf8Decode"]),
+            ["HTTP", "Server", " ", "__", "init__", " ", "utf8", "Decode"]
+        );
+        assert_eq!(
+            split_identifiers(&["0xC0deCafe", " ", "CONSTANT_CASE"]),
+            ["0xC0deCafe", " ", "CONSTANT_", "CASE"]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_words() {
+        let regex = Regex::new(r"[a-z]+\.[a-z]+|\d+").unwrap();
+        assert_eq!(
+            tokenize_words("SELECT t.id, 42\nFROM t\n", &regex),
+            [
+                "S", "E", "L", "E", "C", "T", " ", "t.id", ",", " ", "42", "\n", "F", "R", "O",
+                "M", " ", "t", "\n"
+            ]
+        );
+    }
+
     #[test]
     fn test_empty() {
         let no_strings: Vec<String> = Vec::new();
@@ -77,6 +275,40 @@ mod tests {
         assert_eq!(tokenize("+!,"), ["+", "!", ","]);
     }
 
+    #[test]
+    fn test_classify() {
+        use TokenClass::*;
+
+        let tokens = tokenize("x = f(12, \"a \\\" b\");\n'c");
+        assert_eq!(
+            classify(&tokens),
+            [
+                Identifier,  // x
+                Whitespace,  //
+                Punctuation, // =
+                Whitespace,  //
+                Identifier,  // f
+                Punctuation, // (
+                Number,      // 12
+                Punctuation, // ,
+                Whitespace,  //
+                String,      // "
+                String,      // a
+                String,      //
+                String,      // \
+                String,      // "
+                String,      //
+                String,      // b
+                String,      // "
+                Punctuation, // )
+                Punctuation, // ;
+                Whitespace,  // \n
+                String,      // '
+                String,      // c
+            ]
+        );
+    }
+
     #[test]
     fn test_non_breaking_space() {
         assert_eq!(tokenize("\u{00a0}"), ["\u{00a0}"]);
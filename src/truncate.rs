@@ -0,0 +1,115 @@
+use crate::ansi::parts;
+use crate::display_width::char_width;
+use crate::palette::Palette;
+use std::borrow::Cow;
+
+/// Truncate a highlighted line so that it fits in `max_width` columns,
+/// marking the truncation with an ellipsis.
+///
+/// Widths are display widths, so wide characters take two columns and ANSI
+/// escape sequences take none. Truncated lines end with all styling reset.
+#[must_use]
+pub fn truncate<'a>(line: &'a str, max_width: usize, palette: &Palette) -> Cow<'a, str> {
+    let mut column = 0;
+    for c in parts(line).flatten() {
+        column += char_width(c, column);
+    }
+    if column <= max_width {
+        return Cow::Borrowed(line);
+    }
+
+    // Leave room for the ellipsis
+    let text_width = max_width.saturating_sub(1);
+
+    let mut truncated = String::with_capacity(line.len());
+    let mut column = 0;
+    for part in parts(line) {
+        match part {
+            Err(escape) => truncated.push_str(escape),
+            Ok(c) => {
+                let c_width = char_width(c, column);
+                if column + c_width > text_width {
+                    break;
+                }
+                column += c_width;
+                truncated.push(c);
+            }
+        }
+    }
+
+    truncated.push_str(&palette.normal);
+    if max_width > 0 {
+        truncated.push_str(&palette.faint);
+        truncated.push('…');
+        truncated.push_str(&palette.normal);
+    }
+
+    return Cow::Owned(truncated);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::*;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_short_enough() {
+        let palette = Palette::default();
+        assert_eq!(truncate("", 5, &palette), "");
+        assert_eq!(truncate("12345", 5, &palette), "12345");
+        assert_eq!(
+            truncate(&format!("{OLD}-1234{NORMAL}"), 5, &palette),
+            format!("{OLD}-1234{NORMAL}")
+        );
+    }
+
+    #[test]
+    fn test_truncate() {
+        let palette = Palette::default();
+        assert_eq!(
+            truncate("123456", 5, &palette),
+            format!("1234{NORMAL}{FAINT}…{NORMAL}")
+        );
+
+        // Escape codes don't count, and are closed by the final NORMAL
+        assert_eq!(
+            truncate(&format!("{NEW}+{INVERSE_VIDEO}23456{NORMAL}"), 5, &palette),
+            format!("{NEW}+{INVERSE_VIDEO}234{NORMAL}{FAINT}…{NORMAL}")
+        );
+    }
+
+    #[test]
+    fn test_wide_characters() {
+        let palette = Palette::default();
+
+        // Each of these takes two columns, and we can't split them
+        assert_eq!(
+            truncate("日本語です", 6, &palette),
+            format!("日本{NORMAL}{FAINT}…{NORMAL}")
+        );
+    }
+
+    #[test]
+    fn test_hyperlinks() {
+        let palette = Palette::default();
+        let link = "\x1b]8;;https://example.com/\x1b\\abc\x1b]8;;\x1b\\";
+        assert_eq!(truncate(link, 3, &palette), link);
+        assert_eq!(
+            truncate(&format!("{link}de"), 4, &palette),
+            format!("{link}{NORMAL}{FAINT}…{NORMAL}")
+        );
+    }
+
+    #[test]
+    fn test_tabs() {
+        let palette = Palette::default();
+        assert_eq!(truncate("a\tb", 9, &palette), "a\tb");
+        assert_eq!(
+            truncate("a\tbc", 9, &palette),
+            format!("a\t{NORMAL}{FAINT}…{NORMAL}")
+        );
+    }
+}
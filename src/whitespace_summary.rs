@@ -0,0 +1,161 @@
+use crate::compact;
+use crate::config::Config;
+use crate::hunk::HunkLine;
+use crate::profile::Profile;
+
+/// Whitespace errors in the added lines of one file
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Counts {
+    /// Added lines ending in whitespace
+    pub trailing_whitespace: usize,
+
+    /// TABs after the indentation of added lines, not counting TABs in
+    /// trailing whitespace
+    pub nonleading_tabs: usize,
+}
+
+impl Counts {
+    fn is_empty(&self) -> bool {
+        return self.trailing_whitespace == 0 && self.nonleading_tabs == 0;
+    }
+
+    /// Count the whitespace errors we flag in one added line, following the
+    /// same rules as [`Profile::highlight_whitespace_errors`]
+    fn add_line(&mut self, line: &str, profile: Profile) {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        let code = line.trim_end_matches(char::is_whitespace);
+        let trailer = &line[code.len()..];
+
+        // In Markdown, exactly two trailing spaces are a hard line break
+        let is_hard_line_break = profile == Profile::Markdown && trailer == "  ";
+        if !trailer.is_empty() && !is_hard_line_break {
+            self.trailing_whitespace += 1;
+        }
+
+        if profile != Profile::Tsv {
+            // TABs are what separates the cells in TSV files
+            let indentation_length = code.len() - code.trim_start_matches('\t').len();
+            self.nonleading_tabs += code[indentation_length..].matches('\t').count();
+        }
+    }
+}
+
+/// Whitespace errors in added lines per file, in the order the files came in
+/// the diff. Printed at the end of the output by `--whitespace-summary`.
+#[derive(Debug, Default)]
+pub struct WhitespaceSummary {
+    files: Vec<(String, Counts)>,
+}
+
+impl WhitespaceSummary {
+    pub fn add_hunk(&mut self, file: &str, new_lines: &[HunkLine], profile: Profile) {
+        let mut counts = Counts::default();
+        for line in new_lines {
+            counts.add_line(&line.text, profile);
+        }
+        if counts.is_empty() {
+            return;
+        }
+
+        // Hunks of the same file come one after the other
+        if let Some((last_file, last_counts)) = self.files.last_mut() {
+            if last_file == file {
+                last_counts.trailing_whitespace += counts.trailing_whitespace;
+                last_counts.nonleading_tabs += counts.nonleading_tabs;
+                return;
+            }
+        }
+        self.files.push((file.to_string(), counts));
+    }
+
+    /// One line per file with whitespace errors, `None` if there were none
+    #[must_use]
+    pub fn render(&self, config: &Config) -> Option<String> {
+        if self.files.is_empty() {
+            return None;
+        }
+
+        let palette = &config.palette;
+        let mut rendered = format!(
+            "{}{}{} Whitespace errors in added lines:{}\n",
+            palette.bold,
+            palette.old,
+            compact::warning_sign(config),
+            palette.normal
+        );
+        for (file, counts) in &self.files {
+            let mut problems: Vec<String> = Vec::new();
+            if counts.trailing_whitespace > 0 {
+                problems.push(format!(
+                    "{} trailing whitespace",
+                    counts.trailing_whitespace
+                ));
+            }
+            match counts.nonleading_tabs {
+                0 => {}
+                1 => problems.push("1 non-leading TAB".to_string()),
+                tabs => problems.push(format!("{tabs} non-leading TABs")),
+            }
+            rendered.push_str(&format!("  {file}: {}\n", problems.join(", ")));
+        }
+
+        return Some(rendered);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    fn added(lines: &[&str]) -> Vec<HunkLine> {
+        return lines
+            .iter()
+            .map(|line| HunkLine {
+                text: line.to_string(),
+                ..HunkLine::default()
+            })
+            .collect();
+    }
+
+    #[test]
+    fn test_counts() {
+        let mut summary = WhitespaceSummary::default();
+        summary.add_hunk(
+            "b/src/x.rs",
+            &added(&["\tlet a = 1;  ", "\t\tb\tc\t", "clean"]),
+            Profile::Rust,
+        );
+        summary.add_hunk("b/src/x.rs", &added(&["x\ty"]), Profile::Rust);
+        summary.add_hunk("b/clean.rs", &added(&["fine"]), Profile::Rust);
+        summary.add_hunk(
+            "b/README.md",
+            &added(&["break  ", "a \t"]),
+            Profile::Markdown,
+        );
+        summary.add_hunk("b/data.tsv", &added(&["a\tb"]), Profile::Tsv);
+
+        let config = Config::default();
+        let palette = &config.palette;
+        assert_eq!(
+            summary.render(&config),
+            Some(format!(
+                concat!(
+                    "{}{}⚠ Whitespace errors in added lines:{}\n",
+                    "  b/src/x.rs: 2 trailing whitespace, 2 non-leading TABs\n",
+                    "  b/README.md: 1 trailing whitespace\n",
+                ),
+                palette.bold, palette.old, palette.normal
+            ))
+        );
+    }
+
+    #[test]
+    fn test_no_errors() {
+        let mut summary = WhitespaceSummary::default();
+        summary.add_hunk("b/x.rs", &added(&["\tclean"]), Profile::Rust);
+        assert_eq!(summary.render(&Config::default()), None);
+    }
+}
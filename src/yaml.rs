@@ -0,0 +1,156 @@
+use crate::token_collector::{Style, StyledToken};
+use crate::tokenizer;
+
+/// Keys whose values change on every Kubernetes update without anybody having
+/// changed anything. Lines with these keys get lowlighted.
+const CHURN_KEYS: &[&str] = &[
+    "generation",
+    "observedGeneration",
+    "resourceVersion",
+    "uid",
+    "creationTimestamp",
+];
+
+/// Tokenize YAML so that keys are single tokens.
+///
+/// This way, renaming `app-name:` into `app-id:` highlights the whole key
+/// rather than part of it, while values are refined as usual.
+pub fn tokenize(input: &str) -> Vec<&str> {
+    let mut result: Vec<&str> = Vec::with_capacity(input.len());
+    for line in input.split_inclusive('\n') {
+        if let Some(without_newline) = line.strip_suffix('\n') {
+            tokenize_line(without_newline, &mut result);
+            result.push(&line[without_newline.len()..]);
+        } else {
+            tokenize_line(line, &mut result);
+        }
+    }
+
+    return result;
+}
+
+fn tokenize_line<'a>(line: &'a str, result: &mut Vec<&'a str>) {
+    // Indentation and list item markers
+    let mut rest = line;
+    loop {
+        if let Some(stripped) = rest.strip_prefix(' ') {
+            result.push(" ");
+            rest = stripped;
+        } else if let Some(stripped) = rest.strip_prefix("- ") {
+            result.push("-");
+            result.push(" ");
+            rest = stripped;
+        } else {
+            break;
+        }
+    }
+
+    let key_length = if let Some(index) = rest.find(": ") {
+        Some(index)
+    } else {
+        rest.strip_suffix(':').map(str::len)
+    };
+
+    if let Some(key_length) = key_length {
+        let key = &rest[..key_length];
+        if is_plain_key(key) {
+            result.push(key);
+            rest = &rest[key_length..];
+        }
+    }
+
+    result.append(&mut tokenizer::tokenize(rest));
+}
+
+/// Flow collections (`{`, `[`), comments and such are better left to the
+/// default tokenizer
+fn is_plain_key(key: &str) -> bool {
+    return !key.is_empty() && !key.contains(['{', '[', ',', '#', '"', '\'']);
+}
+
+/// Lowlight lines with keys in `CHURN_KEYS`
+pub fn lowlight_churn(tokens: &mut [StyledToken]) {
+    for row in tokens.split_mut(|token| token.token() == "\n") {
+        let key_index = row
+            .iter()
+            .position(|token| token.token() != " " && token.token() != "-");
+        let key_index = match key_index {
+            Some(key_index) => key_index,
+            None => continue,
+        };
+
+        let is_churn = CHURN_KEYS.contains(&row[key_index].token())
+            && row.get(key_index + 1).map(StyledToken::token) == Some(":");
+        if !is_churn {
+            continue;
+        }
+
+        for token in row.iter_mut() {
+            token.set_style(Style::Lowlighted);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(test)]
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_tokenize_key_value() {
+        assert_eq!(
+            tokenize("  image: nginx:1.14.2\n"),
+            [" ", " ", "image", ":", " ", "nginx", ":", "1", ".", "14", ".", "2", "\n"]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_dashed_key() {
+        assert_eq!(
+            tokenize("- app-name: my-app\n"),
+            ["-", " ", "app-name", ":", " ", "my", "-", "app", "\n"]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_no_value() {
+        assert_eq!(tokenize("metadata:"), ["metadata", ":"]);
+    }
+
+    #[test]
+    fn test_tokenize_flow_collection() {
+        assert_eq!(
+            tokenize("{a-b: c}"),
+            ["{", "a", "-", "b", ":", " ", "c", "}"]
+        );
+    }
+
+    #[test]
+    fn test_lowlight_churn() {
+        let text = "  resourceVersion: \"1234\"\n  replicas: 3\n";
+        let mut tokens: Vec<StyledToken> = tokenize(text)
+            .into_iter()
+            .map(|token| StyledToken::new(token.to_string(), Style::Highlighted))
+            .collect();
+        lowlight_churn(&mut tokens);
+
+        // Only the first line, up to but not including its linefeed, should
+        // have been lowlighted
+        let lowlighted_count = tokenize("  resourceVersion: \"1234\"").len();
+        let expected: Vec<StyledToken> = tokenize(text)
+            .into_iter()
+            .enumerate()
+            .map(|(index, token)| {
+                let style = if index < lowlighted_count {
+                    Style::Lowlighted
+                } else {
+                    Style::Highlighted
+                };
+                StyledToken::new(token.to_string(), style)
+            })
+            .collect();
+        assert_eq!(tokens, expected);
+    }
+}